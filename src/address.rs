@@ -0,0 +1,163 @@
+/// Spreadsheet column letters for a 0-based column index (0 is "A", 26 is "AA").
+pub fn column_name(col: usize) -> String {
+    let mut letters = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        letters.push(b'A' + rem as u8);
+        n = (n - 1) / 26;
+    }
+    letters.reverse();
+    String::from_utf8(letters).unwrap_or_default()
+}
+
+/// A1-style address for a 0-based row and column, e.g. (2, 2) is "C3".
+pub fn cell_address(row: usize, col: usize) -> String {
+    format!("{}{}", column_name(col), row + 1)
+}
+
+/// Sheet name as written in a formula reference, quoted when it contains
+/// anything other than letters, digits and underscores.
+pub fn quote_sheet_name(sheet: &str) -> String {
+    let plain = !sheet.is_empty()
+        && !sheet.starts_with(|c: char| c.is_ascii_digit())
+        && sheet.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if plain {
+        sheet.to_string()
+    } else {
+        format!("'{}'", sheet.replace('\'', "''"))
+    }
+}
+
+/// Full reference to a cell, e.g. `Employees!C3` or `'Q1 Sales'!B2`.
+pub fn sheet_cell_reference(sheet: &str, row: usize, col: usize) -> String {
+    format!("{}!{}", quote_sheet_name(sheet), cell_address(row, col))
+}
+
+/// 0-based row and column of an A1-style address such as "C3" or "$C$3".
+pub fn parse_cell_address(address: &str) -> Option<(usize, usize)> {
+    let address = address.trim();
+    let address = address.strip_prefix('$').unwrap_or(address);
+    let letters_len = address.find(|c: char| !c.is_ascii_alphabetic())?;
+    let (letters, digits) = address.split_at(letters_len);
+    let digits = digits.strip_prefix('$').unwrap_or(digits);
+    if letters.is_empty() || letters.len() > 3 || digits.is_empty() {
+        return None;
+    }
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let col = letters.bytes().fold(0usize, |col, letter| {
+        col * 26 + (letter.to_ascii_uppercase() - b'A') as usize + 1
+    });
+    let row: usize = digits.parse().ok()?;
+    if row == 0 {
+        return None;
+    }
+    Some((row - 1, col - 1))
+}
+
+/// Sheet name as written in a reference, without the quotes `quote_sheet_name` adds.
+pub fn unquote_sheet_name(sheet: &str) -> String {
+    match sheet.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(quoted) => quoted.replace("''", "'"),
+        None => sheet.to_string(),
+    }
+}
+
+/// A cell or range reference such as `B2`, `Sheet1!B2:B10` or `'Q1 Sales'!$A$1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeReference {
+    /// Sheet named in the reference, if any.
+    pub sheet: Option<String>,
+    /// 0-based (row, column) of the top-left and bottom-right cells.
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl RangeReference {
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        (self.start.0..=self.end.0).contains(&row) && (self.start.1..=self.end.1).contains(&col)
+    }
+}
+
+/// Parse a cell or range reference. The corners may be given in either
+/// order; they are returned as top-left and bottom-right.
+pub fn parse_range_reference(reference: &str) -> Option<RangeReference> {
+    let reference = reference.trim();
+    let (sheet, range) = match reference.rsplit_once('!') {
+        Some((sheet, range)) => (Some(unquote_sheet_name(sheet)), range),
+        None => (None, reference),
+    };
+    let (first, last) = match range.split_once(':') {
+        Some((first, last)) => (parse_cell_address(first)?, parse_cell_address(last)?),
+        None => {
+            let cell = parse_cell_address(range)?;
+            (cell, cell)
+        }
+    };
+    Some(RangeReference {
+        sheet,
+        start: (first.0.min(last.0), first.1.min(last.1)),
+        end: (first.0.max(last.0), first.1.max(last.1)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_name() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(701), "ZZ");
+        assert_eq!(column_name(702), "AAA");
+        assert_eq!(column_name(16383), "XFD");
+    }
+
+    #[test]
+    fn test_parse_cell_address() {
+        assert_eq!(parse_cell_address("A1"), Some((0, 0)));
+        assert_eq!(parse_cell_address("c3"), Some((2, 2)));
+        assert_eq!(parse_cell_address("$AA$10"), Some((9, 26)));
+        assert_eq!(parse_cell_address("XFD1048576"), Some((1048575, 16383)));
+        assert_eq!(parse_cell_address("A0"), None);
+        assert_eq!(parse_cell_address("12"), None);
+        assert_eq!(parse_cell_address("B"), None);
+        assert_eq!(parse_cell_address("B2C"), None);
+    }
+
+    #[test]
+    fn test_parse_range_reference() {
+        assert_eq!(
+            parse_range_reference("B2"),
+            Some(RangeReference {
+                sheet: None,
+                start: (1, 1),
+                end: (1, 1),
+            })
+        );
+        assert_eq!(
+            parse_range_reference("'Bob''s Sheet'!$B$10:A2"),
+            Some(RangeReference {
+                sheet: Some("Bob's Sheet".to_string()),
+                start: (1, 0),
+                end: (9, 1),
+            })
+        );
+        assert_eq!(parse_range_reference("Sheet1!B2:"), None);
+        assert_eq!(parse_range_reference("Rates"), None);
+    }
+
+    #[test]
+    fn test_sheet_cell_reference() {
+        assert_eq!(cell_address(2, 2), "C3");
+        assert_eq!(sheet_cell_reference("Employees", 2, 2), "Employees!C3");
+        assert_eq!(sheet_cell_reference("Q1 Sales", 1, 1), "'Q1 Sales'!B2");
+        assert_eq!(sheet_cell_reference("Bob's", 0, 0), "'Bob''s'!A1");
+        assert_eq!(sheet_cell_reference("2024", 0, 0), "'2024'!A1");
+    }
+}
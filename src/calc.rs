@@ -0,0 +1,683 @@
+use crate::address::{sheet_cell_reference, RangeReference};
+use crate::formula::{parse_formula, BinaryOp, Expr};
+use crate::{read_sheet, XlsqError};
+use anyhow::{bail, Context, Result};
+use calamine::{Data, Range, Reader, Xlsx};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Seek};
+
+/// A value produced while evaluating formulas.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    /// An Excel error such as `#DIV/0!` or `#N/A`.
+    Error(String),
+    /// The cells of a range, row by row.
+    Array(Vec<Vec<Value>>),
+}
+
+/// Outcome of an Excel operation; `Err` holds the error value it produced.
+pub(crate) type Eval<T> = std::result::Result<T, Value>;
+
+pub(crate) fn error(code: &str) -> Value {
+    Value::Error(code.to_string())
+}
+
+/// Format a number as Excel shows it in General format, to 15 significant
+/// digits so `0.1+0.2` prints as 0.3.
+pub fn format_number(number: f64) -> String {
+    let rounded: f64 = format!("{:.14e}", number).parse().unwrap_or(number);
+    if rounded.fract() == 0.0 && rounded.abs() < 1e15 {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            Value::Number(number) => f.write_str(&format_number(*number)),
+            Value::Text(text) => f.write_str(text),
+            Value::Bool(true) => f.write_str("TRUE"),
+            Value::Bool(false) => f.write_str("FALSE"),
+            Value::Error(code) => f.write_str(code),
+            Value::Array(_) => f.write_str("#VALUE!"),
+        }
+    }
+}
+
+impl Value {
+    /// Read a value typed on the command line: a number (`0.05` or `5%`),
+    /// TRUE or FALSE, or else text.
+    pub fn from_input(input: &str) -> Value {
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            return Value::Empty;
+        }
+        if let Ok(number) = trimmed.parse::<f64>() {
+            return Value::Number(number);
+        }
+        if let Some(Ok(percent)) = trimmed.strip_suffix('%').map(|n| n.trim().parse::<f64>()) {
+            return Value::Number(percent / 100.0);
+        }
+        if trimmed.eq_ignore_ascii_case("TRUE") {
+            return Value::Bool(true);
+        }
+        if trimmed.eq_ignore_ascii_case("FALSE") {
+            return Value::Bool(false);
+        }
+        Value::Text(input.to_string())
+    }
+
+    fn from_data(data: &Data) -> Value {
+        match data {
+            Data::Empty => Value::Empty,
+            Data::String(text) => Value::Text(text.clone()),
+            Data::Float(number) => Value::Number(*number),
+            Data::Int(number) => Value::Number(*number as f64),
+            Data::Bool(value) => Value::Bool(*value),
+            Data::Error(err) => Value::Error(err.to_string()),
+            Data::DateTime(datetime) => Value::Number(datetime.as_f64()),
+            Data::DateTimeIso(text) | Data::DurationIso(text) => Value::Text(text.clone()),
+        }
+    }
+
+    /// The single value a range stands for where one value is expected:
+    /// its only cell, else `#VALUE!`.
+    pub(crate) fn scalar(self) -> Value {
+        match self {
+            Value::Array(rows) => match rows.as_slice() {
+                [row] if row.len() == 1 => row[0].clone(),
+                _ => error("#VALUE!"),
+            },
+            value => value,
+        }
+    }
+
+    pub(crate) fn as_number(&self) -> Eval<f64> {
+        match self {
+            Value::Empty => Ok(0.0),
+            Value::Number(number) => Ok(*number),
+            Value::Bool(value) => Ok(if *value { 1.0 } else { 0.0 }),
+            Value::Text(text) => text.trim().parse().map_err(|_| error("#VALUE!")),
+            Value::Error(_) => Err(self.clone()),
+            Value::Array(_) => self.clone().scalar().as_number(),
+        }
+    }
+
+    pub(crate) fn as_text(&self) -> Eval<String> {
+        match self {
+            Value::Error(_) => Err(self.clone()),
+            Value::Array(_) => self.clone().scalar().as_text(),
+            value => Ok(value.to_string()),
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> Eval<bool> {
+        match self {
+            Value::Empty => Ok(false),
+            Value::Number(number) => Ok(*number != 0.0),
+            Value::Bool(value) => Ok(*value),
+            Value::Text(text) if text.eq_ignore_ascii_case("TRUE") => Ok(true),
+            Value::Text(text) if text.eq_ignore_ascii_case("FALSE") => Ok(false),
+            Value::Text(_) => Err(error("#VALUE!")),
+            Value::Error(_) => Err(self.clone()),
+            Value::Array(_) => self.clone().scalar().as_bool(),
+        }
+    }
+
+    /// Cells of an array row by row, or the value itself.
+    pub(crate) fn cells(&self) -> Vec<&Value> {
+        match self {
+            Value::Array(rows) => rows.iter().flatten().collect(),
+            value => vec![value],
+        }
+    }
+}
+
+/// Compare two values the way Excel's comparison operators do: numbers sort
+/// before text, text before booleans, text ignoring case, and a blank cell
+/// is 0, "" or FALSE depending on the other side.
+pub(crate) fn compare(left: &Value, right: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Text(_) => 1,
+            Value::Bool(_) => 2,
+            _ => 0,
+        }
+    }
+    let blank_as = |other: &Value| match other {
+        Value::Text(_) => Value::Text(String::new()),
+        Value::Bool(_) => Value::Bool(false),
+        _ => Value::Number(0.0),
+    };
+    let (left, right) = match (left, right) {
+        (Value::Empty, other) => (blank_as(other), other.clone()),
+        (other, Value::Empty) => (other.clone(), blank_as(other)),
+        _ => (left.clone(), right.clone()),
+    };
+    match (&left, &right) {
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (Value::Text(a), Value::Text(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => rank(&left).cmp(&rank(&right)),
+    }
+}
+
+struct SheetData {
+    values: Range<Data>,
+    formulas: HashMap<(usize, usize), String>,
+    /// Last used (row, column), counting formula cells.
+    used_end: (usize, usize),
+}
+
+type CellKey = (String, usize, usize);
+
+enum Lookup {
+    Known(Value),
+    Formula(String),
+}
+
+/// A workbook's formulas, evaluated on demand. Cells without a formula
+/// keep the value saved in the file unless overridden with `set_value`;
+/// formula cells are always recomputed, so overrides flow through to
+/// everything that depends on them.
+///
+/// Dates are serial numbers in the 1900 date system.
+pub struct Model<'a, RS: Read + Seek> {
+    workbook: &'a mut Xlsx<RS>,
+    sheet_names: Vec<String>,
+    /// Defined names, upper case, to the formula they stand for.
+    names: HashMap<String, String>,
+    sheets: HashMap<String, SheetData>,
+    inputs: HashMap<CellKey, Value>,
+    computed: HashMap<CellKey, Value>,
+    in_progress: HashSet<CellKey>,
+    names_in_progress: HashSet<String>,
+}
+
+impl<'a, RS: Read + Seek> Model<'a, RS> {
+    pub fn new(workbook: &'a mut Xlsx<RS>) -> Self {
+        let sheet_names = workbook.sheet_names();
+        let names = workbook
+            .defined_names()
+            .iter()
+            .map(|(name, formula)| (name.to_uppercase(), formula.clone()))
+            .collect();
+        Model {
+            workbook,
+            sheet_names,
+            names,
+            sheets: HashMap::new(),
+            inputs: HashMap::new(),
+            computed: HashMap::new(),
+            in_progress: HashSet::new(),
+            names_in_progress: HashSet::new(),
+        }
+    }
+
+    /// Override a cell, as if the value had been typed into it.
+    pub fn set_value(&mut self, sheet: &str, row: usize, col: usize, value: Value) {
+        self.inputs.insert((sheet.to_string(), row, col), value);
+        self.computed.clear();
+    }
+
+    /// Evaluate one cell.
+    pub fn evaluate_cell(&mut self, sheet: &str, row: usize, col: usize) -> Result<Value> {
+        let key = (sheet.to_string(), row, col);
+        let formula = match self.lookup(&key)? {
+            Lookup::Known(value) => return Ok(value),
+            Lookup::Formula(formula) => formula,
+        };
+        self.prepare(&key, &formula)?;
+        self.compute(key, &formula)
+    }
+
+    /// A cell's value if it is already known, else its formula.
+    fn lookup(&mut self, key: &CellKey) -> Result<Lookup> {
+        if let Some(value) = self.inputs.get(key).or_else(|| self.computed.get(key)) {
+            return Ok(Lookup::Known(value.clone()));
+        }
+        let (sheet, row, col) = key;
+        let data = self.sheet(sheet)?;
+        if let Some(formula) = data.formulas.get(&(*row, *col)) {
+            return Ok(Lookup::Formula(formula.clone()));
+        }
+        let value = data
+            .values
+            .get_value((*row as u32, *col as u32))
+            .map(Value::from_data)
+            .unwrap_or(Value::Empty);
+        Ok(Lookup::Known(value))
+    }
+
+    /// Compute the formula cells a cell refers to, directly or not, before
+    /// the cell itself, so a long chain of cells is walked with a work stack
+    /// rather than one nested evaluation per cell. Errors are left for the
+    /// cells that actually need the value to report, since an `IF` may
+    /// never look at it.
+    fn prepare(&mut self, root: &CellKey, formula: &str) -> Result<()> {
+        let mut entered = HashSet::new();
+        let mut stack = vec![(root.clone(), formula.to_string(), false)];
+        while let Some((key, formula, expanded)) = stack.pop() {
+            if expanded {
+                if key != *root {
+                    let _ = self.compute(key, &formula);
+                }
+                continue;
+            }
+            if self.in_progress.contains(&key) || !entered.insert(key.clone()) {
+                continue;
+            }
+            let Ok(expr) = parse_formula(&formula) else {
+                continue;
+            };
+            let precedents = self.precedents(&expr, &key.0)?;
+            stack.push((key, formula, true));
+            for precedent in precedents {
+                if self.in_progress.contains(&precedent) || entered.contains(&precedent) {
+                    continue;
+                }
+                if let Lookup::Formula(formula) = self.lookup(&precedent)? {
+                    stack.push((precedent, formula, false));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Formula cells a formula refers to, through defined names too.
+    fn precedents(&mut self, expr: &Expr, sheet: &str) -> Result<Vec<CellKey>> {
+        let mut found = Vec::new();
+        let mut names = HashSet::new();
+        let mut pending = vec![(expr.clone(), sheet.to_string())];
+        while let Some((expr, sheet)) = pending.pop() {
+            for reference in expr.references() {
+                match reference {
+                    Expr::Reference(reference) => {
+                        let sheet = match &reference.sheet {
+                            Some(name) => match self.resolve_sheet(name) {
+                                Some(sheet) => sheet,
+                                None => continue,
+                            },
+                            None => sheet.clone(),
+                        };
+                        let end = self.clip(reference, &sheet)?;
+                        let data = self.sheet(&sheet)?;
+                        let rows = reference.start.0..=end.0;
+                        let cols = reference.start.1..=end.1;
+                        let cells = rows.clone().count().saturating_mul(cols.clone().count());
+                        if cells <= data.formulas.len() {
+                            for row in rows {
+                                for col in cols.clone() {
+                                    if data.formulas.contains_key(&(row, col)) {
+                                        found.push((sheet.clone(), row, col));
+                                    }
+                                }
+                            }
+                        } else {
+                            found.extend(
+                                data.formulas
+                                    .keys()
+                                    .filter(|(row, col)| rows.contains(row) && cols.contains(col))
+                                    .map(|&(row, col)| (sheet.clone(), row, col)),
+                            );
+                        }
+                    }
+                    Expr::Name(name) if names.insert(name.to_uppercase()) => {
+                        if let Some(Ok(expr)) = self
+                            .names
+                            .get(&name.to_uppercase())
+                            .map(|f| parse_formula(f))
+                        {
+                            pending.push((expr, sheet.clone()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Evaluate a formula cell; what it refers to is computed on demand.
+    fn compute(&mut self, key: CellKey, formula: &str) -> Result<Value> {
+        let reference = sheet_cell_reference(&key.0, key.1, key.2);
+        if !self.in_progress.insert(key.clone()) {
+            bail!("Circular reference through {}", reference);
+        }
+        let result = parse_formula(formula)
+            .with_context(|| format!("Cannot parse the formula in {}", reference))
+            .and_then(|expr| {
+                self.evaluate(&expr, &key.0)
+                    .with_context(|| format!("Cannot evaluate {} (={})", reference, formula))
+            });
+        // Whatever the outcome, the cell is no longer being evaluated
+        self.in_progress.remove(&key);
+        let value = result?.scalar();
+        self.computed.insert(key, value.clone());
+        Ok(value)
+    }
+
+    /// Evaluate every cell of a block, row by row.
+    pub fn evaluate_range(
+        &mut self,
+        sheet: &str,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> Result<Vec<Vec<Value>>> {
+        (start.0..=end.0)
+            .map(|row| {
+                (start.1..=end.1)
+                    .map(|col| self.evaluate_cell(sheet, row, col))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Evaluate a formula as if it were entered on `sheet`.
+    pub fn evaluate_formula(&mut self, sheet: &str, formula: &str) -> Result<Value> {
+        let expr = parse_formula(formula)?;
+        Ok(self.evaluate(&expr, sheet)?.scalar())
+    }
+
+    fn sheet(&mut self, name: &str) -> Result<&SheetData> {
+        if !self.sheets.contains_key(name) {
+            let values = read_sheet(self.workbook, name)?;
+            let formula_range = self.workbook.worksheet_formula(name).map_err(|source| {
+                XlsqError::UnreadableSheet {
+                    sheet: name.to_string(),
+                    source,
+                }
+            })?;
+            let (top, left) = formula_range.start().unwrap_or_default();
+            let formulas: HashMap<(usize, usize), String> = formula_range
+                .cells()
+                .filter(|(_, _, formula)| !formula.is_empty())
+                .map(|(row, col, formula)| {
+                    ((top as usize + row, left as usize + col), formula.clone())
+                })
+                .collect();
+            let mut used_end = values
+                .end()
+                .map(|(row, col)| (row as usize, col as usize))
+                .unwrap_or_default();
+            for &(row, col) in formulas.keys() {
+                used_end = (used_end.0.max(row), used_end.1.max(col));
+            }
+            self.sheets.insert(
+                name.to_string(),
+                SheetData {
+                    values,
+                    formulas,
+                    used_end,
+                },
+            );
+        }
+        Ok(&self.sheets[name])
+    }
+
+    /// Sheet name as the workbook spells it; references ignore case.
+    fn resolve_sheet(&self, name: &str) -> Option<String> {
+        self.sheet_names
+            .iter()
+            .find(|sheet| sheet.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    fn evaluate_reference(&mut self, reference: &RangeReference, sheet: &str) -> Result<Value> {
+        let sheet = match &reference.sheet {
+            Some(name) => match self.resolve_sheet(name) {
+                Some(sheet) => sheet,
+                None => return Ok(error("#REF!")),
+            },
+            None => sheet.to_string(),
+        };
+        let end = self.clip(reference, &sheet)?;
+        Ok(Value::Array(self.evaluate_range(
+            &sheet,
+            reference.start,
+            end,
+        )?))
+    }
+
+    /// Last cell of a reference, with whole columns stopping at the last used row.
+    fn clip(&mut self, reference: &RangeReference, sheet: &str) -> Result<(usize, usize)> {
+        let used_end = self.sheet(sheet)?.used_end;
+        Ok((
+            reference.end.0.min(used_end.0.max(reference.start.0)),
+            reference.end.1.min(used_end.1.max(reference.start.1)),
+        ))
+    }
+
+    pub(crate) fn evaluate(&mut self, expr: &Expr, sheet: &str) -> Result<Value> {
+        Ok(match expr {
+            Expr::Number(number) => Value::Number(*number),
+            Expr::Text(text) => Value::Text(text.clone()),
+            Expr::Bool(value) => Value::Bool(*value),
+            Expr::Error(code) => Value::Error(code.clone()),
+            Expr::Missing => Value::Empty,
+            Expr::Reference(reference) => self.evaluate_reference(reference, sheet)?,
+            Expr::Name(name) => {
+                let key = name.to_uppercase();
+                let Some(formula) = self.names.get(&key).cloned() else {
+                    return Ok(error("#NAME?"));
+                };
+                if !self.names_in_progress.insert(key.clone()) {
+                    bail!("Circular reference through the defined name {}", name);
+                }
+                let result = parse_formula(&formula)
+                    .with_context(|| format!("Cannot parse the defined name {}", name))
+                    .and_then(|expr| self.evaluate(&expr, sheet));
+                self.names_in_progress.remove(&key);
+                result?
+            }
+            Expr::Negate(inner) => {
+                let value = self.evaluate(inner, sheet)?.scalar();
+                value
+                    .as_number()
+                    .map(|n| Value::Number(-n))
+                    .unwrap_or_else(|e| e)
+            }
+            Expr::Percent(inner) => {
+                let value = self.evaluate(inner, sheet)?.scalar();
+                value
+                    .as_number()
+                    .map(|n| Value::Number(n / 100.0))
+                    .unwrap_or_else(|e| e)
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.evaluate(left, sheet)?.scalar();
+                let right = self.evaluate(right, sheet)?.scalar();
+                binary(*op, &left, &right).unwrap_or_else(|e| e)
+            }
+            Expr::Call(name, args) => self.call(name, args, sheet)?,
+        })
+    }
+}
+
+fn binary(op: BinaryOp, left: &Value, right: &Value) -> Eval<Value> {
+    // Errors propagate before anything else, the left one first
+    for value in [left, right] {
+        if let Value::Error(_) = value {
+            return Err(value.clone());
+        }
+    }
+    let comparison =
+        |expected: &[Ordering]| Ok(Value::Bool(expected.contains(&compare(left, right))));
+    match op {
+        BinaryOp::Equal => comparison(&[Ordering::Equal]),
+        BinaryOp::NotEqual => comparison(&[Ordering::Less, Ordering::Greater]),
+        BinaryOp::Less => comparison(&[Ordering::Less]),
+        BinaryOp::LessEqual => comparison(&[Ordering::Less, Ordering::Equal]),
+        BinaryOp::Greater => comparison(&[Ordering::Greater]),
+        BinaryOp::GreaterEqual => comparison(&[Ordering::Greater, Ordering::Equal]),
+        BinaryOp::Concat => Ok(Value::Text(left.as_text()? + &right.as_text()?)),
+        _ => {
+            let (a, b) = (left.as_number()?, right.as_number()?);
+            let result = match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Subtract => a - b,
+                BinaryOp::Multiply => a * b,
+                BinaryOp::Divide if b == 0.0 => return Err(error("#DIV/0!")),
+                BinaryOp::Divide => a / b,
+                _ => a.powf(b),
+            };
+            number(result)
+        }
+    }
+}
+
+/// A numeric result, or `#NUM!` when it isn't a finite number.
+pub(crate) fn number(value: f64) -> Eval<Value> {
+    if value.is_finite() {
+        Ok(Value::Number(value))
+    } else {
+        Err(error("#NUM!"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::open_excel_file;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_value_from_input() {
+        assert_eq!(Value::from_input("0.05"), Value::Number(0.05));
+        assert_eq!(Value::from_input("12.5%"), Value::Number(0.125));
+        assert_eq!(Value::from_input("true"), Value::Bool(true));
+        assert_eq!(Value::from_input("North"), Value::Text("North".to_string()));
+        assert_eq!(Value::from_input(""), Value::Empty);
+        assert_eq!(format_number(0.1 + 0.2), "0.3");
+        assert_eq!(format_number(2100.0), "2100");
+    }
+
+    #[test]
+    fn test_model() {
+        use xlsxwriter::*;
+
+        let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let workbook = Workbook::new(temp_file.path().to_str().unwrap()).unwrap();
+        let mut inputs = workbook.add_worksheet(Some("Inputs")).unwrap();
+        inputs.write_string(0, 0, "Rate", None).unwrap();
+        inputs.write_number(0, 1, 0.05, None).unwrap();
+        inputs.write_string(1, 0, "Units", None).unwrap();
+        inputs.write_number(1, 1, 100.0, None).unwrap();
+        inputs.write_string(2, 0, "Price", None).unwrap();
+        inputs.write_number(2, 1, 20.0, None).unwrap();
+        let mut summary = workbook.add_worksheet(Some("Summary")).unwrap();
+        summary
+            .write_formula_num(0, 1, "=Inputs!B2*Inputs!B3", None, 2000.0)
+            .unwrap();
+        summary
+            .write_formula_num(1, 1, "=B1*(1+Rate)", None, 2100.0)
+            .unwrap();
+        summary
+            .write_formula(2, 1, "=IF(B2>2100,\"high\",\"ok\")", None)
+            .unwrap();
+        summary
+            .write_formula(3, 1, "=SUM(Inputs!B:B)&\" total\"", None)
+            .unwrap();
+        summary.write_formula(0, 2, "=C2+1", None).unwrap();
+        summary.write_formula(1, 2, "=C1", None).unwrap();
+        workbook.define_name("Rate", "=Inputs!$B$1").unwrap();
+        workbook.close().unwrap();
+
+        let mut workbook = open_excel_file(temp_file.path()).unwrap();
+        let mut model = Model::new(&mut workbook);
+        assert_eq!(
+            model.evaluate_cell("Summary", 1, 1).unwrap(),
+            Value::Number(2100.0)
+        );
+        assert_eq!(
+            model.evaluate_range("Summary", (2, 1), (3, 1)).unwrap(),
+            vec![
+                vec![Value::Text("ok".to_string())],
+                vec![Value::Text("120.05 total".to_string())]
+            ]
+        );
+
+        model.set_value("Inputs", 0, 1, Value::Number(0.1));
+        assert_eq!(
+            model.evaluate_formula("Summary", "B2").unwrap(),
+            Value::Number(2200.0)
+        );
+        assert_eq!(
+            model.evaluate_cell("Summary", 2, 1).unwrap(),
+            Value::Text("high".to_string())
+        );
+        assert_eq!(
+            model.evaluate_formula("Summary", "Missing!A1+1").unwrap(),
+            Value::Error("#REF!".to_string())
+        );
+
+        let err = model.evaluate_cell("Summary", 0, 2).unwrap_err();
+        assert!(format!("{:#}", err).contains("Circular reference through Summary!C1"));
+        assert_eq!(
+            model
+                .evaluate_formula("Summary", "TEXT(B2,\"#,##0\")")
+                .unwrap(),
+            Value::Text("2,200".to_string())
+        );
+        let err = model
+            .evaluate_formula("Summary", "INDIRECT(\"B1\")")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Function INDIRECT is not supported");
+    }
+
+    #[test]
+    fn test_model_long_chain() {
+        use xlsxwriter::*;
+
+        let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let workbook = Workbook::new(temp_file.path().to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Balance")).unwrap();
+        sheet.write_number(0, 0, 1.0, None).unwrap();
+        for row in 1..20_000 {
+            sheet
+                .write_formula(row, 0, &format!("=A{}+1", row), None)
+                .unwrap();
+        }
+        sheet
+            .write_formula(0, 1, "=INDIRECT(\"A1\")", None)
+            .unwrap();
+        sheet
+            .write_formula(2, 1, "=TEXT(A20000,\"#,##0\")", None)
+            .unwrap();
+        sheet.write_formula(1, 1, "=Loop", None).unwrap();
+        workbook.define_name("Loop", "=Loop+1").unwrap();
+        workbook.close().unwrap();
+
+        let mut workbook = open_excel_file(temp_file.path()).unwrap();
+        let mut model = Model::new(&mut workbook);
+        assert_eq!(
+            model.evaluate_cell("Balance", 19_999, 0).unwrap(),
+            Value::Number(20_000.0)
+        );
+        assert_eq!(
+            model.evaluate_formula("Balance", "SUM(A:A)").unwrap(),
+            Value::Number(200_010_000.0)
+        );
+
+        // A failed cell fails the same way every time
+        for _ in 0..2 {
+            let err = model.evaluate_cell("Balance", 0, 1).unwrap_err();
+            assert!(format!("{:#}", err).contains("Function INDIRECT is not supported"));
+        }
+        assert_eq!(
+            model.evaluate_cell("Balance", 2, 1).unwrap(),
+            Value::Text("20,000".to_string())
+        );
+        let err = model.evaluate_cell("Balance", 1, 1).unwrap_err();
+        assert!(format!("{:#}", err).contains("Circular reference through the defined name Loop"));
+    }
+}
@@ -0,0 +1,437 @@
+use crate::address::{parse_cell_address, sheet_cell_reference};
+use crate::comments::{read_comments, Comment};
+use crate::format_cell;
+use crate::formula::shift_formula;
+use crate::links::{hyperlink_targets, Hyperlink, RawLink};
+use crate::number_format::{date_kind, format_with_code};
+use crate::package::{attribute, for_each_element, Package};
+use crate::styles::{read_cell_style, CellStyle};
+use anyhow::{Context, Result};
+use calamine::{CellErrorType, Data, ExcelDateTime};
+use quick_xml::events::Event;
+use std::collections::HashMap;
+
+/// Everything known about one cell, for `xlsq cell`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellDetails {
+    pub sheet: String,
+    pub row: usize,
+    pub col: usize,
+    /// Value as calamine reads it; `Data::Empty` for a blank cell.
+    pub value: Data,
+    /// Formula without the leading `=`.
+    pub formula: Option<String>,
+    /// Format index from the cell's `s` attribute.
+    pub style_index: usize,
+    pub style: Option<CellStyle>,
+    pub comment: Option<Comment>,
+    pub hyperlink: Option<Hyperlink>,
+}
+
+/// Name of the calamine value type, e.g. "Float" or "DateTime".
+pub fn value_type(value: &Data) -> &'static str {
+    match value {
+        Data::Empty => "Empty",
+        Data::String(_) => "String",
+        Data::Float(_) => "Float",
+        Data::Int(_) => "Int",
+        Data::Bool(_) => "Bool",
+        Data::Error(_) => "Error",
+        Data::DateTime(_) => "DateTime",
+        Data::DateTimeIso(_) => "DateTimeIso",
+        Data::DurationIso(_) => "DurationIso",
+    }
+}
+
+/// A cell's `<c>` element as written in the sheet XML.
+#[derive(Debug, Default, PartialEq)]
+struct RawCell {
+    /// The `t` attribute, e.g. "s" for a shared string or "b" for a boolean.
+    kind: Option<String>,
+    style_index: usize,
+    /// Text of `<v>`, or of an inline string's `<t>` elements.
+    value: Option<String>,
+    formula: Option<String>,
+}
+
+/// Gather the value, formula, style, comment and hyperlink of a cell. The
+/// sheet XML is read once, for everything but the comment.
+pub fn inspect_cell(
+    package: &mut Package,
+    sheet_name: &str,
+    row: usize,
+    col: usize,
+) -> Result<CellDetails> {
+    let Some(sheet_part) = package.sheet_part(sheet_name)? else {
+        anyhow::bail!("Sheet '{}' is not in the workbook package", sheet_name);
+    };
+    let xml = package.read_part(&sheet_part)?.unwrap_or_default();
+    let (cell, link) =
+        scan_sheet(&xml, row, col).with_context(|| format!("Malformed {}", sheet_part))?;
+
+    let style = read_cell_style(package, cell.style_index)?;
+    let value = cell_value(package, &cell, style.as_ref())?;
+    let comment = read_comments(package, sheet_name)?
+        .into_iter()
+        .find(|comment| (comment.row, comment.col) == (row, col));
+    let mut hyperlink = None;
+    if let Some(link) = link {
+        if let Some(target) = link.target(&hyperlink_targets(package, &sheet_part)?) {
+            hyperlink = Some(Hyperlink {
+                sheet: sheet_name.to_string(),
+                row,
+                col,
+                display: link.display.unwrap_or_else(|| format_cell(&value)),
+                target,
+            });
+        }
+    }
+
+    Ok(CellDetails {
+        sheet: sheet_name.to_string(),
+        row,
+        col,
+        value,
+        formula: cell.formula,
+        style_index: cell.style_index,
+        style,
+        comment,
+        hyperlink,
+    })
+}
+
+/// Read the cell at (`row`, `col`) and the hyperlink on it in one pass over
+/// a worksheet part. A cell sharing a formula gets it moved from the first
+/// cell, as calamine does.
+fn scan_sheet(xml: &str, row: usize, col: usize) -> Result<(RawCell, Option<RawLink>)> {
+    enum Field {
+        Formula,
+        Value,
+    }
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut found = RawCell::default();
+    let mut link = None;
+    // The text and first cell of each shared formula, by `si`
+    let mut shared: HashMap<String, (String, (usize, usize))> = HashMap::new();
+    let mut next = (0, 0);
+    // The cell being read: its position, the `si` and `ref` of its formula,
+    // and the text of its formula and value
+    let mut position: Option<(usize, usize)> = None;
+    let mut shared_formula: Option<(String, bool)> = None;
+    let (mut formula, mut value) = (String::new(), None::<String>);
+    let mut field = None;
+    let mut phonetic = false;
+
+    loop {
+        let event = reader.read_event()?;
+        let empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+                b"row" => {
+                    if let Some(r) = attribute(&element, b"r")?.and_then(|r| r.parse().ok()) {
+                        next = (usize::saturating_sub(r, 1), 0);
+                    }
+                }
+                b"c" => {
+                    let here = attribute(&element, b"r")?
+                        .and_then(|r| parse_cell_address(&r))
+                        .unwrap_or(next);
+                    next = (here.0, here.1 + 1);
+                    if here == (row, col) {
+                        found.kind = attribute(&element, b"t")?;
+                        found.style_index = attribute(&element, b"s")?
+                            .and_then(|s| s.parse().ok())
+                            .unwrap_or(0);
+                    }
+                    if !empty {
+                        position = Some(here);
+                        (shared_formula, formula, value) = (None, String::new(), None);
+                    }
+                }
+                b"f" if position.is_some() => {
+                    if attribute(&element, b"t")?.as_deref() == Some("shared") {
+                        let index = attribute(&element, b"si")?.unwrap_or_default();
+                        shared_formula = Some((index, attribute(&element, b"ref")?.is_some()));
+                    }
+                    if !empty {
+                        field = Some(Field::Formula);
+                    }
+                }
+                b"v" if position.is_some() && !empty => {
+                    value.get_or_insert_with(String::new);
+                    field = Some(Field::Value);
+                }
+                b"rPh" => phonetic = true,
+                // The text of an inline string, leaving out phonetic runs
+                b"t" if position.is_some() && !empty && !phonetic => {
+                    value.get_or_insert_with(String::new);
+                    field = Some(Field::Value);
+                }
+                b"hyperlink" if link.is_none() => {
+                    link = RawLink::from_element(&element)?
+                        .filter(|link| (link.row, link.col) == (row, col));
+                }
+                _ => {}
+            },
+            Event::Text(text) => match field {
+                Some(Field::Formula) => formula.push_str(&text.unescape()?),
+                Some(Field::Value) => {
+                    if let Some(value) = &mut value {
+                        value.push_str(&text.unescape()?);
+                    }
+                }
+                None => {}
+            },
+            Event::End(element) => match element.local_name().as_ref() {
+                b"f" | b"v" | b"t" => field = None,
+                b"rPh" => phonetic = false,
+                b"c" => {
+                    let Some(here) = position.take() else {
+                        continue;
+                    };
+                    let formula = std::mem::take(&mut formula);
+                    let formula = match shared_formula.take() {
+                        // The first cell of a shared formula holds its text
+                        Some((index, true)) => {
+                            shared.insert(index, (formula.clone(), here));
+                            formula
+                        }
+                        Some((index, false)) if formula.is_empty() => shared
+                            .get(&index)
+                            .map(|(first, origin)| {
+                                shift_formula(
+                                    first,
+                                    here.0 as i64 - origin.0 as i64,
+                                    here.1 as i64 - origin.1 as i64,
+                                )
+                            })
+                            .unwrap_or_default(),
+                        _ => formula,
+                    };
+                    if here == (row, col) {
+                        found.formula = Some(formula).filter(|formula| !formula.is_empty());
+                        found.value = value.take();
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok((found, link))
+}
+
+/// The value calamine reads for a cell: shared strings looked up, and
+/// numbers in a date or time format turned into dates.
+fn cell_value(package: &mut Package, cell: &RawCell, style: Option<&CellStyle>) -> Result<Data> {
+    let Some(text) = &cell.value else {
+        return Ok(Data::Empty);
+    };
+    let number = match cell.kind.as_deref() {
+        Some("s") => {
+            let index = text.trim().parse().unwrap_or(0);
+            return Ok(shared_string(package, index)?.map_or(Data::Empty, Data::String));
+        }
+        Some("b") => return Ok(Data::Bool(text != "0")),
+        Some("e") => {
+            return Ok(match text.parse::<CellErrorType>() {
+                Ok(error) => Data::Error(error),
+                Err(_) => Data::String(text.clone()),
+            })
+        }
+        Some("d") => return Ok(Data::DateTimeIso(text.clone())),
+        Some("str" | "inlineStr") => return Ok(Data::String(text.clone())),
+        Some("n") if text.is_empty() => return Ok(Data::Empty),
+        _ => match text.parse::<f64>() {
+            Ok(number) => number,
+            Err(_) => return Ok(Data::String(text.clone())),
+        },
+    };
+    let kind = style
+        .and_then(|style| style.number_format.as_deref())
+        .and_then(date_kind);
+    Ok(match kind {
+        Some(kind) => Data::DateTime(ExcelDateTime::new(number, kind, uses_1904_dates(package)?)),
+        None => Data::Float(number),
+    })
+}
+
+/// Entry `index` of the workbook's shared string table.
+fn shared_string(package: &mut Package, index: usize) -> Result<Option<String>> {
+    let part = package
+        .relationships("xl/workbook.xml")?
+        .into_iter()
+        .find(|relationship| relationship.kind == "sharedStrings")
+        .map_or_else(
+            || "xl/sharedStrings.xml".to_string(),
+            |relationship| relationship.target,
+        );
+    let Some(xml) = package.read_part(&part)? else {
+        return Ok(None);
+    };
+
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut seen = 0;
+    let mut text = None::<String>;
+    let (mut in_text, mut phonetic) = (false, false);
+    loop {
+        match reader
+            .read_event()
+            .with_context(|| format!("Malformed {}", part))?
+        {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"si" if seen == index => text = Some(String::new()),
+                b"t" => in_text = true,
+                b"rPh" => phonetic = true,
+                _ => {}
+            },
+            Event::Text(value) if in_text && !phonetic => {
+                if let Some(text) = &mut text {
+                    text.push_str(&value.unescape()?);
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"si" if text.is_some() => return Ok(text),
+                b"si" => seen += 1,
+                b"t" => in_text = false,
+                b"rPh" => phonetic = false,
+                _ => {}
+            },
+            Event::Empty(element) if element.local_name().as_ref() == b"si" => {
+                if seen == index {
+                    return Ok(Some(String::new()));
+                }
+                seen += 1;
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Whether the workbook counts dates from 1904 rather than 1900.
+fn uses_1904_dates(package: &mut Package) -> Result<bool> {
+    let Some(xml) = package.read_part("xl/workbook.xml")? else {
+        return Ok(false);
+    };
+    let mut date_1904 = false;
+    for_each_element(&xml, |element| {
+        if element.local_name().as_ref() == b"workbookPr" {
+            date_1904 = matches!(
+                attribute(element, b"date1904")?.as_deref(),
+                Some("1" | "true")
+            );
+        }
+        Ok(())
+    })
+    .context("Malformed xl/workbook.xml")?;
+    Ok(date_1904)
+}
+
+pub fn print_cell_details(details: &CellDetails) {
+    let none = || "(none)".to_string();
+    println!(
+        "Cell: {}",
+        sheet_cell_reference(&details.sheet, details.row, details.col)
+    );
+    println!("Type: {}", value_type(&details.value));
+    println!("Raw value: {:?}", details.value);
+    if let Some(code) = details
+        .style
+        .as_ref()
+        .and_then(|style| style.number_format.as_deref())
+    {
+        println!(
+            "Formatted value: {}",
+            format_with_code(&details.value, code)
+        );
+    }
+    println!("Displayed by xlsq: {}", format_cell(&details.value));
+    println!(
+        "Formula: {}",
+        details
+            .formula
+            .as_ref()
+            .map(|formula| format!("={}", formula))
+            .unwrap_or_else(none)
+    );
+
+    match &details.style {
+        Some(style) => {
+            let code = style
+                .number_format
+                .clone()
+                .unwrap_or_else(|| "locale-dependent built-in".to_string());
+            println!("Number format: {} (id {})", code, style.number_format_id);
+            println!("Font: {}", style.font.describe());
+            println!("Fill: {}", style.fill.describe());
+            println!("Border: {}", style.border.describe());
+        }
+        None => println!("Style: (none)"),
+    }
+    println!("Style index: {}", details.style_index);
+
+    println!(
+        "Comment: {}",
+        details
+            .comment
+            .as_ref()
+            .map(|comment| match &comment.author {
+                Some(author) => format!("({}) {}", author, comment.text),
+                None => comment.text.clone(),
+            })
+            .unwrap_or_else(none)
+    );
+    println!(
+        "Hyperlink: {}",
+        details
+            .hyperlink
+            .as_ref()
+            .map(|link| format!("{} ({})", link.target, link.target.kind()))
+            .unwrap_or_else(none)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_sheet() {
+        let xml = r#"<worksheet><sheetData>
+<row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" s="3"><v>1</v></c></row>
+<row r="2"><c r="A2" s="7"/><c t="inlineStr"><is><t>Hi</t><rPh><t>ha</t></rPh></is></c></row>
+<row r="3"><c r="C3"><f t="shared" ref="C3:C5" si="0">A3*$B$1</f><v>2</v></c></row>
+<row r="5"><c r="C5"><f t="shared" si="0"/><v>4</v></c></row>
+</sheetData><hyperlinks><hyperlink ref="B1" r:id="rId1" display="Site"/></hyperlinks></worksheet>"#;
+
+        let (cell, link) = scan_sheet(xml, 0, 1).unwrap();
+        assert_eq!(cell.style_index, 3);
+        assert_eq!(cell.value.as_deref(), Some("1"));
+        let link = link.unwrap();
+        assert_eq!((link.row, link.col), (0, 1));
+        assert_eq!(link.display.as_deref(), Some("Site"));
+
+        let (cell, link) = scan_sheet(xml, 0, 0).unwrap();
+        assert_eq!(cell.kind.as_deref(), Some("s"));
+        assert_eq!(cell.style_index, 0);
+        assert_eq!(link, None);
+
+        let (cell, _) = scan_sheet(xml, 1, 0).unwrap();
+        assert_eq!((cell.style_index, cell.value), (7, None));
+
+        // A cell without `r` follows the one before it
+        let (cell, _) = scan_sheet(xml, 1, 1).unwrap();
+        assert_eq!(cell.value.as_deref(), Some("Hi"));
+
+        let (cell, _) = scan_sheet(xml, 2, 2).unwrap();
+        assert_eq!(cell.formula.as_deref(), Some("A3*$B$1"));
+        let (cell, _) = scan_sheet(xml, 4, 2).unwrap();
+        assert_eq!(cell.formula.as_deref(), Some("A5*$B$1"));
+        assert_eq!(cell.value.as_deref(), Some("4"));
+
+        assert_eq!(scan_sheet(xml, 5, 5).unwrap(), (RawCell::default(), None));
+    }
+}
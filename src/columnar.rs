@@ -0,0 +1,193 @@
+use crate::export::{cell_timestamp, infer_column_type, json_headers, ColumnType};
+use crate::format_cell;
+use anyhow::Result;
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, TimestampMillisecondArray,
+};
+use arrow::datatypes::{DataType as ArrowType, Field, Schema, TimeUnit};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use calamine::{Data, DataType, Range};
+use parquet::arrow::ArrowWriter;
+use std::io::Write;
+use std::sync::Arc;
+
+fn arrow_type(column_type: ColumnType) -> ArrowType {
+    match column_type {
+        ColumnType::Int => ArrowType::Int64,
+        ColumnType::Float => ArrowType::Float64,
+        ColumnType::Bool => ArrowType::Boolean,
+        ColumnType::Timestamp => ArrowType::Timestamp(TimeUnit::Millisecond, None),
+        ColumnType::Utf8 => ArrowType::Utf8,
+    }
+}
+
+fn build_column<'a>(column_type: ColumnType, cells: impl Iterator<Item = &'a Data>) -> ArrayRef {
+    match column_type {
+        ColumnType::Int => Arc::new(
+            cells
+                .map(|cell| match cell {
+                    Data::Int(i) => Some(*i),
+                    Data::Float(f) => Some(*f as i64),
+                    _ => None,
+                })
+                .collect::<Int64Array>(),
+        ),
+        ColumnType::Float => Arc::new(
+            cells
+                .map(|cell| match cell {
+                    Data::Int(i) => Some(*i as f64),
+                    Data::Float(f) => Some(*f),
+                    _ => None,
+                })
+                .collect::<Float64Array>(),
+        ),
+        ColumnType::Bool => Arc::new(cells.map(|cell| cell.get_bool()).collect::<BooleanArray>()),
+        ColumnType::Timestamp => Arc::new(
+            cells
+                .map(|cell| cell_timestamp(cell).map(|dt| dt.and_utc().timestamp_millis()))
+                .collect::<TimestampMillisecondArray>(),
+        ),
+        ColumnType::Utf8 => Arc::new(
+            cells
+                .map(|cell| match cell {
+                    Data::Empty => None,
+                    other => Some(format_cell(other)),
+                })
+                .collect::<StringArray>(),
+        ),
+    }
+}
+
+/// Convert a sheet range into a single record batch, using the first row as
+/// column names. Every column is nullable; `Data::Empty` becomes null.
+pub fn range_to_record_batch(range: &Range<Data>) -> Result<RecordBatch> {
+    let headers = json_headers(range);
+    let body: Vec<&[Data]> = range.rows().skip(1).collect();
+
+    let mut fields = Vec::with_capacity(headers.len());
+    let mut columns = Vec::with_capacity(headers.len());
+    for (col_idx, header) in headers.iter().enumerate() {
+        let column_type = infer_column_type(body.iter().map(|row| &row[col_idx]));
+        fields.push(Field::new(header, arrow_type(column_type), true));
+        columns.push(build_column(
+            column_type,
+            body.iter().map(|row| &row[col_idx]),
+        ));
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    if columns.is_empty() {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+pub fn write_parquet<W: Write + Send>(range: &Range<Data>, writer: &mut W) -> Result<()> {
+    let batch = range_to_record_batch(range)?;
+    let mut parquet_writer = ArrowWriter::try_new(writer, batch.schema(), None)?;
+    parquet_writer.write(&batch)?;
+    parquet_writer.close()?;
+    Ok(())
+}
+
+pub fn write_arrow_ipc<W: Write>(range: &Range<Data>, writer: &mut W) -> Result<()> {
+    let batch = range_to_record_batch(range)?;
+    let mut ipc_writer = FileWriter::try_new(writer, &batch.schema())?;
+    ipc_writer.write(&batch)?;
+    ipc_writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+    use arrow::ipc::reader::FileReader;
+    use std::io::Cursor;
+
+    fn typed_range() -> Range<Data> {
+        let mut range = Range::new((0, 0), (2, 4));
+        for (col, name) in ["Id", "Price", "Active", "Name", "Mixed"]
+            .iter()
+            .enumerate()
+        {
+            range.set_value((0, col as u32), Data::String(name.to_string()));
+        }
+        range.set_value((1, 0), Data::Float(1.0));
+        range.set_value((1, 1), Data::Float(9.99));
+        range.set_value((1, 2), Data::Bool(true));
+        range.set_value((1, 3), Data::String("Laptop".to_string()));
+        range.set_value((1, 4), Data::Int(7));
+        range.set_value((2, 0), Data::Int(2));
+        range.set_value((2, 1), Data::Int(5));
+        range.set_value((2, 4), Data::String("n/a".to_string()));
+        range
+    }
+
+    #[test]
+    fn test_range_to_record_batch() {
+        let batch = range_to_record_batch(&typed_range()).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+
+        let schema = batch.schema();
+        let types: Vec<&ArrowType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                &ArrowType::Int64,
+                &ArrowType::Float64,
+                &ArrowType::Boolean,
+                &ArrowType::Utf8,
+                &ArrowType::Utf8,
+            ]
+        );
+
+        // Empty cells are nulls
+        assert!(batch.column(2).is_null(1));
+        assert!(batch.column(3).is_null(1));
+    }
+
+    #[test]
+    fn test_iso_dates() {
+        use arrow::array::AsArray;
+        use arrow::datatypes::TimestampMillisecondType;
+
+        let mut range = Range::new((0, 0), (2, 1));
+        range.set_value((0, 0), Data::String("Day".to_string()));
+        range.set_value((0, 1), Data::String("Time".to_string()));
+        range.set_value((1, 0), Data::DateTimeIso("2024-03-31".to_string()));
+        range.set_value((1, 1), Data::DateTimeIso("10:30:00".to_string()));
+        range.set_value((2, 0), Data::DateTimeIso("2024-04-01T12:00:00".to_string()));
+
+        let batch = range_to_record_batch(&range).unwrap();
+        // A date without a time is midnight rather than null
+        let days = batch.column(0).as_primitive::<TimestampMillisecondType>();
+        assert_eq!(days.null_count(), 0);
+        assert_eq!(days.value(0), 1_711_843_200_000);
+        assert_eq!(days.value(1), 1_711_972_800_000);
+        // A time without a date is kept as text
+        assert_eq!(batch.schema().field(1).data_type(), &ArrowType::Utf8);
+        assert_eq!(batch.column(1).as_string::<i32>().value(0), "10:30:00");
+    }
+
+    #[test]
+    fn test_write_arrow_ipc_round_trip() {
+        let mut out = Vec::new();
+        write_arrow_ipc(&typed_range(), &mut out).unwrap();
+
+        let reader = FileReader::try_new(Cursor::new(out), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 2);
+        assert_eq!(batches[0].schema().field(0).name(), "Id");
+    }
+
+    #[test]
+    fn test_write_parquet_magic() {
+        let mut out = Vec::new();
+        write_parquet(&typed_range(), &mut out).unwrap();
+        assert!(out.starts_with(b"PAR1"));
+        assert!(out.ends_with(b"PAR1"));
+    }
+}
@@ -0,0 +1,380 @@
+use crate::address::{cell_address, parse_cell_address};
+use crate::package::{attribute, for_each_element, Package};
+use anyhow::{Context, Result};
+use quick_xml::events::Event;
+use std::collections::HashMap;
+
+/// A cell comment: a note, or the first comment of a thread with its
+/// replies as further `Author: text` lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// 0-based worksheet row and column of the cell.
+    pub row: usize,
+    pub col: usize,
+    pub author: Option<String>,
+    pub text: String,
+}
+
+impl Comment {
+    pub fn address(&self) -> String {
+        cell_address(self.row, self.col)
+    }
+}
+
+/// Read the comments of a sheet, ordered by row and then column. Threaded
+/// comments replace the placeholder note Excel saves in the same cell for
+/// older versions.
+pub fn read_comments(package: &mut Package, sheet_name: &str) -> Result<Vec<Comment>> {
+    let Some(sheet_part) = package.sheet_part(sheet_name)? else {
+        return Ok(Vec::new());
+    };
+    let mut comments = Vec::new();
+    let mut threads = Vec::new();
+    let mut persons = None;
+    for relationship in package.relationships(&sheet_part)? {
+        if relationship.external {
+            continue;
+        }
+        let threaded = match relationship.kind.as_str() {
+            "comments" => false,
+            "threadedComment" => true,
+            _ => continue,
+        };
+        let Some(xml) = package.read_part(&relationship.target)? else {
+            continue;
+        };
+        let context = || format!("Malformed comments in {}", relationship.target);
+        if threaded {
+            if persons.is_none() {
+                persons = Some(read_persons(package)?);
+            }
+            let persons = persons.as_ref().expect("persons were just read");
+            threads.extend(parse_threaded_comments(&xml, persons).with_context(context)?);
+        } else {
+            comments.extend(parse_comments(&xml).with_context(context)?);
+        }
+    }
+    comments.retain(|comment: &Comment| {
+        !threads
+            .iter()
+            .any(|thread: &Comment| (thread.row, thread.col) == (comment.row, comment.col))
+    });
+    comments.extend(threads);
+    comments.sort_by_key(|comment| (comment.row, comment.col));
+    Ok(comments)
+}
+
+/// Display names of the people in threaded comments, by person id.
+fn read_persons(package: &mut Package) -> Result<HashMap<String, String>> {
+    let mut persons = HashMap::new();
+    for relationship in package.relationships("xl/workbook.xml")? {
+        if relationship.kind != "person" || relationship.external {
+            continue;
+        }
+        let Some(xml) = package.read_part(&relationship.target)? else {
+            continue;
+        };
+        for_each_element(&xml, |element| {
+            if element.local_name().as_ref() == b"person" {
+                if let (Some(id), Some(name)) = (
+                    attribute(element, b"id")?,
+                    attribute(element, b"displayName")?,
+                ) {
+                    persons.insert(id, name);
+                }
+            }
+            Ok(())
+        })
+        .with_context(|| format!("Malformed {}", relationship.target))?;
+    }
+    Ok(persons)
+}
+
+/// Threads of a `threadedComments` part, one `Comment` per thread with the
+/// replies appended in order.
+fn parse_threaded_comments(xml: &str, persons: &HashMap<String, String>) -> Result<Vec<Comment>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut threads: Vec<Comment> = Vec::new();
+    // Thread of each comment id, for the replies that point at it
+    let mut thread_ids: HashMap<String, usize> = HashMap::new();
+    // The comment being read, with its id and the id of the one it replies to
+    let mut current: Option<(Comment, Option<String>, Option<String>)> = None;
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"threadedComment" => {
+                    let reference = attribute(&element, b"ref")?.unwrap_or_default();
+                    let Some((row, col)) = parse_cell_address(&reference) else {
+                        continue;
+                    };
+                    let author = attribute(&element, b"personId")?
+                        .and_then(|id| persons.get(&id).cloned())
+                        .filter(|author| !author.is_empty());
+                    let comment = Comment {
+                        row,
+                        col,
+                        author,
+                        text: String::new(),
+                    };
+                    current = Some((
+                        comment,
+                        attribute(&element, b"id")?,
+                        attribute(&element, b"parentId")?,
+                    ));
+                }
+                b"text" => in_text = true,
+                _ => {}
+            },
+            Event::Text(text) if in_text => {
+                if let Some((comment, _, _)) = &mut current {
+                    comment.text.push_str(&text.unescape()?);
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"text" => in_text = false,
+                b"threadedComment" => {
+                    let Some((mut comment, id, parent_id)) = current.take() else {
+                        continue;
+                    };
+                    comment.text = comment.text.trim().to_string();
+                    match parent_id.and_then(|parent| thread_ids.get(&parent).copied()) {
+                        Some(thread) => {
+                            let text = &mut threads[thread].text;
+                            text.push('\n');
+                            if let Some(author) = &comment.author {
+                                text.push_str(&format!("{}: ", author));
+                            }
+                            text.push_str(&comment.text);
+                        }
+                        None => {
+                            if let Some(id) = id {
+                                thread_ids.insert(id, threads.len());
+                            }
+                            threads.push(comment);
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(threads)
+}
+
+fn parse_comments(xml: &str) -> Result<Vec<Comment>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut authors = Vec::new();
+    let mut comments = Vec::new();
+    let mut current: Option<(Comment, Option<usize>)> = None;
+    let mut in_author = false;
+    let mut in_text = false;
+    // Phonetic guides (<rPh>) repeat the text in another script
+    let mut in_phonetic = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"author" => {
+                    in_author = true;
+                    authors.push(String::new());
+                }
+                b"comment" => {
+                    let reference = attribute(&element, b"ref")?.unwrap_or_default();
+                    let Some((row, col)) = parse_cell_address(&reference) else {
+                        continue;
+                    };
+                    let author_id =
+                        attribute(&element, b"authorId")?.and_then(|id| id.parse().ok());
+                    let comment = Comment {
+                        row,
+                        col,
+                        author: None,
+                        text: String::new(),
+                    };
+                    current = Some((comment, author_id));
+                }
+                b"t" => in_text = true,
+                b"rPh" => in_phonetic = true,
+                _ => {}
+            },
+            // An author without a name still takes up its place in the list
+            Event::Empty(element) if element.local_name().as_ref() == b"author" => {
+                authors.push(String::new());
+            }
+            Event::Text(text) => {
+                let text = text.unescape()?;
+                if in_author {
+                    if let Some(author) = authors.last_mut() {
+                        author.push_str(&text);
+                    }
+                } else if in_text && !in_phonetic {
+                    if let Some((comment, _)) = &mut current {
+                        comment.text.push_str(&text);
+                    }
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"author" => in_author = false,
+                b"t" => in_text = false,
+                b"rPh" => in_phonetic = false,
+                b"comment" => {
+                    if let Some((mut comment, author_id)) = current.take() {
+                        comment.author = author_id
+                            .and_then(|id: usize| authors.get(id))
+                            .filter(|author| !author.is_empty())
+                            .cloned();
+                        comment.text = comment.text.trim().to_string();
+                        comments.push(comment);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(comments)
+}
+
+/// Print each comment with its cell, author and text; lines after the first
+/// are indented under it.
+pub fn print_comments(sheet_name: &str, comments: &[Comment]) {
+    println!("Comments in sheet: {}", sheet_name);
+    if comments.is_empty() {
+        println!("No comments found.");
+        return;
+    }
+    for comment in comments {
+        let text = comment.text.replace('\n', "\n    ");
+        match &comment.author {
+            Some(author) => println!("  {} ({}): {}", comment.address(), author, text),
+            None => println!("  {}: {}", comment.address(), text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_comments() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<comments xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+  <authors><author>Ada</author><author></author><author/><author>Grace</author></authors>
+  <commentList>
+    <comment ref="C2" authorId="0"><text><r><rPr><b/></rPr><t>Ada:</t></r><r><t xml:space="preserve">
+Check &amp; confirm</t></r></text></comment>
+    <comment ref="A1" authorId="1"><text><t>Plain note</t><rPh sb="0" eb="1"><t>ignored</t></rPh></text></comment>
+    <comment ref="B5" authorId="3"><text><t>Rate confirmed</t></text></comment>
+  </commentList>
+</comments>"#;
+
+        let comments = parse_comments(xml).unwrap();
+        assert_eq!(
+            comments,
+            vec![
+                Comment {
+                    row: 1,
+                    col: 2,
+                    author: Some("Ada".to_string()),
+                    text: "Ada:\nCheck & confirm".to_string(),
+                },
+                Comment {
+                    row: 0,
+                    col: 0,
+                    author: None,
+                    text: "Plain note".to_string(),
+                },
+                Comment {
+                    row: 4,
+                    col: 1,
+                    author: Some("Grace".to_string()),
+                    text: "Rate confirmed".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_threaded_comments() {
+        use std::io::{Cursor, Write};
+        use zip::write::SimpleFileOptions;
+
+        let parts = [
+            (
+                "xl/workbook.xml",
+                r#"<workbook xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Audit" sheetId="1" r:id="rId1"/></sheets></workbook>"#,
+            ),
+            (
+                "xl/_rels/workbook.xml.rels",
+                r#"<Relationships>
+  <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+  <Relationship Id="rId2" Type="http://schemas.microsoft.com/office/2017/10/relationships/person" Target="persons/person.xml"/>
+</Relationships>"#,
+            ),
+            (
+                "xl/worksheets/_rels/sheet1.xml.rels",
+                r#"<Relationships>
+  <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/comments" Target="../comments1.xml"/>
+  <Relationship Id="rId2" Type="http://schemas.microsoft.com/office/2017/10/relationships/threadedComment" Target="../threadedComments/threadedComment1.xml"/>
+</Relationships>"#,
+            ),
+            (
+                "xl/comments1.xml",
+                r#"<comments><authors><author>tc={0001}</author><author>Grace</author></authors><commentList>
+  <comment ref="B2" authorId="0"><text><t>[Threaded comment]
+Your version of Excel allows you to read this threaded comment</t></text></comment>
+  <comment ref="A1" authorId="1"><text><t>Plain note</t></text></comment>
+</commentList></comments>"#,
+            ),
+            (
+                "xl/threadedComments/threadedComment1.xml",
+                r#"<ThreadedComments xmlns="http://schemas.microsoft.com/office/spreadsheetml/2018/threadedcomments">
+  <threadedComment ref="B2" dT="2024-05-01T10:00:00.00" personId="{P1}" id="{0001}"><text>Receipt missing?</text></threadedComment>
+  <threadedComment ref="B2" dT="2024-05-02T09:00:00.00" personId="{P2}" id="{0002}" parentId="{0001}"><text>Found it &amp; attached</text></threadedComment>
+</ThreadedComments>"#,
+            ),
+            (
+                "xl/persons/person.xml",
+                r#"<personList xmlns="http://schemas.microsoft.com/office/spreadsheetml/2018/threadedcomments">
+  <person displayName="Ada Lovelace" id="{P1}" userId="ada" providerId="None"/>
+  <person displayName="Grace Hopper" id="{P2}" userId="grace" providerId="None"/>
+</personList>"#,
+            ),
+        ];
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, xml) in parts {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(xml.as_bytes()).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut package = Package::new(Cursor::new(bytes)).unwrap();
+        let comments = read_comments(&mut package, "Audit").unwrap();
+        assert_eq!(
+            comments,
+            vec![
+                Comment {
+                    row: 0,
+                    col: 0,
+                    author: Some("Grace".to_string()),
+                    text: "Plain note".to_string(),
+                },
+                // The thread, not the placeholder note
+                Comment {
+                    row: 1,
+                    col: 1,
+                    author: Some("Ada Lovelace".to_string()),
+                    text: "Receipt missing?\nGrace Hopper: Found it & attached".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,640 @@
+use crate::address::{cell_address, column_name, quote_sheet_name, sheet_cell_reference};
+use crate::formula::{parse_formula, Expr, MAX_ROW};
+use crate::XlsqError;
+use anyhow::Result;
+use calamine::{Reader, Xlsx};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Seek};
+
+/// A cell, range or defined name in the dependency graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Node {
+    Cell {
+        sheet: String,
+        row: usize,
+        col: usize,
+    },
+    /// A block of cells a formula refers to as a whole, e.g. `B2:B9`.
+    Range {
+        sheet: String,
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+    Name(String),
+}
+
+impl Node {
+    /// e.g. `Summary!C9`, `'Q1 Sales'!B2:B9`, `Data!A:B` or `Rate`.
+    pub fn label(&self) -> String {
+        match self {
+            Node::Cell { sheet, row, col } => sheet_cell_reference(sheet, *row, *col),
+            Node::Range { sheet, start, end } if start.0 == 0 && end.0 == MAX_ROW => format!(
+                "{}!{}:{}",
+                quote_sheet_name(sheet),
+                column_name(start.1),
+                column_name(end.1)
+            ),
+            Node::Range { sheet, start, end } => format!(
+                "{}!{}:{}",
+                quote_sheet_name(sheet),
+                cell_address(start.0, start.1),
+                cell_address(end.0, end.1)
+            ),
+            Node::Name(name) => name.clone(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Node::Cell { .. } => "cell",
+            Node::Range { .. } => "range",
+            Node::Name(_) => "name",
+        }
+    }
+
+    pub fn sheet(&self) -> Option<&str> {
+        match self {
+            Node::Cell { sheet, .. } | Node::Range { sheet, .. } => Some(sheet),
+            Node::Name(_) => None,
+        }
+    }
+
+    fn contains(&self, sheet_name: &str, row: usize, col: usize) -> bool {
+        match self {
+            Node::Range { sheet, start, end } => {
+                sheet == sheet_name
+                    && (start.0..=end.0).contains(&row)
+                    && (start.1..=end.1).contains(&col)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Which way to follow the graph from a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// What the node is calculated from.
+    Precedents,
+    /// What is calculated from the node.
+    Dependents,
+}
+
+/// Which cells, ranges and names each formula refers to. A range depends
+/// on the formula cells inside it, so paths run through ranges into the
+/// cells that feed them.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    /// Formula of each formula cell and defined name, without the `=`.
+    formulas: BTreeMap<Node, String>,
+    /// Direct references of each formula cell and defined name.
+    uses: BTreeMap<Node, BTreeSet<Node>>,
+    used_by: BTreeMap<Node, BTreeSet<Node>>,
+    /// Formula cells inside each referenced range, found once by `build`.
+    members: BTreeMap<Node, Vec<Node>>,
+    /// Referenced ranges taking in each cell of the graph.
+    containing: BTreeMap<Node, Vec<Node>>,
+    /// Formulas that could not be parsed, by the node holding them.
+    unparsed: Vec<Node>,
+}
+
+impl DependencyGraph {
+    /// Parse every formula of the workbook and its defined names.
+    pub fn build<RS: Read + Seek>(workbook: &mut Xlsx<RS>) -> Result<Self> {
+        let sheet_names = workbook.sheet_names();
+        let mut graph = DependencyGraph::default();
+
+        // Register every name first, so references to one in any case resolve to it
+        let defined_names = workbook.defined_names().to_vec();
+        for (name, formula) in &defined_names {
+            graph
+                .formulas
+                .insert(Node::Name(name.clone()), formula.clone());
+        }
+        // Names have no sheet of their own; unqualified references fall back to the first
+        let default_sheet = sheet_names.first().cloned().unwrap_or_default();
+        for (name, formula) in &defined_names {
+            let formula = formula.strip_prefix('=').unwrap_or(formula);
+            graph.add_formula(
+                Node::Name(name.clone()),
+                formula,
+                &default_sheet,
+                &sheet_names,
+            );
+        }
+
+        for sheet_name in &sheet_names {
+            let formulas = workbook.worksheet_formula(sheet_name).map_err(|source| {
+                XlsqError::UnreadableSheet {
+                    sheet: sheet_name.clone(),
+                    source,
+                }
+            })?;
+            let (top, left) = formulas.start().unwrap_or_default();
+            for (row, col, formula) in formulas.cells() {
+                if formula.is_empty() {
+                    continue;
+                }
+                let node = Node::Cell {
+                    sheet: sheet_name.clone(),
+                    row: top as usize + row,
+                    col: left as usize + col,
+                };
+                graph.add_formula(node, formula, sheet_name, &sheet_names);
+            }
+        }
+        graph.index_ranges();
+
+        Ok(graph)
+    }
+
+    /// Match every referenced range with the cells of the graph inside it,
+    /// so neighbour lookups don't scan every formula or range.
+    fn index_ranges(&mut self) {
+        let mut columns: BTreeMap<(&str, usize), BTreeSet<usize>> = BTreeMap::new();
+        for node in self.formulas.keys().chain(self.used_by.keys()) {
+            if let Node::Cell { sheet, row, col } = node {
+                columns.entry((sheet, *col)).or_default().insert(*row);
+            }
+        }
+
+        let mut members: BTreeMap<Node, Vec<Node>> = BTreeMap::new();
+        let mut containing: BTreeMap<Node, Vec<Node>> = BTreeMap::new();
+        for range in self.used_by.keys() {
+            let Node::Range { sheet, start, end } = range else {
+                continue;
+            };
+            for (&(_, col), rows) in
+                columns.range((sheet.as_str(), start.1)..=(sheet.as_str(), end.1))
+            {
+                for &row in rows.range(start.0..=end.0) {
+                    let cell = Node::Cell {
+                        sheet: sheet.clone(),
+                        row,
+                        col,
+                    };
+                    if self.formulas.contains_key(&cell) {
+                        members.entry(range.clone()).or_default().push(cell.clone());
+                    }
+                    containing.entry(cell).or_default().push(range.clone());
+                }
+            }
+        }
+        self.members = members;
+        self.containing = containing;
+    }
+
+    fn add_formula(&mut self, node: Node, formula: &str, sheet: &str, sheet_names: &[String]) {
+        self.formulas.insert(node.clone(), formula.to_string());
+        let Ok(expr) = parse_formula(formula) else {
+            self.unparsed.push(node);
+            return;
+        };
+        for reference in expr.references() {
+            let precedent = match reference {
+                Expr::Reference(range) => {
+                    // Sheet names match without regard to case; unknown sheets stay as written
+                    let sheet = match &range.sheet {
+                        Some(name) => sheet_names
+                            .iter()
+                            .find(|sheet| sheet.eq_ignore_ascii_case(name))
+                            .unwrap_or(name)
+                            .clone(),
+                        None => sheet.to_string(),
+                    };
+                    if range.start == range.end {
+                        Node::Cell {
+                            sheet,
+                            row: range.start.0,
+                            col: range.start.1,
+                        }
+                    } else {
+                        Node::Range {
+                            sheet,
+                            start: range.start,
+                            end: range.end,
+                        }
+                    }
+                }
+                Expr::Name(name) => Node::Name(self.canonical_name(name)),
+                _ => continue,
+            };
+            self.used_by
+                .entry(precedent.clone())
+                .or_default()
+                .insert(node.clone());
+            self.uses.entry(node.clone()).or_default().insert(precedent);
+        }
+    }
+
+    /// A name as the workbook defines it, since references ignore case.
+    fn canonical_name(&self, name: &str) -> String {
+        self.formulas
+            .keys()
+            .find_map(|node| match node {
+                Node::Name(defined) if defined.eq_ignore_ascii_case(name) => Some(defined.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Formula of a formula cell or defined name.
+    pub fn formula(&self, node: &Node) -> Option<&str> {
+        self.formulas.get(node).map(String::as_str)
+    }
+
+    /// Formula cells and names whose formula could not be parsed.
+    pub fn unparsed(&self) -> &[Node] {
+        &self.unparsed
+    }
+
+    /// A node for a name, if the workbook defines it.
+    pub fn defined_name(&self, name: &str) -> Option<Node> {
+        let node = Node::Name(self.canonical_name(name));
+        self.formulas.contains_key(&node).then_some(node)
+    }
+
+    /// Every node: formula cells, names and everything they refer to.
+    pub fn nodes(&self) -> BTreeSet<Node> {
+        self.formulas
+            .keys()
+            .chain(self.used_by.keys())
+            .cloned()
+            .collect()
+    }
+
+    /// Direct neighbours of a node in one direction.
+    pub fn neighbours(&self, node: &Node, direction: Direction) -> BTreeSet<Node> {
+        let (edges, members) = match direction {
+            Direction::Precedents => (&self.uses, self.range_members(node)),
+            Direction::Dependents => (&self.used_by, self.ranges_containing(node)),
+        };
+        let mut neighbours = edges.get(node).cloned().unwrap_or_default();
+        neighbours.extend(members);
+        neighbours
+    }
+
+    /// Formula cells inside a range.
+    fn range_members(&self, range: &Node) -> Vec<Node> {
+        self.members.get(range).cloned().unwrap_or_default()
+    }
+
+    /// Referenced ranges that take in a cell.
+    fn ranges_containing(&self, cell: &Node) -> Vec<Node> {
+        let Node::Cell { sheet, row, col } = cell else {
+            return Vec::new();
+        };
+        if self.formulas.contains_key(cell) || self.used_by.contains_key(cell) {
+            return self.containing.get(cell).cloned().unwrap_or_default();
+        }
+        // A cell outside the graph, e.g. an input only referenced as part of a range
+        self.used_by
+            .keys()
+            .filter(|range| range.contains(sheet, *row, *col))
+            .cloned()
+            .collect()
+    }
+
+    /// The node and everything reachable from it in one direction.
+    pub fn reachable(&self, start: &Node, direction: Direction) -> BTreeSet<Node> {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![start.clone()];
+        while let Some(node) = pending.pop() {
+            if seen.insert(node.clone()) {
+                pending.extend(self.neighbours(&node, direction));
+            }
+        }
+        seen
+    }
+
+    /// Edges `(precedent, dependent)` between the given nodes.
+    pub fn edges(&self, nodes: &BTreeSet<Node>) -> Vec<(Node, Node)> {
+        let mut edges = Vec::new();
+        for node in nodes {
+            for precedent in self.neighbours(node, Direction::Precedents) {
+                if nodes.contains(&precedent) {
+                    edges.push((precedent, node.clone()));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Nodes at the end of every chain: those nothing further depends on,
+    /// or, for dependents, those that depend on nothing.
+    pub fn roots(&self, direction: Direction) -> Vec<Node> {
+        let opposite = match direction {
+            Direction::Precedents => Direction::Dependents,
+            Direction::Dependents => Direction::Precedents,
+        };
+        self.nodes()
+            .into_iter()
+            .filter(|node| self.neighbours(node, opposite).is_empty())
+            .filter(|node| !self.neighbours(node, direction).is_empty())
+            .collect()
+    }
+
+    fn describe(&self, node: &Node) -> String {
+        match self.formula(node) {
+            Some(formula) => format!("{} ={}", node.label(), formula),
+            None => node.label(),
+        }
+    }
+
+    /// Draw the nodes reachable from `start` as an indented tree. A node
+    /// already drawn is marked `(see above)` instead of being expanded again.
+    pub fn tree(&self, start: &Node, direction: Direction) -> String {
+        let mut out = self.describe(start);
+        out.push('\n');
+        let mut expanded = BTreeSet::from([start.clone()]);
+        // Levels still being drawn: their children, the prefix those are
+        // drawn with and the next one to draw. An explicit stack keeps long
+        // chains of formulas from overflowing the call stack
+        let children =
+            |node: &Node| -> Vec<Node> { self.neighbours(node, direction).into_iter().collect() };
+        let mut stack: Vec<(Vec<Node>, String, usize)> = vec![(children(start), String::new(), 0)];
+        while let Some((siblings, prefix, next)) = stack.last_mut() {
+            let Some(child) = siblings.get(*next).cloned() else {
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            let (branch, indent) = if *next == siblings.len() {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let grandchildren = children(&child);
+            if !grandchildren.is_empty() && !expanded.insert(child.clone()) {
+                out.push_str(&format!(
+                    "{}{}{} (see above)\n",
+                    prefix,
+                    branch,
+                    child.label()
+                ));
+                continue;
+            }
+            out.push_str(&format!("{}{}{}\n", prefix, branch, self.describe(&child)));
+            let child_prefix = format!("{}{}", prefix, indent);
+            stack.push((grandchildren, child_prefix, 0));
+        }
+        out
+    }
+
+    /// The nodes and the edges between them in Graphviz DOT, one cluster
+    /// per sheet, with edges pointing from precedent to dependent.
+    pub fn to_dot(&self, nodes: &BTreeSet<Node>) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let attributes = |node: &Node| {
+            let shape = match node {
+                Node::Cell { .. } if self.formula(node).is_some() => "box",
+                Node::Cell { .. } => "ellipse",
+                Node::Range { .. } => "box, style=dashed",
+                Node::Name(_) => "hexagon",
+            };
+            let label = match node {
+                Node::Cell { row, col, .. } => cell_address(*row, *col),
+                // Drop the sheet, which the cluster already shows
+                _ => node
+                    .label()
+                    .rsplit_once('!')
+                    .map_or(node.label(), |(_, range)| range.to_string()),
+            };
+            format!(
+                "{} [label={}, shape={}];",
+                quote(&node.label()),
+                quote(&label),
+                shape
+            )
+        };
+
+        let mut sheets: BTreeMap<&str, Vec<&Node>> = BTreeMap::new();
+        let mut names = Vec::new();
+        for node in nodes {
+            match node.sheet() {
+                Some(sheet) => sheets.entry(sheet).or_default().push(node),
+                None => names.push(node),
+            }
+        }
+
+        let mut out = String::from("digraph dependencies {\n    rankdir=LR;\n");
+        for (idx, (sheet, members)) in sheets.iter().enumerate() {
+            out.push_str(&format!("    subgraph cluster_{} {{\n", idx));
+            out.push_str(&format!("        label={};\n", quote(sheet)));
+            for node in members {
+                out.push_str(&format!("        {}\n", attributes(node)));
+            }
+            out.push_str("    }\n");
+        }
+        for node in names {
+            out.push_str(&format!("    {}\n", attributes(node)));
+        }
+        for (from, to) in self.edges(nodes) {
+            out.push_str(&format!(
+                "    {} -> {};\n",
+                quote(&from.label()),
+                quote(&to.label())
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// The nodes and edges as JSON; edges run from precedent to dependent.
+    pub fn to_json(&self, nodes: &BTreeSet<Node>) -> serde_json::Value {
+        let node_values: Vec<serde_json::Value> = nodes
+            .iter()
+            .map(|node| {
+                json!({
+                    "id": node.label(),
+                    "kind": node.kind(),
+                    "sheet": node.sheet(),
+                    "formula": self.formula(node),
+                })
+            })
+            .collect();
+        let edges: Vec<serde_json::Value> = self
+            .edges(nodes)
+            .iter()
+            .map(|(from, to)| json!({ "from": from.label(), "to": to.label() }))
+            .collect();
+        json!({ "nodes": node_values, "edges": edges })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::open_excel_file;
+    use tempfile::NamedTempFile;
+
+    fn cell(sheet: &str, row: usize, col: usize) -> Node {
+        Node::Cell {
+            sheet: sheet.to_string(),
+            row,
+            col,
+        }
+    }
+
+    fn sample_graph() -> DependencyGraph {
+        use xlsxwriter::*;
+
+        let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let workbook = Workbook::new(temp_file.path().to_str().unwrap()).unwrap();
+        let mut inputs = workbook.add_worksheet(Some("Inputs")).unwrap();
+        inputs.write_number(0, 1, 0.05, None).unwrap();
+        inputs.write_number(1, 1, 10.0, None).unwrap();
+        inputs.write_number(2, 1, 20.0, None).unwrap();
+        let mut summary = workbook.add_worksheet(Some("Summary")).unwrap();
+        summary.write_formula(0, 0, "=Inputs!B2*2", None).unwrap();
+        summary.write_formula(1, 0, "=Inputs!B3*2", None).unwrap();
+        summary
+            .write_formula(2, 0, "=SUM(A1:A2)*(1+rate)", None)
+            .unwrap();
+        summary.write_formula(3, 0, "=A3+A1", None).unwrap();
+        workbook.define_name("Rate", "=Inputs!$B$1").unwrap();
+        workbook.close().unwrap();
+
+        let mut workbook = open_excel_file(temp_file.path()).unwrap();
+        DependencyGraph::build(&mut workbook).unwrap()
+    }
+
+    #[test]
+    fn test_precedents() {
+        let graph = sample_graph();
+        let total = cell("Summary", 2, 0);
+        assert_eq!(graph.formula(&total), Some("SUM(A1:A2)*(1+rate)"));
+
+        let reachable = graph.reachable(&total, Direction::Precedents);
+        let labels: Vec<String> = reachable.iter().map(Node::label).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "Inputs!B1",
+                "Inputs!B2",
+                "Inputs!B3",
+                "Summary!A1",
+                "Summary!A2",
+                "Summary!A3",
+                "Summary!A1:A2",
+                "Rate"
+            ]
+        );
+        assert_eq!(
+            graph.tree(&total, Direction::Precedents),
+            "Summary!A3 =SUM(A1:A2)*(1+rate)
+├── Summary!A1:A2
+│   ├── Summary!A1 =Inputs!B2*2
+│   │   └── Inputs!B2
+│   └── Summary!A2 =Inputs!B3*2
+│       └── Inputs!B3
+└── Rate =Inputs!$B$1
+    └── Inputs!B1
+"
+        );
+    }
+
+    #[test]
+    fn test_dependents() {
+        let graph = sample_graph();
+        // Through the range, an input reaches both totals
+        let reachable = graph.reachable(&cell("Inputs", 1, 1), Direction::Dependents);
+        let labels: Vec<String> = reachable.iter().map(Node::label).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "Inputs!B2",
+                "Summary!A1",
+                "Summary!A3",
+                "Summary!A4",
+                "Summary!A1:A2"
+            ]
+        );
+        assert_eq!(
+            graph.tree(&cell("Inputs", 1, 1), Direction::Dependents),
+            "Inputs!B2
+└── Summary!A1 =Inputs!B2*2
+    ├── Summary!A4 =A3+A1
+    └── Summary!A1:A2
+        └── Summary!A3 =SUM(A1:A2)*(1+rate)
+            └── Summary!A4 =A3+A1
+"
+        );
+
+        assert_eq!(
+            graph.roots(Direction::Precedents),
+            vec![cell("Summary", 3, 0)]
+        );
+    }
+
+    #[test]
+    fn test_tree_long_chain() {
+        use xlsxwriter::*;
+
+        let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let workbook = Workbook::new(temp_file.path().to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Balance")).unwrap();
+        sheet.write_number(0, 0, 1.0, None).unwrap();
+        for row in 1..5_000 {
+            sheet
+                .write_formula(row, 0, &format!("=A{}+1", row), None)
+                .unwrap();
+        }
+        workbook.close().unwrap();
+
+        let mut workbook = open_excel_file(temp_file.path()).unwrap();
+        let graph = DependencyGraph::build(&mut workbook).unwrap();
+        let tree = graph.tree(&cell("Balance", 4_999, 0), Direction::Precedents);
+        assert_eq!(tree.lines().count(), 5_000);
+        assert!(tree.starts_with("Balance!A5000 =A4999+1\n└── Balance!A4999 =A4998+1\n"));
+        assert!(tree.ends_with("└── Balance!A1\n"));
+    }
+
+    #[test]
+    fn test_many_ranges() {
+        use xlsxwriter::*;
+
+        // Running totals over a growing range, each a root of its own
+        let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let workbook = Workbook::new(temp_file.path().to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Totals")).unwrap();
+        for row in 0..20_000 {
+            sheet.write_number(row, 0, 1.0, None).unwrap();
+            sheet
+                .write_formula(row, 1, &format!("=SUM(A$1:A{})", row + 1), None)
+                .unwrap();
+        }
+        workbook.close().unwrap();
+
+        let mut workbook = open_excel_file(temp_file.path()).unwrap();
+        let graph = DependencyGraph::build(&mut workbook).unwrap();
+        let roots = graph.roots(Direction::Precedents);
+        assert_eq!(roots.len(), 20_000);
+        assert_eq!(
+            graph.tree(&roots[1], Direction::Precedents),
+            "Totals!B2 =SUM(A$1:A2)\n└── Totals!A1:A2\n"
+        );
+        let nodes = graph.nodes();
+        assert_eq!(graph.edges(&nodes).len(), 20_000);
+        // Every range taking in the first input depends on it
+        let dependents = graph.neighbours(&cell("Totals", 0, 0), Direction::Dependents);
+        assert_eq!(dependents.len(), 20_000);
+    }
+
+    #[test]
+    fn test_dot_and_json() {
+        let graph = sample_graph();
+        let nodes = graph.reachable(&cell("Summary", 0, 0), Direction::Precedents);
+        let dot = graph.to_dot(&nodes);
+        assert!(dot.starts_with("digraph dependencies {"));
+        assert!(dot.contains("label=\"Inputs\""));
+        assert!(dot.contains("\"Summary!A1\" [label=\"A1\", shape=box];"));
+        assert!(dot.contains("\"Inputs!B2\" -> \"Summary!A1\";"));
+
+        let json = graph.to_json(&nodes);
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 2);
+        assert_eq!(json["edges"][0]["from"], "Inputs!B2");
+        assert_eq!(json["edges"][0]["to"], "Summary!A1");
+    }
+}
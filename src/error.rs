@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors the library reports for callers that need to tell failures apart.
+#[derive(Debug, Error)]
+pub enum XlsqError {
+    #[error("Failed to open Excel file: {path:?}")]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: calamine::XlsxError,
+    },
+
+    #[error("Failed to read Excel workbook data")]
+    OpenReader {
+        #[source]
+        source: calamine::XlsxError,
+    },
+
+    #[error("Sheet '{name}' not found. Available sheets: {available:?}")]
+    SheetNotFound {
+        name: String,
+        available: Vec<String>,
+    },
+
+    #[error("Sheet index {index} out of range. Available sheets: 0-{}", .count.saturating_sub(1))]
+    SheetIndexOutOfRange { index: usize, count: usize },
+
+    #[error("Could not read sheet: {sheet}")]
+    UnreadableSheet {
+        sheet: String,
+        #[source]
+        source: calamine::XlsxError,
+    },
+
+    #[error("Unsupported format for {path:?}: {reason}")]
+    UnsupportedFormat { path: PathBuf, reason: String },
+}
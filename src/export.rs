@@ -0,0 +1,434 @@
+use crate::{format_cell, get_sheet_name, read_sheet, XlsqError};
+use anyhow::{bail, Context, Result};
+use calamine::{Data, DataType, Range, Reader, Xlsx};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    Json,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    #[cfg(feature = "parquet")]
+    ArrowIpc,
+}
+
+impl ExportFormat {
+    /// Pick the output format from a file extension (`.csv`, `.tsv`, `.json`,
+    /// plus `.parquet` and `.arrow` with the `parquet` feature).
+    pub fn from_path(path: &Path) -> Result<Self, XlsqError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("csv") => Ok(ExportFormat::Csv),
+            Some("tsv") | Some("tab") => Ok(ExportFormat::Tsv),
+            Some("json") => Ok(ExportFormat::Json),
+            #[cfg(feature = "parquet")]
+            Some("parquet") => Ok(ExportFormat::Parquet),
+            #[cfg(feature = "parquet")]
+            Some("arrow") | Some("ipc") | Some("feather") => Ok(ExportFormat::ArrowIpc),
+            #[cfg(not(feature = "parquet"))]
+            Some("parquet") | Some("arrow") | Some("ipc") | Some("feather") => {
+                Err(XlsqError::UnsupportedFormat {
+                    path: path.to_path_buf(),
+                    reason: "xlsq was built without the `parquet` feature".to_string(),
+                })
+            }
+            _ => Err(XlsqError::UnsupportedFormat {
+                path: path.to_path_buf(),
+                reason: "use a .csv, .tsv or .json extension".to_string(),
+            }),
+        }
+    }
+}
+
+/// Turn a sheet name into something safe to use as a file name on any platform.
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let trimmed = sanitized.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if trimmed.is_empty() {
+        "sheet".to_string()
+    } else {
+        avoid_reserved_names(trimmed)
+    }
+}
+
+/// Device names Windows reserves, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Drop trailing dots and spaces, which Windows strips from file names, and
+/// add `_` after a reserved device name before the first dot (`CON.csv`
+/// becomes `CON_.csv`).
+fn avoid_reserved_names(name: &str) -> String {
+    let name = name.trim_end_matches(['.', ' ']);
+    let (stem, rest) = name.split_at(name.find('.').unwrap_or(name.len()));
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.trim_end().eq_ignore_ascii_case(reserved))
+    {
+        format!("{}_{}", stem, rest)
+    } else {
+        name.to_string()
+    }
+}
+
+fn escape_delimited(value: &str, delimiter: char) -> String {
+    if value.contains(delimiter)
+        || value.contains('"')
+        || value.contains('\n')
+        || value.contains('\r')
+    {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn cell_to_json(cell: &Data) -> Value {
+    match cell {
+        Data::Empty => Value::Null,
+        Data::Int(i) => Value::from(*i),
+        Data::Float(f) if f.is_finite() => {
+            if f.fract() == 0.0 && f.abs() < i64::MAX as f64 {
+                Value::from(*f as i64)
+            } else {
+                Value::from(*f)
+            }
+        }
+        Data::Bool(b) => Value::Bool(*b),
+        other => Value::String(format_cell(other)),
+    }
+}
+
+/// Date and time of a date cell. ISO values holding only a date (`2024-03-31`)
+/// are read as midnight; ones holding only a time have no timestamp.
+pub(crate) fn cell_timestamp(cell: &Data) -> Option<NaiveDateTime> {
+    match cell {
+        Data::DateTimeIso(iso) => cell.as_datetime().or_else(|| {
+            NaiveDate::parse_from_str(iso, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        }),
+        _ => cell.as_datetime(),
+    }
+}
+
+/// Column type inferred from the non-empty cells below the header row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int,
+    Float,
+    Bool,
+    Timestamp,
+    Utf8,
+}
+
+impl ColumnType {
+    fn of(cell: &Data) -> Option<ColumnType> {
+        match cell {
+            Data::Empty => None,
+            Data::Int(_) => Some(ColumnType::Int),
+            Data::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                Some(ColumnType::Int)
+            }
+            Data::Float(_) => Some(ColumnType::Float),
+            Data::Bool(_) => Some(ColumnType::Bool),
+            Data::DateTime(_) => Some(ColumnType::Timestamp),
+            Data::DateTimeIso(_) if cell_timestamp(cell).is_some() => Some(ColumnType::Timestamp),
+            _ => Some(ColumnType::Utf8),
+        }
+    }
+
+    fn merge(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Int, ColumnType::Float) | (ColumnType::Float, ColumnType::Int) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::Utf8,
+        }
+    }
+}
+
+/// Infer a column type, ignoring empty cells. Mixed columns fall back to `Utf8`.
+pub fn infer_column_type<'a>(cells: impl IntoIterator<Item = &'a Data>) -> ColumnType {
+    cells
+        .into_iter()
+        .filter_map(ColumnType::of)
+        .reduce(ColumnType::merge)
+        .unwrap_or(ColumnType::Utf8)
+}
+
+pub(crate) fn json_headers(range: &Range<Data>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut headers = Vec::with_capacity(range.width());
+
+    for col_idx in 0..range.width() {
+        let base = match range.rows().next().and_then(|row| row.get(col_idx)) {
+            Some(cell) if !format_cell(cell).is_empty() => format_cell(cell),
+            _ => format!("column_{}", col_idx + 1),
+        };
+
+        let mut header = base.clone();
+        let mut suffix = 2;
+        while !seen.insert(header.clone()) {
+            header = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        headers.push(header);
+    }
+
+    headers
+}
+
+/// Write a whole range in the given format. JSON output is an array of objects
+/// keyed by the first row.
+pub fn write_range<W: Write + Send>(
+    range: &Range<Data>,
+    format: ExportFormat,
+    writer: &mut W,
+) -> Result<()> {
+    match format {
+        ExportFormat::Csv | ExportFormat::Tsv => {
+            let delimiter = if format == ExportFormat::Csv {
+                ','
+            } else {
+                '\t'
+            };
+            for row in range.rows() {
+                let line: Vec<String> = row
+                    .iter()
+                    .map(|cell| escape_delimited(&format_cell(cell), delimiter))
+                    .collect();
+                writeln!(writer, "{}", line.join(&delimiter.to_string()))?;
+            }
+        }
+        ExportFormat::Json => {
+            let headers = json_headers(range);
+            let records: Vec<Value> = range
+                .rows()
+                .skip(1)
+                .map(|row| {
+                    let record: Map<String, Value> = headers
+                        .iter()
+                        .cloned()
+                        .zip(row.iter().map(cell_to_json))
+                        .collect();
+                    Value::Object(record)
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut *writer, &records)?;
+            writeln!(writer)?;
+        }
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => crate::columnar::write_parquet(range, writer)?,
+        #[cfg(feature = "parquet")]
+        ExportFormat::ArrowIpc => crate::columnar::write_arrow_ipc(range, writer)?,
+    }
+
+    Ok(())
+}
+
+fn write_range_to_file(range: &Range<Data>, path: &Path) -> Result<()> {
+    let format = ExportFormat::from_path(path)?;
+    let file = File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+    write_range(range, format, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn export_sheet<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_identifier: &str,
+    output: &Path,
+    force: bool,
+) -> Result<()> {
+    let sheet_name = get_sheet_name(workbook, sheet_identifier)?;
+    ExportFormat::from_path(output)?;
+
+    if output.exists() && !force {
+        bail!("{:?} already exists. Use --force to overwrite", output);
+    }
+
+    let range = read_sheet(workbook, &sheet_name)?;
+    write_range_to_file(&range, output)?;
+    println!("Exported sheet '{}' to {}", sheet_name, output.display());
+
+    Ok(())
+}
+
+/// Expand a `--name-template` such as `{file}_{sheet}.csv` for one sheet.
+pub fn render_name_template(template: &str, file_stem: &str, sheet_name: &str) -> String {
+    let file_name = template
+        .replace("{file}", &sanitize_file_name(file_stem))
+        .replace("{sheet}", &sanitize_file_name(sheet_name));
+    avoid_reserved_names(&file_name)
+}
+
+pub fn export_all_sheets<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    file_stem: &str,
+    output_dir: &Path,
+    name_template: &str,
+    force: bool,
+) -> Result<()> {
+    if !name_template.contains("{sheet}") {
+        bail!("Name template must contain {{sheet}} when exporting all sheets");
+    }
+
+    // Work out every target up front so nothing is written if any of them clash.
+    let mut targets: Vec<(String, PathBuf)> = Vec::new();
+    let mut used = HashSet::new();
+    for sheet_name in workbook.sheet_names() {
+        let file_name = render_name_template(name_template, file_stem, &sheet_name);
+        let mut path = output_dir.join(&file_name);
+        let mut suffix = 2;
+        while !used.insert(path.clone()) {
+            let stem = Path::new(&file_name)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(&file_name);
+            let renamed = match Path::new(&file_name).extension().and_then(|e| e.to_str()) {
+                Some(ext) => format!("{}_{}.{}", stem, suffix, ext),
+                None => format!("{}_{}", stem, suffix),
+            };
+            path = output_dir.join(renamed);
+            suffix += 1;
+        }
+        ExportFormat::from_path(&path)?;
+        targets.push((sheet_name, path));
+    }
+
+    if !force {
+        if let Some((_, existing)) = targets.iter().find(|(_, path)| path.exists()) {
+            bail!("{:?} already exists. Use --force to overwrite", existing);
+        }
+    }
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create output directory {:?}", output_dir))?;
+
+    for (sheet_name, path) in &targets {
+        let range = read_sheet(workbook, sheet_name)?;
+        write_range_to_file(&range, path)?;
+        println!("Exported sheet '{}' to {}", sheet_name, path.display());
+    }
+
+    println!("Exported {} sheet(s)", targets.len());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_range() -> Range<Data> {
+        let mut range = Range::new((0, 0), (2, 1));
+        range.set_value((0, 0), Data::String("Name".to_string()));
+        range.set_value((0, 1), Data::String("Amount".to_string()));
+        range.set_value((1, 0), Data::String("Doe, John".to_string()));
+        range.set_value((1, 1), Data::Float(10.5));
+        range.set_value((2, 0), Data::String("Say \"hi\"".to_string()));
+        range
+    }
+
+    #[test]
+    fn test_export_format_from_path() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out/a.CSV")).unwrap(),
+            ExportFormat::Csv
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("a.tsv")).unwrap(),
+            ExportFormat::Tsv
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("a.json")).unwrap(),
+            ExportFormat::Json
+        );
+        assert!(matches!(
+            ExportFormat::from_path(Path::new("a.xlsx")),
+            Err(XlsqError::UnsupportedFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("Q1/Q2: Sales?"), "Q1_Q2_ Sales_");
+        assert_eq!(sanitize_file_name(" ..hidden.. "), "hidden");
+        assert_eq!(sanitize_file_name("..."), "sheet");
+        assert_eq!(
+            render_name_template("{file}_{sheet}.csv", "book", "A|B"),
+            "book_A_B.csv"
+        );
+        // Windows device names, whatever their case or extension
+        assert_eq!(sanitize_file_name("con"), "con_");
+        assert_eq!(sanitize_file_name("LPT1.backup"), "LPT1_.backup");
+        assert_eq!(sanitize_file_name("Console"), "Console");
+        assert_eq!(
+            render_name_template("{sheet}.csv", "book", "Aux"),
+            "Aux_.csv"
+        );
+        assert_eq!(
+            render_name_template("{file}_{sheet}.csv", "nul", "A"),
+            "nul__A.csv"
+        );
+        assert_eq!(render_name_template("{sheet}. ", "book", "Sales"), "Sales");
+    }
+
+    #[test]
+    fn test_infer_column_type() {
+        let ints = [Data::Float(1.0), Data::Empty, Data::Int(3)];
+        assert_eq!(infer_column_type(&ints), ColumnType::Int);
+
+        let floats = [Data::Float(1.5), Data::Int(3)];
+        assert_eq!(infer_column_type(&floats), ColumnType::Float);
+
+        let mixed = [Data::Bool(true), Data::Int(3)];
+        assert_eq!(infer_column_type(&mixed), ColumnType::Utf8);
+
+        assert_eq!(infer_column_type(&[Data::Empty]), ColumnType::Utf8);
+    }
+
+    #[test]
+    fn test_write_range_csv_quotes_fields() {
+        let mut out = Vec::new();
+        write_range(&sample_range(), ExportFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Name,Amount\n\"Doe, John\",10.5\n\"Say \"\"hi\"\"\",\n"
+        );
+    }
+
+    #[test]
+    fn test_write_range_json_records() {
+        let mut out = Vec::new();
+        write_range(&sample_range(), ExportFormat::Json, &mut out).unwrap();
+        let value: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value[0]["Name"], "Doe, John");
+        assert_eq!(value[0]["Amount"], 10.5);
+        assert_eq!(value[1]["Amount"], Value::Null);
+    }
+}
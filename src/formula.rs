@@ -0,0 +1,728 @@
+use crate::address::{column_name, parse_cell_address, parse_range_reference, RangeReference};
+use anyhow::{bail, Result};
+
+/// Last row and column of a worksheet, 0-based.
+pub const MAX_ROW: usize = 1_048_575;
+pub const MAX_COL: usize = 16_383;
+
+/// A parsed formula, as stored in a workbook (without the leading `=`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    /// An error literal such as `#N/A`.
+    Error(String),
+    /// A cell or range, e.g. `B2`, `Inputs!$B$2:$B$9` or the whole column `A:A`.
+    Reference(RangeReference),
+    /// A defined name.
+    Name(String),
+    /// An argument left out, as in `IF(A1,,1)`.
+    Missing,
+    Negate(Box<Expr>),
+    Percent(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// A function call; the name is upper case, without any `_xlfn.` prefix.
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Concat,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Expr {
+    /// Every reference in the formula, in the order written: each is an
+    /// `Expr::Reference` or an `Expr::Name`.
+    pub fn references(&self) -> Vec<&Expr> {
+        let mut found = Vec::new();
+        self.collect_references(&mut found);
+        found
+    }
+
+    fn collect_references<'a>(&'a self, found: &mut Vec<&'a Expr>) {
+        match self {
+            Expr::Reference(_) | Expr::Name(_) => found.push(self),
+            Expr::Negate(inner) | Expr::Percent(inner) => inner.collect_references(found),
+            Expr::Binary(_, left, right) => {
+                left.collect_references(found);
+                right.collect_references(found);
+            }
+            Expr::Call(_, args) => {
+                for arg in args {
+                    arg.collect_references(found);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(String),
+    Reference(RangeReference),
+    Name(String),
+    /// A function name; the opening parenthesis is part of the token.
+    Function(String),
+    Operator(&'static str),
+    Open,
+    Close,
+    Comma,
+}
+
+const ERROR_LITERALS: [&str; 8] = [
+    "#NULL!",
+    "#DIV/0!",
+    "#VALUE!",
+    "#REF!",
+    "#NAME?",
+    "#NUM!",
+    "#N/A",
+    "#GETTING_DATA",
+];
+
+/// Operators, longest first so `<=` wins over `<`.
+const OPERATORS: [&str; 13] = [
+    "<=", ">=", "<>", "+", "-", "*", "/", "^", "&", "=", "<", ">", "%",
+];
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '$' | '\\')
+}
+
+fn tokenize(formula: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    // Read a run of word characters starting at `pos`
+    let word = |pos: &mut usize| -> String {
+        let start = *pos;
+        while *pos < chars.len() && is_word_char(chars[*pos]) {
+            *pos += 1;
+        }
+        chars[start..*pos].iter().collect()
+    };
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        match c {
+            ' ' | '\t' | '\r' | '\n' => pos += 1,
+            '"' => {
+                let mut text = String::new();
+                pos += 1;
+                loop {
+                    match chars.get(pos) {
+                        Some('"') if chars.get(pos + 1) == Some(&'"') => {
+                            text.push('"');
+                            pos += 2;
+                        }
+                        Some('"') => {
+                            pos += 1;
+                            break;
+                        }
+                        Some(&c) => {
+                            text.push(c);
+                            pos += 1;
+                        }
+                        None => bail!("Unterminated string in formula {:?}", formula),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '#' => {
+                let rest: String = chars[pos..].iter().collect();
+                let Some(literal) = ERROR_LITERALS
+                    .iter()
+                    .find(|literal| rest.to_ascii_uppercase().starts_with(*literal))
+                else {
+                    bail!("Unknown error literal in formula {:?}", formula);
+                };
+                tokens.push(Token::Error(literal.to_string()));
+                pos += literal.chars().count();
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                pos += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                pos += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                pos += 1;
+            }
+            '{' => bail!("Array constants are not supported: {:?}", formula),
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(pos + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let start = pos;
+                while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                    pos += 1;
+                }
+                if matches!(chars.get(pos), Some('e' | 'E')) {
+                    let mut end = pos + 1;
+                    if matches!(chars.get(end), Some('+' | '-')) {
+                        end += 1;
+                    }
+                    if chars.get(end).is_some_and(char::is_ascii_digit) {
+                        pos = end;
+                        while pos < chars.len() && chars[pos].is_ascii_digit() {
+                            pos += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..pos].iter().collect();
+                match text.parse() {
+                    Ok(number) => tokens.push(Token::Number(number)),
+                    Err(_) => bail!("Invalid number {:?} in formula {:?}", text, formula),
+                }
+            }
+            '\'' => {
+                // A quoted sheet name, which must be followed by `!` and a reference
+                let start = pos;
+                pos += 1;
+                loop {
+                    match chars.get(pos) {
+                        Some('\'') if chars.get(pos + 1) == Some(&'\'') => pos += 2,
+                        Some('\'') => {
+                            pos += 1;
+                            break;
+                        }
+                        Some(_) => pos += 1,
+                        None => bail!("Unterminated sheet name in formula {:?}", formula),
+                    }
+                }
+                if chars.get(pos) != Some(&'!') {
+                    bail!("Expected '!' after sheet name in formula {:?}", formula);
+                }
+                let sheet: String = chars[start..pos].iter().collect();
+                pos += 1;
+                tokens.push(qualified_token(&sheet, &chars, &mut pos, &word)?);
+            }
+            c if is_word_char(c) => {
+                let name = word(&mut pos);
+                if chars.get(pos) == Some(&'!') {
+                    pos += 1;
+                    tokens.push(qualified_token(&name, &chars, &mut pos, &word)?);
+                } else if chars.get(pos) == Some(&'(') {
+                    pos += 1;
+                    let upper = name.to_ascii_uppercase();
+                    let upper = upper
+                        .strip_prefix("_XLFN.")
+                        .or_else(|| upper.strip_prefix("_XLWS."))
+                        .unwrap_or(&upper);
+                    tokens.push(Token::Function(upper.to_string()));
+                } else {
+                    let reference = range_text(name, &chars, &mut pos, &word);
+                    tokens.push(classify(None, &reference));
+                }
+            }
+            _ => {
+                let rest: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+                let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(*op)) else {
+                    bail!("Unexpected {:?} in formula {:?}", c, formula);
+                };
+                tokens.push(Token::Operator(operator));
+                pos += operator.len();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Extend a reference such as `A1` with a following `:B2`.
+fn range_text(
+    first: String,
+    chars: &[char],
+    pos: &mut usize,
+    word: &impl Fn(&mut usize) -> String,
+) -> String {
+    if chars.get(*pos) == Some(&':') && chars.get(*pos + 1).is_some_and(|&c| is_word_char(c)) {
+        *pos += 1;
+        format!("{}:{}", first, word(pos))
+    } else {
+        first
+    }
+}
+
+/// The token after `Sheet!`: a reference, a `#REF!` left by a deleted range,
+/// or a sheet-scoped name.
+fn qualified_token(
+    sheet: &str,
+    chars: &[char],
+    pos: &mut usize,
+    word: &impl Fn(&mut usize) -> String,
+) -> Result<Token> {
+    if chars.get(*pos) == Some(&'#') {
+        let rest: String = chars[*pos..].iter().collect();
+        if rest.to_ascii_uppercase().starts_with("#REF!") {
+            *pos += "#REF!".len();
+            return Ok(Token::Error("#REF!".to_string()));
+        }
+    }
+    let first = word(pos);
+    if first.is_empty() {
+        bail!("Expected a reference after {}!", sheet);
+    }
+    let reference = range_text(first, chars, pos, word);
+    Ok(classify(Some(sheet), &reference))
+}
+
+fn classify(sheet: Option<&str>, text: &str) -> Token {
+    let full = match sheet {
+        Some(sheet) => format!("{}!{}", sheet, text),
+        None => text.to_string(),
+    };
+    if let Some(reference) = parse_range_reference(&full).or_else(|| parse_column_range(&full)) {
+        return Token::Reference(reference);
+    }
+    if sheet.is_none() {
+        if text.eq_ignore_ascii_case("TRUE") {
+            return Token::Bool(true);
+        }
+        if text.eq_ignore_ascii_case("FALSE") {
+            return Token::Bool(false);
+        }
+    }
+    Token::Name(text.to_string())
+}
+
+/// Parse a whole-column range such as `A:C` or `Data!$B:$B`.
+fn parse_column_range(reference: &str) -> Option<RangeReference> {
+    let (sheet, range) = match reference.rsplit_once('!') {
+        Some((sheet, range)) => (Some(crate::address::unquote_sheet_name(sheet)), range),
+        None => (None, reference),
+    };
+    let (first, last) = range.split_once(':')?;
+    let column = |text: &str| {
+        let letters = text.strip_prefix('$').unwrap_or(text);
+        if !letters.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return None;
+        }
+        parse_cell_address(&format!("{}1", letters)).map(|(_, col)| col)
+    };
+    let (first, last) = (column(first)?, column(last)?);
+    Some(RangeReference {
+        sheet,
+        start: (0, first.min(last)),
+        end: (MAX_ROW, first.max(last)),
+    })
+}
+
+/// Parse a formula, with or without its leading `=`.
+pub fn parse_formula(formula: &str) -> Result<Expr> {
+    let formula = formula.trim();
+    let formula = formula.strip_prefix('=').unwrap_or(formula);
+    let tokens = tokenize(formula)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.comparison()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        bail!("Unexpected {:?} in formula {:?}", token, formula);
+    }
+    Ok(expr)
+}
+
+/// Move the relative references of a formula by `rows` and `cols`, as
+/// Excel does when a shared formula is filled in from its first cell.
+/// References pushed off the sheet become `#REF!`; text and quoted sheet
+/// names are left alone.
+pub fn shift_formula(formula: &str, rows: i64, cols: i64) -> String {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut shifted = String::with_capacity(formula.len());
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        let start = pos;
+        pos += 1;
+        match c {
+            '"' | '\'' => {
+                // Up to the closing quote; a doubled quote is part of the text
+                while pos < chars.len() {
+                    pos += 1;
+                    if chars[pos - 1] == c {
+                        if chars.get(pos) != Some(&c) {
+                            break;
+                        }
+                        pos += 1;
+                    }
+                }
+                shifted.extend(&chars[start..pos]);
+            }
+            c if is_word_char(c) => {
+                while pos < chars.len() && is_word_char(chars[pos]) {
+                    pos += 1;
+                }
+                let word: String = chars[start..pos].iter().collect();
+                let next = chars.get(pos);
+                // Functions and sheet names aren't references
+                let moved = if matches!(next, Some('(' | '!')) {
+                    None
+                } else {
+                    let in_range = next == Some(&':') || (start > 0 && chars[start - 1] == ':');
+                    shift_reference(&word, rows, cols, in_range)
+                };
+                shifted.push_str(moved.as_deref().unwrap_or(&word));
+            }
+            _ => shifted.push(c),
+        }
+    }
+    shifted
+}
+
+/// `word` moved by `rows` and `cols` when it is a cell reference, or one
+/// end of a whole-column or whole-row range; `None` when it is neither.
+fn shift_reference(word: &str, rows: i64, cols: i64, in_range: bool) -> Option<String> {
+    let (col_absolute, rest) = match word.strip_prefix('$') {
+        Some(rest) => (true, rest),
+        None => (false, word),
+    };
+    let letters_end = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let (letters, rest) = rest.split_at(letters_end);
+    let (row_absolute, digits) = match rest.strip_prefix('$') {
+        Some(digits) => (true, digits),
+        None => (false, rest),
+    };
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let moved = |index: usize, delta: i64, absolute: bool, max: usize| {
+        let index = if absolute {
+            index as i64
+        } else {
+            index as i64 + delta
+        };
+        usize::try_from(index).ok().filter(|&index| index <= max)
+    };
+    let off_sheet = || Some("#REF!".to_string());
+    let dollar = |absolute: bool| if absolute { "$" } else { "" };
+    match (letters.is_empty(), digits.is_empty()) {
+        // A cell such as `B$2`
+        (false, false) => {
+            let (row, col) = parse_cell_address(&format!("{}{}", letters, digits))?;
+            let (Some(row), Some(col)) = (
+                moved(row, rows, row_absolute, MAX_ROW),
+                moved(col, cols, col_absolute, MAX_COL),
+            ) else {
+                return off_sheet();
+            };
+            Some(format!(
+                "{}{}{}{}",
+                dollar(col_absolute),
+                column_name(col),
+                dollar(row_absolute),
+                row + 1
+            ))
+        }
+        // A column of `A:C`
+        (false, true) if in_range && !row_absolute => {
+            let (_, col) = parse_cell_address(&format!("{}1", letters))?;
+            match moved(col, cols, col_absolute, MAX_COL) {
+                Some(col) => Some(format!("{}{}", dollar(col_absolute), column_name(col))),
+                None => off_sheet(),
+            }
+        }
+        // A row of `1:3`, where a `$` comes before the digits
+        (true, false) if in_range && !row_absolute => {
+            let row = digits.parse::<usize>().ok()?.checked_sub(1)?;
+            match moved(row, rows, col_absolute, MAX_ROW) {
+                Some(row) => Some(format!("{}{}", dollar(col_absolute), row + 1)),
+                None => off_sheet(),
+            }
+        }
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_operator(&self, operators: &[&str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Operator(op)) if operators.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Parse a left-associative level: `operand (op operand)*`.
+    fn binary_level(
+        &mut self,
+        operators: &[(&str, BinaryOp)],
+        operand: fn(&mut Self) -> Result<Expr>,
+    ) -> Result<Expr> {
+        let symbols: Vec<&str> = operators.iter().map(|(symbol, _)| *symbol).collect();
+        let mut left = operand(self)?;
+        while let Some(symbol) = self.peek_operator(&symbols) {
+            self.pos += 1;
+            let op = operators
+                .iter()
+                .find(|(s, _)| *s == symbol)
+                .map(|(_, op)| *op)
+                .unwrap_or(BinaryOp::Add);
+            let right = operand(self)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        self.binary_level(
+            &[
+                ("=", BinaryOp::Equal),
+                ("<>", BinaryOp::NotEqual),
+                ("<", BinaryOp::Less),
+                ("<=", BinaryOp::LessEqual),
+                (">", BinaryOp::Greater),
+                (">=", BinaryOp::GreaterEqual),
+            ],
+            Self::concat,
+        )
+    }
+
+    fn concat(&mut self) -> Result<Expr> {
+        self.binary_level(&[("&", BinaryOp::Concat)], Self::additive)
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        self.binary_level(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Subtract)],
+            Self::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr> {
+        self.binary_level(
+            &[("*", BinaryOp::Multiply), ("/", BinaryOp::Divide)],
+            Self::power,
+        )
+    }
+
+    fn power(&mut self) -> Result<Expr> {
+        self.binary_level(&[("^", BinaryOp::Power)], Self::percent)
+    }
+
+    fn percent(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek_operator(&["%"]).is_some() {
+            self.pos += 1;
+            expr = Expr::Percent(Box::new(expr));
+        }
+        Ok(expr)
+    }
+
+    /// Negation binds tighter than `^`, so `-2^2` is 4 as in Excel.
+    fn unary(&mut self) -> Result<Expr> {
+        match self.peek_operator(&["-", "+"]) {
+            Some("-") => {
+                self.pos += 1;
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            Some(_) => {
+                self.pos += 1;
+                self.unary()
+            }
+            None => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            bail!("Formula ends unexpectedly");
+        };
+        self.pos += 1;
+        Ok(match token {
+            Token::Number(number) => Expr::Number(number),
+            Token::Text(text) => Expr::Text(text),
+            Token::Bool(value) => Expr::Bool(value),
+            Token::Error(code) => Expr::Error(code),
+            Token::Reference(reference) => Expr::Reference(reference),
+            Token::Name(name) => Expr::Name(name),
+            Token::Open => {
+                let expr = self.comparison()?;
+                if self.tokens.get(self.pos) != Some(&Token::Close) {
+                    bail!("Missing ')'");
+                }
+                self.pos += 1;
+                expr
+            }
+            Token::Function(name) => Expr::Call(name, self.arguments()?),
+            token => bail!("Unexpected {:?}", token),
+        })
+    }
+
+    /// Arguments up to and including the closing parenthesis.
+    fn arguments(&mut self) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.tokens.get(self.pos) == Some(&Token::Close) {
+            self.pos += 1;
+            return Ok(args);
+        }
+        loop {
+            match self.tokens.get(self.pos) {
+                Some(Token::Comma | Token::Close) => args.push(Expr::Missing),
+                _ => args.push(self.comparison()?),
+            }
+            match self.tokens.get(self.pos) {
+                Some(Token::Comma) => self.pos += 1,
+                Some(Token::Close) => {
+                    self.pos += 1;
+                    return Ok(args);
+                }
+                _ => bail!("Missing ')' after function arguments"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(sheet: Option<&str>, start: (usize, usize), end: (usize, usize)) -> Expr {
+        Expr::Reference(RangeReference {
+            sheet: sheet.map(str::to_string),
+            start,
+            end,
+        })
+    }
+
+    fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        Expr::Binary(op, Box::new(left), Box::new(right))
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        assert_eq!(
+            parse_formula("=1+2*3").unwrap(),
+            binary(
+                BinaryOp::Add,
+                Expr::Number(1.0),
+                binary(BinaryOp::Multiply, Expr::Number(2.0), Expr::Number(3.0))
+            )
+        );
+        assert_eq!(
+            parse_formula("-2^2").unwrap(),
+            binary(
+                BinaryOp::Power,
+                Expr::Negate(Box::new(Expr::Number(2.0))),
+                Expr::Number(2.0)
+            )
+        );
+        assert_eq!(
+            parse_formula("A1&\"x\"=\"1x\"").unwrap(),
+            binary(
+                BinaryOp::Equal,
+                binary(
+                    BinaryOp::Concat,
+                    reference(None, (0, 0), (0, 0)),
+                    Expr::Text("x".to_string())
+                ),
+                Expr::Text("1x".to_string())
+            )
+        );
+        assert_eq!(
+            parse_formula("50%").unwrap(),
+            Expr::Percent(Box::new(Expr::Number(50.0)))
+        );
+    }
+
+    #[test]
+    fn test_parse_references() {
+        assert_eq!(
+            parse_formula("SUM(Inputs!$B$2:B4,'Q1 Sales'!C3,A:B)").unwrap(),
+            Expr::Call(
+                "SUM".to_string(),
+                vec![
+                    reference(Some("Inputs"), (1, 1), (3, 1)),
+                    reference(Some("Q1 Sales"), (2, 2), (2, 2)),
+                    reference(None, (0, 0), (MAX_ROW, 1)),
+                ]
+            )
+        );
+        assert_eq!(
+            parse_formula("_xlfn.IFNA(Rates,TRUE)").unwrap(),
+            Expr::Call(
+                "IFNA".to_string(),
+                vec![Expr::Name("Rates".to_string()), Expr::Bool(true)]
+            )
+        );
+        assert_eq!(
+            parse_formula("IF(A1,,\"say \"\"hi\"\"\")").unwrap(),
+            Expr::Call(
+                "IF".to_string(),
+                vec![
+                    reference(None, (0, 0), (0, 0)),
+                    Expr::Missing,
+                    Expr::Text("say \"hi\"".to_string())
+                ]
+            )
+        );
+        assert_eq!(
+            parse_formula("Old!#REF!+1.5E+3").unwrap(),
+            binary(
+                BinaryOp::Add,
+                Expr::Error("#REF!".to_string()),
+                Expr::Number(1500.0)
+            )
+        );
+    }
+
+    #[test]
+    fn test_references() {
+        let expr = parse_formula("IF(Rate>0,SUM(B2:B4)*Rate,-Inputs!A1)").unwrap();
+        assert_eq!(
+            expr.references(),
+            vec![
+                &Expr::Name("Rate".to_string()),
+                &reference(None, (1, 1), (3, 1)),
+                &Expr::Name("Rate".to_string()),
+                &reference(Some("Inputs"), (0, 0), (0, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_formula("SUM(1,2").is_err());
+        assert!(parse_formula("1+").is_err());
+        assert!(parse_formula("\"open").is_err());
+        assert!(parse_formula("{1,2}").is_err());
+        assert!(parse_formula("1 2").is_err());
+    }
+
+    #[test]
+    fn test_shift_formula() {
+        assert_eq!(
+            shift_formula("SUM(A1:B$2)*$C3+Rate", 2, 1),
+            "SUM(B3:C$2)*$C5+Rate"
+        );
+        assert_eq!(
+            shift_formula("'Q1 A1'!A1&\"A1\"&LOG10(A:A)", 1, 1),
+            "'Q1 A1'!B2&\"A1\"&LOG10(B:B)"
+        );
+        assert_eq!(shift_formula("SUM(1:$3)+1.5", 1, 0), "SUM(2:$3)+1.5");
+        assert_eq!(shift_formula("A1+1", -1, 0), "#REF!+1");
+    }
+}
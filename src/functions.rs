@@ -0,0 +1,1130 @@
+//! The worksheet functions `calc` understands.
+
+use crate::calc::{compare, error, number, Eval, Model, Value};
+use crate::formula::Expr;
+use crate::number_format::format_with_code;
+use anyhow::{bail, Result};
+use calamine::Data;
+use chrono::{Datelike, Days, Local, Months, NaiveDate, Timelike};
+use std::cmp::Ordering;
+use std::io::{Read, Seek};
+
+/// Serial number of 31 December 9999, the last date Excel handles.
+const MAX_SERIAL: f64 = 2_958_465.0;
+
+/// Most characters a cell holds.
+const MAX_TEXT_LENGTH: f64 = 32_767.0;
+
+/// Supported functions with their smallest and largest argument counts.
+pub const FUNCTIONS: &[(&str, usize, usize)] = &[
+    // Math and aggregates
+    ("ABS", 1, 1),
+    ("AVERAGE", 1, 255),
+    ("COUNT", 1, 255),
+    ("COUNTA", 1, 255),
+    ("COUNTBLANK", 1, 1),
+    ("INT", 1, 1),
+    ("MAX", 1, 255),
+    ("MIN", 1, 255),
+    ("MOD", 2, 2),
+    ("POWER", 2, 2),
+    ("PRODUCT", 1, 255),
+    ("ROUND", 2, 2),
+    ("ROUNDDOWN", 2, 2),
+    ("ROUNDUP", 2, 2),
+    ("SQRT", 1, 1),
+    ("SUM", 1, 255),
+    ("SUMPRODUCT", 1, 255),
+    // Conditional aggregates
+    ("AVERAGEIF", 2, 3),
+    ("COUNTIF", 2, 2),
+    ("COUNTIFS", 2, 254),
+    ("SUMIF", 2, 3),
+    ("SUMIFS", 3, 255),
+    // Logic
+    ("AND", 1, 255),
+    ("CHOOSE", 2, 255),
+    ("FALSE", 0, 0),
+    ("IF", 2, 3),
+    ("IFERROR", 2, 2),
+    ("IFNA", 2, 2),
+    ("ISBLANK", 1, 1),
+    ("ISERROR", 1, 1),
+    ("ISNA", 1, 1),
+    ("ISNUMBER", 1, 1),
+    ("ISTEXT", 1, 1),
+    ("NOT", 1, 1),
+    ("OR", 1, 255),
+    ("TRUE", 0, 0),
+    // Lookup
+    ("HLOOKUP", 3, 4),
+    ("INDEX", 2, 3),
+    ("MATCH", 2, 3),
+    ("VLOOKUP", 3, 4),
+    // Text
+    ("CONCAT", 1, 255),
+    ("CONCATENATE", 1, 255),
+    ("EXACT", 2, 2),
+    ("FIND", 2, 3),
+    ("LEFT", 1, 2),
+    ("LEN", 1, 1),
+    ("LOWER", 1, 1),
+    ("MID", 3, 3),
+    ("REPT", 2, 2),
+    ("RIGHT", 1, 2),
+    ("SEARCH", 2, 3),
+    ("SUBSTITUTE", 3, 4),
+    ("TEXT", 2, 2),
+    ("TRIM", 1, 1),
+    ("UPPER", 1, 1),
+    ("VALUE", 1, 1),
+    // Dates
+    ("DATE", 3, 3),
+    ("DAY", 1, 1),
+    ("DAYS", 2, 2),
+    ("EDATE", 2, 2),
+    ("EOMONTH", 2, 2),
+    ("MONTH", 1, 1),
+    ("NOW", 0, 0),
+    ("TODAY", 0, 0),
+    ("WEEKDAY", 1, 2),
+    ("YEAR", 1, 1),
+];
+
+impl<RS: Read + Seek> Model<'_, RS> {
+    pub(crate) fn call(&mut self, name: &str, args: &[Expr], sheet: &str) -> Result<Value> {
+        let Some(&(_, min, max)) = FUNCTIONS.iter().find(|(function, _, _)| *function == name)
+        else {
+            bail!("Function {} is not supported", name);
+        };
+        if args.len() < min || args.len() > max {
+            bail!(
+                "{} takes {} to {} arguments, got {}",
+                name,
+                min,
+                max,
+                args.len()
+            );
+        }
+
+        // These evaluate only the arguments they need
+        match name {
+            "IF" => {
+                let condition = self.evaluate(&args[0], sheet)?.scalar();
+                let branch = match condition.as_bool() {
+                    Ok(true) => args.get(1),
+                    Ok(false) => args.get(2),
+                    Err(err) => return Ok(err),
+                };
+                return Ok(match branch {
+                    None => Value::Bool(false),
+                    Some(Expr::Missing) => Value::Number(0.0),
+                    Some(expr) => self.evaluate(expr, sheet)?,
+                });
+            }
+            "IFERROR" | "IFNA" => {
+                let value = self.evaluate(&args[0], sheet)?.scalar();
+                return match value {
+                    Value::Error(code) if name == "IFERROR" || code == "#N/A" => {
+                        self.evaluate(&args[1], sheet)
+                    }
+                    value => Ok(value),
+                };
+            }
+            "CHOOSE" => {
+                let index = match self.evaluate(&args[0], sheet)?.as_number() {
+                    Ok(index) => index.trunc(),
+                    Err(err) => return Ok(err),
+                };
+                if index < 1.0 || index as usize >= args.len() {
+                    return Ok(error("#VALUE!"));
+                }
+                return self.evaluate(&args[index as usize], sheet);
+            }
+            _ => {}
+        }
+
+        let values = args
+            .iter()
+            .map(|arg| self.evaluate(arg, sheet))
+            .collect::<Result<Vec<Value>>>()?;
+        Ok(apply(name, &values).unwrap_or_else(|err| err))
+    }
+}
+
+fn apply(name: &str, args: &[Value]) -> Eval<Value> {
+    let scalar = |index: usize| args.get(index).cloned().map(Value::scalar);
+    let num = |index: usize| scalar(index).unwrap_or(Value::Empty).as_number();
+    let text = |index: usize| scalar(index).unwrap_or(Value::Empty).as_text();
+    // An optional numeric argument, with its default when left out
+    let num_or = |index: usize, default: f64| match scalar(index) {
+        None | Some(Value::Empty) => Ok(default),
+        Some(value) => value.as_number(),
+    };
+
+    match name {
+        "ABS" => number(num(0)?.abs()),
+        "SUM" => number(numbers(args)?.iter().sum()),
+        "PRODUCT" => {
+            let numbers = numbers(args)?;
+            number(if numbers.is_empty() {
+                0.0
+            } else {
+                numbers.iter().product()
+            })
+        }
+        "AVERAGE" => {
+            let numbers = numbers(args)?;
+            if numbers.is_empty() {
+                return Err(error("#DIV/0!"));
+            }
+            number(numbers.iter().sum::<f64>() / numbers.len() as f64)
+        }
+        "MIN" => number(numbers(args)?.into_iter().reduce(f64::min).unwrap_or(0.0)),
+        "MAX" => number(numbers(args)?.into_iter().reduce(f64::max).unwrap_or(0.0)),
+        "COUNT" => Ok(count(args, |cell| match cell {
+            Value::Number(_) => true,
+            Value::Text(text) => text.trim().parse::<f64>().is_ok(),
+            _ => false,
+        })),
+        "COUNTA" => Ok(count(args, |cell| !matches!(cell, Value::Empty))),
+        "COUNTBLANK" => Ok(count(args, |cell| match cell {
+            Value::Empty => true,
+            Value::Text(text) => text.is_empty(),
+            _ => false,
+        })),
+        "INT" => number(num(0)?.floor()),
+        "MOD" => {
+            let (n, divisor) = (num(0)?, num(1)?);
+            if divisor == 0.0 {
+                return Err(error("#DIV/0!"));
+            }
+            number(n - divisor * (n / divisor).floor())
+        }
+        "POWER" => number(num(0)?.powf(num(1)?)),
+        "SQRT" => {
+            let n = num(0)?;
+            if n < 0.0 {
+                return Err(error("#NUM!"));
+            }
+            number(n.sqrt())
+        }
+        "ROUND" => round(num(0)?, num(1)?, f64::round),
+        "ROUNDUP" => round(
+            num(0)?,
+            num(1)?,
+            |n| if n < 0.0 { n.floor() } else { n.ceil() },
+        ),
+        "ROUNDDOWN" => round(num(0)?, num(1)?, f64::trunc),
+        "SUMPRODUCT" => sum_product(args),
+
+        "SUMIF" | "AVERAGEIF" => {
+            let criteria = [(&args[0], Criterion::new(&args[1].clone().scalar()))];
+            let targets = args.get(2).unwrap_or(&args[0]);
+            let matched = matching_numbers(targets, &criteria)?;
+            if name == "SUMIF" {
+                return number(matched.iter().sum());
+            }
+            if matched.is_empty() {
+                return Err(error("#DIV/0!"));
+            }
+            number(matched.iter().sum::<f64>() / matched.len() as f64)
+        }
+        "SUMIFS" => {
+            if args.len().is_multiple_of(2) {
+                return Err(error("#VALUE!"));
+            }
+            let criteria = criteria_pairs(&args[1..]);
+            number(matching_numbers(&args[0], &criteria)?.iter().sum())
+        }
+        "COUNTIF" | "COUNTIFS" => {
+            if !args.len().is_multiple_of(2) {
+                return Err(error("#VALUE!"));
+            }
+            let criteria = criteria_pairs(args);
+            let rows = shape(&args[0]);
+            let mut total = 0;
+            for row in 0..rows.0 {
+                for col in 0..rows.1 {
+                    if criteria_match(&criteria, row, col) {
+                        total += 1;
+                    }
+                }
+            }
+            Ok(Value::Number(total as f64))
+        }
+
+        "TRUE" => Ok(Value::Bool(true)),
+        "FALSE" => Ok(Value::Bool(false)),
+        "NOT" => Ok(Value::Bool(!scalar(0).unwrap_or(Value::Empty).as_bool()?)),
+        "AND" | "OR" => {
+            let mut flags = Vec::new();
+            for arg in args {
+                match arg {
+                    Value::Array(_) => {
+                        for cell in arg.cells() {
+                            match cell {
+                                Value::Number(_) | Value::Bool(_) | Value::Error(_) => {
+                                    flags.push(cell.as_bool()?)
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    Value::Empty => {}
+                    value => flags.push(value.as_bool()?),
+                }
+            }
+            if flags.is_empty() {
+                return Err(error("#VALUE!"));
+            }
+            Ok(Value::Bool(if name == "AND" {
+                flags.iter().all(|flag| *flag)
+            } else {
+                flags.iter().any(|flag| *flag)
+            }))
+        }
+        "ISBLANK" => Ok(Value::Bool(matches!(scalar(0), Some(Value::Empty)))),
+        "ISERROR" => Ok(Value::Bool(matches!(scalar(0), Some(Value::Error(_))))),
+        "ISNA" => Ok(Value::Bool(
+            matches!(scalar(0), Some(Value::Error(code)) if code == "#N/A"),
+        )),
+        "ISNUMBER" => Ok(Value::Bool(matches!(scalar(0), Some(Value::Number(_))))),
+        "ISTEXT" => Ok(Value::Bool(matches!(scalar(0), Some(Value::Text(_))))),
+
+        "MATCH" => {
+            let value = scalar(0).unwrap_or(Value::Empty);
+            if let Value::Error(_) = value {
+                return Err(value);
+            }
+            let candidates = vector(&args[1]).ok_or_else(|| error("#N/A"))?;
+            let mode = num_or(2, 1.0)?;
+            match lookup(&value, &candidates, mode) {
+                Some(position) => Ok(Value::Number(position as f64 + 1.0)),
+                None => Err(error("#N/A")),
+            }
+        }
+        "VLOOKUP" | "HLOOKUP" => {
+            let value = scalar(0).unwrap_or(Value::Empty);
+            if let Value::Error(_) = value {
+                return Err(value);
+            }
+            let table = rows(&args[1]);
+            let table = if name == "HLOOKUP" {
+                transpose(&table)
+            } else {
+                table
+            };
+            let index = num(2)?.trunc();
+            if index < 1.0 {
+                return Err(error("#VALUE!"));
+            }
+            let approximate = match scalar(3) {
+                None => true,
+                Some(flag) => flag.as_bool()?,
+            };
+            let keys: Vec<&Value> = table.iter().filter_map(|row| row.first()).collect();
+            let mode = if approximate { 1.0 } else { 0.0 };
+            let position = lookup(&value, &keys, mode).ok_or_else(|| error("#N/A"))?;
+            table[position]
+                .get(index as usize - 1)
+                .cloned()
+                .ok_or_else(|| error("#REF!"))
+        }
+        "INDEX" => {
+            let table = rows(&args[0]);
+            let (height, width) = (table.len(), table.first().map_or(0, Vec::len));
+            let (mut row, mut col) = (num(1)?.trunc(), num_or(2, 0.0)?.trunc());
+            // A single row is indexed by its columns
+            if height == 1 && args.len() == 2 {
+                (row, col) = (1.0, row);
+            }
+            if row < 0.0 || col < 0.0 || row as usize > height || col as usize > width {
+                return Err(error("#REF!"));
+            }
+            let (row, col) = (row as usize, col as usize);
+            Ok(match (row, col) {
+                (0, 0) => Value::Array(table),
+                (0, col) => Value::Array(table.iter().map(|r| vec![r[col - 1].clone()]).collect()),
+                (row, 0) if width > 1 => Value::Array(vec![table[row - 1].clone()]),
+                (row, 0) => table[row - 1][0].clone(),
+                (row, col) => table[row - 1][col - 1].clone(),
+            })
+        }
+
+        "CONCATENATE" => {
+            let mut joined = String::new();
+            for index in 0..args.len() {
+                joined.push_str(&text(index)?);
+            }
+            Ok(Value::Text(joined))
+        }
+        "CONCAT" => {
+            let mut joined = String::new();
+            for arg in args {
+                for cell in arg.cells() {
+                    joined.push_str(&cell.as_text()?);
+                }
+            }
+            Ok(Value::Text(joined))
+        }
+        "LEFT" | "RIGHT" => {
+            let value = text(0)?;
+            let count = num_or(1, 1.0)?;
+            if count < 0.0 {
+                return Err(error("#VALUE!"));
+            }
+            let chars: Vec<char> = value.chars().collect();
+            let count = (count as usize).min(chars.len());
+            let part = if name == "LEFT" {
+                &chars[..count]
+            } else {
+                &chars[chars.len() - count..]
+            };
+            Ok(Value::Text(part.iter().collect()))
+        }
+        "MID" => {
+            let value = text(0)?;
+            let (start, count) = (num(1)?.trunc(), num(2)?.trunc());
+            if start < 1.0 || count < 0.0 {
+                return Err(error("#VALUE!"));
+            }
+            Ok(Value::Text(
+                value
+                    .chars()
+                    .skip(start as usize - 1)
+                    .take(count as usize)
+                    .collect(),
+            ))
+        }
+        "LEN" => Ok(Value::Number(text(0)?.chars().count() as f64)),
+        "UPPER" => Ok(Value::Text(text(0)?.to_uppercase())),
+        "LOWER" => Ok(Value::Text(text(0)?.to_lowercase())),
+        "TRIM" => Ok(Value::Text(
+            text(0)?
+                .split(' ')
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+        )),
+        "EXACT" => Ok(Value::Bool(text(0)? == text(1)?)),
+        "REPT" => {
+            let (value, times) = (text(0)?, num(1)?.trunc());
+            // Excel's cells hold at most 32767 characters
+            if times < 0.0 || times * value.chars().count() as f64 > MAX_TEXT_LENGTH {
+                return Err(error("#VALUE!"));
+            }
+            Ok(Value::Text(value.repeat(times as usize)))
+        }
+        "SUBSTITUTE" => {
+            let (value, old, new) = (text(0)?, text(1)?, text(2)?);
+            if old.is_empty() {
+                return Ok(Value::Text(value));
+            }
+            match scalar(3) {
+                None => Ok(Value::Text(value.replace(&old, &new))),
+                Some(instance) => {
+                    let instance = instance.as_number()?.trunc();
+                    if instance < 1.0 {
+                        return Err(error("#VALUE!"));
+                    }
+                    let position = value.match_indices(&old).nth(instance as usize - 1);
+                    Ok(Value::Text(match position {
+                        Some((at, _)) => {
+                            format!("{}{}{}", &value[..at], new, &value[at + old.len()..])
+                        }
+                        None => value,
+                    }))
+                }
+            }
+        }
+        "FIND" | "SEARCH" => {
+            let (needle, haystack) = (text(0)?, text(1)?);
+            let start = num_or(2, 1.0)?.trunc();
+            let chars: Vec<char> = haystack.chars().collect();
+            if start < 1.0 || start as usize > chars.len() + 1 {
+                return Err(error("#VALUE!"));
+            }
+            let rest: String = chars[start as usize - 1..].iter().collect();
+            let found = if name == "FIND" {
+                rest.find(&needle)
+            } else {
+                rest.to_lowercase().find(&needle.to_lowercase())
+            };
+            match found {
+                Some(at) => Ok(Value::Number(
+                    (start as usize + rest[..at].chars().count()) as f64,
+                )),
+                None => Err(error("#VALUE!")),
+            }
+        }
+        "VALUE" => match scalar(0).unwrap_or(Value::Empty) {
+            Value::Text(value) => match Value::from_input(&value) {
+                Value::Number(number) => Ok(Value::Number(number)),
+                _ => Err(error("#VALUE!")),
+            },
+            value => Ok(Value::Number(value.as_number()?)),
+        },
+        "TEXT" => {
+            // Text that reads as a number is formatted as one
+            let value = match scalar(0).unwrap_or(Value::Empty) {
+                Value::Empty => Data::Float(0.0),
+                Value::Number(number) => Data::Float(number),
+                Value::Bool(flag) => Data::Bool(flag),
+                Value::Text(value) => match Value::from_input(&value) {
+                    Value::Number(number) => Data::Float(number),
+                    _ => Data::String(value),
+                },
+                value => return Err(value),
+            };
+            Ok(Value::Text(format_with_code(&value, &text(1)?)))
+        }
+
+        "DATE" => {
+            let (mut year, month, day) = (num(0)?.trunc(), num(1)?.trunc(), num(2)?.trunc());
+            if year < 1900.0 {
+                year += 1900.0;
+            }
+            let first = NaiveDate::from_ymd_opt(year as i32, 1, 1).ok_or_else(|| error("#NUM!"))?;
+            let date = (month as i64)
+                .checked_sub(1)
+                .and_then(|months| add_months(first, months))
+                .and_then(|date| add_days(date, (day as i64).checked_sub(1)?))
+                .ok_or_else(|| error("#NUM!"))?;
+            date_value(date)
+        }
+        "YEAR" => Ok(Value::Number(date_arg(&args[0])?.year() as f64)),
+        "MONTH" => Ok(Value::Number(date_arg(&args[0])?.month() as f64)),
+        "DAY" => Ok(Value::Number(date_arg(&args[0])?.day() as f64)),
+        "DAYS" => Ok(Value::Number(
+            serial_from_date(date_arg(&args[0])?) - serial_from_date(date_arg(&args[1])?),
+        )),
+        "EDATE" | "EOMONTH" => {
+            let date = date_arg(&args[0])?;
+            let months = num(1)?.trunc() as i64;
+            let date = if name == "EOMONTH" {
+                // The first of the month after the target month, then back a day
+                date.with_day(1)
+                    .and_then(|date| add_months(date, months.checked_add(1)?))
+                    .and_then(|date| add_days(date, -1))
+            } else {
+                add_months(date, months)
+            };
+            date_value(date.ok_or_else(|| error("#NUM!"))?)
+        }
+        "WEEKDAY" => {
+            let date = date_arg(&args[0])?;
+            let from_sunday = date.weekday().num_days_from_sunday() as f64;
+            let from_monday = date.weekday().num_days_from_monday() as f64;
+            match num_or(1, 1.0)? as i64 {
+                1 => Ok(Value::Number(from_sunday + 1.0)),
+                2 => Ok(Value::Number(from_monday + 1.0)),
+                3 => Ok(Value::Number(from_monday)),
+                _ => Err(error("#NUM!")),
+            }
+        }
+        "TODAY" => Ok(Value::Number(serial_from_date(Local::now().date_naive()))),
+        "NOW" => {
+            let now = Local::now();
+            let seconds = now.time().num_seconds_from_midnight() as f64;
+            Ok(Value::Number(
+                serial_from_date(now.date_naive()) + seconds / 86_400.0,
+            ))
+        }
+
+        _ => Err(error("#NAME?")),
+    }
+}
+
+/// Numbers for SUM and friends. Cells of a range count only when they hold
+/// a number; values given directly are converted, so `SUM("2",TRUE)` is 3.
+fn numbers(args: &[Value]) -> Eval<Vec<f64>> {
+    let mut numbers = Vec::new();
+    for arg in args {
+        match arg {
+            Value::Array(_) => {
+                for cell in arg.cells() {
+                    match cell {
+                        Value::Number(number) => numbers.push(*number),
+                        Value::Error(_) => return Err(cell.clone()),
+                        _ => {}
+                    }
+                }
+            }
+            Value::Empty => {}
+            value => numbers.push(value.as_number()?),
+        }
+    }
+    Ok(numbers)
+}
+
+fn count(args: &[Value], counts: impl Fn(&Value) -> bool) -> Value {
+    let total = args
+        .iter()
+        .flat_map(Value::cells)
+        .filter(|cell| counts(cell))
+        .count();
+    Value::Number(total as f64)
+}
+
+fn round(n: f64, digits: f64, mode: fn(f64) -> f64) -> Eval<Value> {
+    let factor = 10f64.powi(digits.trunc() as i32);
+    // Drop binary noise first, so 2.675 rounds up to 2.68 as in Excel
+    let scaled = n * factor;
+    let scaled: f64 = format!("{:.14e}", scaled).parse().unwrap_or(scaled);
+    number(mode(scaled) / factor)
+}
+
+fn rows(value: &Value) -> Vec<Vec<Value>> {
+    match value {
+        Value::Array(rows) => rows.clone(),
+        value => vec![vec![value.clone()]],
+    }
+}
+
+/// (rows, columns) of a value.
+fn shape(value: &Value) -> (usize, usize) {
+    match value {
+        Value::Array(rows) => (rows.len(), rows.first().map_or(0, Vec::len)),
+        _ => (1, 1),
+    }
+}
+
+fn cell_at(value: &Value, row: usize, col: usize) -> Option<&Value> {
+    match value {
+        Value::Array(rows) => rows.get(row).and_then(|r| r.get(col)),
+        value if (row, col) == (0, 0) => Some(value),
+        _ => None,
+    }
+}
+
+fn transpose(table: &[Vec<Value>]) -> Vec<Vec<Value>> {
+    let width = table.first().map_or(0, Vec::len);
+    (0..width)
+        .map(|col| table.iter().map(|row| row[col].clone()).collect())
+        .collect()
+}
+
+/// The cells of a single row or column, or `None` for a 2-D block.
+fn vector(value: &Value) -> Option<Vec<&Value>> {
+    let (height, width) = shape(value);
+    if height > 1 && width > 1 {
+        return None;
+    }
+    Some(value.cells())
+}
+
+fn sum_product(args: &[Value]) -> Eval<Value> {
+    let dimensions = shape(&args[0]);
+    if args.iter().any(|arg| shape(arg) != dimensions) {
+        return Err(error("#VALUE!"));
+    }
+    let mut total = 0.0;
+    for row in 0..dimensions.0 {
+        for col in 0..dimensions.1 {
+            let mut product = 1.0;
+            for arg in args {
+                match cell_at(arg, row, col) {
+                    Some(Value::Number(number)) => product *= number,
+                    Some(err @ Value::Error(_)) => return Err(err.clone()),
+                    _ => product = 0.0,
+                }
+            }
+            total += product;
+        }
+    }
+    number(total)
+}
+
+/// Whether two values are of a kind lookups compare: both numbers, both
+/// text or both booleans.
+fn same_kind(a: &Value, b: &Value) -> bool {
+    matches!(
+        (a, b),
+        (Value::Number(_), Value::Number(_))
+            | (Value::Text(_), Value::Text(_))
+            | (Value::Bool(_), Value::Bool(_))
+    )
+}
+
+/// Position of `value` among `candidates` the way MATCH finds it: mode 0
+/// is an exact match (text may use `*` and `?`), 1 the largest value not
+/// above it in an ascending list, -1 the smallest not below it in a
+/// descending one.
+fn lookup(value: &Value, candidates: &[&Value], mode: f64) -> Option<usize> {
+    if mode == 0.0 {
+        return candidates
+            .iter()
+            .position(|candidate| match (value, candidate) {
+                (Value::Text(pattern), Value::Text(text)) => wildcard_match(pattern, text),
+                _ => same_kind(value, candidate) && compare(value, candidate) == Ordering::Equal,
+            });
+    }
+    let keep = if mode > 0.0 {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    };
+    let mut found = None;
+    for (position, candidate) in candidates.iter().enumerate() {
+        if !same_kind(value, candidate) {
+            continue;
+        }
+        match compare(candidate, value) {
+            Ordering::Equal => found = Some(position),
+            ordering if ordering == keep => found = Some(position),
+            // The list is sorted, so nothing further can match
+            _ => break,
+        }
+    }
+    found
+}
+
+/// Match text against a pattern with `*`, `?` and `~` escapes, ignoring case.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.first() {
+            None => text.is_empty(),
+            Some('*') => (0..=text.len()).any(|skip| matches(&pattern[1..], &text[skip..])),
+            Some('?') => !text.is_empty() && matches(&pattern[1..], &text[1..]),
+            Some('~') if pattern.len() > 1 => {
+                text.first() == Some(&pattern[1]) && matches(&pattern[2..], &text[1..])
+            }
+            Some(c) => text.first() == Some(c) && matches(&pattern[1..], &text[1..]),
+        }
+    }
+    matches(&pattern, &text)
+}
+
+/// A condition such as `">=10"`, `"<>"` or `"North*"` from SUMIF and friends.
+struct Criterion {
+    operator: &'static str,
+    target: Value,
+}
+
+impl Criterion {
+    fn new(criteria: &Value) -> Criterion {
+        let Value::Text(text) = criteria else {
+            return Criterion {
+                operator: "=",
+                target: criteria.clone(),
+            };
+        };
+        let operator = ["<=", ">=", "<>", "<", ">", "="]
+            .into_iter()
+            .find(|op| text.starts_with(op));
+        let rest = &text[operator.map_or(0, str::len)..];
+        let target = match Value::from_input(rest) {
+            Value::Empty => Value::Text(String::new()),
+            Value::Text(_) => Value::Text(rest.to_string()),
+            value => value,
+        };
+        Criterion {
+            operator: operator.unwrap_or("="),
+            target,
+        }
+    }
+
+    fn matches(&self, cell: &Value) -> bool {
+        let equal = || match (&self.target, cell) {
+            (Value::Text(target), Value::Empty) => target.is_empty(),
+            (Value::Text(pattern), Value::Text(text)) => wildcard_match(pattern, text),
+            (Value::Number(target), Value::Text(text)) => {
+                text.trim().parse::<f64>().is_ok_and(|n| n == *target)
+            }
+            (target, cell) => same_kind(target, cell) && compare(target, cell) == Ordering::Equal,
+        };
+        match self.operator {
+            "=" => equal(),
+            "<>" => !equal(),
+            operator => {
+                if !same_kind(&self.target, cell) {
+                    return false;
+                }
+                let ordering = compare(cell, &self.target);
+                match operator {
+                    "<" => ordering == Ordering::Less,
+                    "<=" => ordering != Ordering::Greater,
+                    ">" => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                }
+            }
+        }
+    }
+}
+
+/// (range, criterion) pairs from alternating arguments.
+fn criteria_pairs(args: &[Value]) -> Vec<(&Value, Criterion)> {
+    args.chunks(2)
+        .map(|pair| (&pair[0], Criterion::new(&pair[1].clone().scalar())))
+        .collect()
+}
+
+fn criteria_match(criteria: &[(&Value, Criterion)], row: usize, col: usize) -> bool {
+    criteria.iter().all(|(range, criterion)| {
+        criterion.matches(cell_at(range, row, col).unwrap_or(&Value::Empty))
+    })
+}
+
+/// Numbers in `targets` at the positions where every criterion holds.
+fn matching_numbers(targets: &Value, criteria: &[(&Value, Criterion)]) -> Eval<Vec<f64>> {
+    let (height, width) = shape(criteria[0].0);
+    let mut matched = Vec::new();
+    for row in 0..height {
+        for col in 0..width {
+            if !criteria_match(criteria, row, col) {
+                continue;
+            }
+            match cell_at(targets, row, col) {
+                Some(Value::Number(number)) => matched.push(*number),
+                Some(err @ Value::Error(_)) => return Err(err.clone()),
+                _ => {}
+            }
+        }
+    }
+    Ok(matched)
+}
+
+/// Date of a serial number in the 1900 date system, from 0 to 31 December 9999.
+fn date_from_serial(serial: f64) -> Option<NaiveDate> {
+    if !(0.0..=MAX_SERIAL).contains(&serial) {
+        return None;
+    }
+    let days = serial.floor() as i64;
+    // Excel counts a 29 February 1900 that never was, so later serials are a day ahead
+    let base = if days < 61 {
+        NaiveDate::from_ymd_opt(1899, 12, 31)?
+    } else {
+        NaiveDate::from_ymd_opt(1899, 12, 30)?
+    };
+    add_days(base, days)
+}
+
+pub(crate) fn serial_from_date(date: NaiveDate) -> f64 {
+    let base = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap_or_default();
+    let days = (date - base).num_days();
+    (if days < 61 { days - 1 } else { days }) as f64
+}
+
+/// A computed date as a serial number, or `#NUM!` outside the dates Excel knows.
+fn date_value(date: NaiveDate) -> Eval<Value> {
+    let serial = serial_from_date(date);
+    if !(0.0..=MAX_SERIAL).contains(&serial) {
+        return Err(error("#NUM!"));
+    }
+    Ok(Value::Number(serial))
+}
+
+fn add_months(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+    let count = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+    if months >= 0 {
+        date.checked_add_months(count)
+    } else {
+        date.checked_sub_months(count)
+    }
+}
+
+fn add_days(date: NaiveDate, days: i64) -> Option<NaiveDate> {
+    let count = Days::new(days.unsigned_abs());
+    if days >= 0 {
+        date.checked_add_days(count)
+    } else {
+        date.checked_sub_days(count)
+    }
+}
+
+/// A date argument: a serial number or ISO text such as "2024-03-31".
+fn date_arg(value: &Value) -> Eval<NaiveDate> {
+    let value = value.clone().scalar();
+    if let Value::Text(text) = &value {
+        if let Ok(date) = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d") {
+            return Ok(date);
+        }
+    }
+    let serial = value.as_number()?;
+    if serial < 0.0 {
+        return Err(error("#NUM!"));
+    }
+    date_from_serial(serial).ok_or_else(|| error("#NUM!"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(values: &[f64]) -> Value {
+        Value::Array(values.iter().map(|n| vec![Value::Number(*n)]).collect())
+    }
+
+    fn text_column(values: &[&str]) -> Value {
+        Value::Array(
+            values
+                .iter()
+                .map(|text| vec![Value::Text(text.to_string())])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let range = Value::Array(vec![vec![
+            Value::Number(2.0),
+            Value::Text("skip".to_string()),
+            Value::Empty,
+            Value::Number(4.0),
+        ]]);
+        assert_eq!(
+            apply("SUM", &[range.clone(), Value::Text("1".to_string())]),
+            Ok(Value::Number(7.0))
+        );
+        assert_eq!(
+            apply("AVERAGE", std::slice::from_ref(&range)),
+            Ok(Value::Number(3.0))
+        );
+        assert_eq!(
+            apply("COUNT", std::slice::from_ref(&range)),
+            Ok(Value::Number(2.0))
+        );
+        assert_eq!(
+            apply("COUNTA", std::slice::from_ref(&range)),
+            Ok(Value::Number(3.0))
+        );
+        assert_eq!(
+            apply("AVERAGE", &[Value::Array(vec![vec![Value::Empty]])]),
+            Err(error("#DIV/0!"))
+        );
+        assert_eq!(
+            apply("ROUND", &[Value::Number(2.675), Value::Number(2.0)]),
+            Ok(Value::Number(2.68))
+        );
+        assert_eq!(
+            apply("ROUND", &[Value::Number(-1250.0), Value::Number(-2.0)]),
+            Ok(Value::Number(-1300.0))
+        );
+        assert_eq!(
+            apply("ROUNDDOWN", &[Value::Number(3.789), Value::Number(1.0)]),
+            Ok(Value::Number(3.7))
+        );
+        assert_eq!(
+            apply("MOD", &[Value::Number(-3.0), Value::Number(2.0)]),
+            Ok(Value::Number(1.0))
+        );
+        assert_eq!(
+            apply(
+                "SUMPRODUCT",
+                &[column(&[1.0, 2.0, 3.0]), column(&[10.0, 20.0, 30.0])]
+            ),
+            Ok(Value::Number(140.0))
+        );
+    }
+
+    #[test]
+    fn test_conditional_aggregates() {
+        let regions = text_column(&["North", "South", "Northeast", "North"]);
+        let sales = column(&[100.0, 200.0, 300.0, 400.0]);
+        let text = |s: &str| Value::Text(s.to_string());
+
+        assert_eq!(
+            apply("SUMIF", &[regions.clone(), text("North"), sales.clone()]),
+            Ok(Value::Number(500.0))
+        );
+        assert_eq!(
+            apply("SUMIF", &[regions.clone(), text("North*"), sales.clone()]),
+            Ok(Value::Number(800.0))
+        );
+        assert_eq!(
+            apply("SUMIF", &[sales.clone(), text(">=300")]),
+            Ok(Value::Number(700.0))
+        );
+        assert_eq!(
+            apply("COUNTIF", &[regions.clone(), text("<>North")]),
+            Ok(Value::Number(2.0))
+        );
+        assert_eq!(
+            apply(
+                "SUMIFS",
+                &[
+                    sales.clone(),
+                    regions.clone(),
+                    text("North"),
+                    sales.clone(),
+                    text(">150")
+                ]
+            ),
+            Ok(Value::Number(400.0))
+        );
+        assert_eq!(
+            apply("AVERAGEIF", &[regions, text("South"), sales]),
+            Ok(Value::Number(200.0))
+        );
+    }
+
+    #[test]
+    fn test_lookups() {
+        let table = Value::Array(vec![
+            vec![Value::Number(0.0), Value::Text("Bronze".to_string())],
+            vec![Value::Number(1000.0), Value::Text("Silver".to_string())],
+            vec![Value::Number(5000.0), Value::Text("Gold".to_string())],
+        ]);
+        let vlookup = |value: f64, exact: bool| {
+            apply(
+                "VLOOKUP",
+                &[
+                    Value::Number(value),
+                    table.clone(),
+                    Value::Number(2.0),
+                    Value::Bool(!exact),
+                ],
+            )
+        };
+        assert_eq!(
+            vlookup(2500.0, false),
+            Ok(Value::Text("Silver".to_string()))
+        );
+        assert_eq!(vlookup(5000.0, true), Ok(Value::Text("Gold".to_string())));
+        assert_eq!(vlookup(2500.0, true), Err(error("#N/A")));
+
+        let names = text_column(&["apple", "Banana", "cherry"]);
+        assert_eq!(
+            apply(
+                "MATCH",
+                &[
+                    Value::Text("BANANA".to_string()),
+                    names.clone(),
+                    Value::Number(0.0)
+                ]
+            ),
+            Ok(Value::Number(2.0))
+        );
+        assert_eq!(
+            apply(
+                "MATCH",
+                &[
+                    Value::Text("ch*".to_string()),
+                    names.clone(),
+                    Value::Number(0.0)
+                ]
+            ),
+            Ok(Value::Number(3.0))
+        );
+        assert_eq!(
+            apply("INDEX", &[names, Value::Number(3.0)]),
+            Ok(Value::Text("cherry".to_string()))
+        );
+        assert_eq!(
+            apply("INDEX", &[table, Value::Number(2.0), Value::Number(1.0)]),
+            Ok(Value::Number(1000.0))
+        );
+    }
+
+    #[test]
+    fn test_text_functions() {
+        let text = |s: &str| Value::Text(s.to_string());
+        assert_eq!(
+            apply("LEFT", &[text("Invoice-42"), Value::Number(7.0)]),
+            Ok(text("Invoice"))
+        );
+        assert_eq!(
+            apply("RIGHT", &[text("Invoice-42"), Value::Number(2.0)]),
+            Ok(text("42"))
+        );
+        assert_eq!(
+            apply(
+                "MID",
+                &[text("Invoice-42"), Value::Number(3.0), Value::Number(3.0)]
+            ),
+            Ok(text("voi"))
+        );
+        assert_eq!(apply("TRIM", &[text("  a   b ")]), Ok(text("a b")));
+        assert_eq!(
+            apply("SEARCH", &[text("ICE"), text("Invoice")]),
+            Ok(Value::Number(5.0))
+        );
+        assert_eq!(
+            apply("FIND", &[text("ICE"), text("Invoice")]),
+            Err(error("#VALUE!"))
+        );
+        assert_eq!(
+            apply(
+                "SUBSTITUTE",
+                &[text("a-b-c"), text("-"), text("+"), Value::Number(2.0)]
+            ),
+            Ok(text("a-b+c"))
+        );
+        assert_eq!(
+            apply(
+                "CONCATENATE",
+                &[text("Q"), Value::Number(1.0), Value::Bool(true)]
+            ),
+            Ok(text("Q1TRUE"))
+        );
+        assert_eq!(apply("VALUE", &[text("12.5%")]), Ok(Value::Number(0.125)));
+        assert_eq!(
+            apply("TEXT", &[Value::Number(1234.5), text("#,##0.00")]),
+            Ok(text("1,234.50"))
+        );
+        assert_eq!(
+            apply("TEXT", &[Value::Number(45322.0), text("yyyy-mm-dd")]),
+            Ok(text("2024-01-31"))
+        );
+        assert_eq!(apply("TEXT", &[text("7"), text("000")]), Ok(text("007")));
+        assert_eq!(apply("TEXT", &[text("n/a"), text("0")]), Ok(text("n/a")));
+        assert_eq!(
+            apply("TEXT", &[error("#N/A"), text("0")]),
+            Err(error("#N/A"))
+        );
+    }
+
+    #[test]
+    fn test_date_functions() {
+        let date = |y: f64, m: f64, d: f64| {
+            apply(
+                "DATE",
+                &[Value::Number(y), Value::Number(m), Value::Number(d)],
+            )
+        };
+        assert_eq!(date(2024.0, 1.0, 31.0), Ok(Value::Number(45322.0)));
+        assert_eq!(date(1900.0, 1.0, 1.0), Ok(Value::Number(1.0)));
+        assert_eq!(date(1900.0, 3.0, 1.0), Ok(Value::Number(61.0)));
+        // Months and days past the end roll over
+        assert_eq!(date(2023.0, 14.0, 1.0), date(2024.0, 2.0, 1.0));
+        assert_eq!(
+            apply("EOMONTH", &[Value::Number(45322.0), Value::Number(1.0)]),
+            date(2024.0, 2.0, 29.0)
+        );
+        assert_eq!(
+            apply("EDATE", &[Value::Number(45322.0), Value::Number(1.0)]),
+            date(2024.0, 2.0, 29.0)
+        );
+        assert_eq!(
+            apply("YEAR", &[Value::Text("2024-03-31".to_string())]),
+            Ok(Value::Number(2024.0))
+        );
+        assert_eq!(
+            apply("WEEKDAY", &[Value::Number(45322.0)]),
+            Ok(Value::Number(4.0))
+        );
+
+        // Out of range dates are #NUM!, not a crash
+        let num_error = Err(error("#NUM!"));
+        assert_eq!(apply("YEAR", &[Value::Number(1e15)]), num_error);
+        assert_eq!(apply("YEAR", &[Value::Number(2_958_466.0)]), num_error);
+        assert_eq!(date(2024.0, 1.0, 1e15), num_error);
+        assert_eq!(date(2024.0, -1e19, 1.0), num_error);
+        assert_eq!(date(9999.0, 12.0, 32.0), num_error);
+        assert_eq!(
+            apply("EDATE", &[Value::Number(45322.0), Value::Number(1e12)]),
+            num_error
+        );
+        assert_eq!(
+            apply(
+                "REPT",
+                &[Value::Text("ab".to_string()), Value::Number(1e18)]
+            ),
+            Err(error("#VALUE!"))
+        );
+    }
+}
@@ -0,0 +1,58 @@
+/// Smallest Levenshtein distance between `needle` and any substring of
+/// `haystack`, so "Jon" is one edit away from "John Doe".
+pub fn substring_distance(needle: &str, haystack: &str) -> usize {
+    let needle: Vec<char> = needle.chars().collect();
+    let haystack: Vec<char> = haystack.chars().collect();
+
+    // The first row is all zeros: a match may start anywhere in the haystack.
+    let mut prev = vec![0; haystack.len() + 1];
+    for (i, needle_char) in needle.iter().enumerate() {
+        let mut current = Vec::with_capacity(haystack.len() + 1);
+        current.push(i + 1);
+        for (j, haystack_char) in haystack.iter().enumerate() {
+            let cost = usize::from(needle_char != haystack_char);
+            let value = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+            current.push(value);
+        }
+        prev = current;
+    }
+
+    prev.into_iter().min().unwrap_or(0)
+}
+
+/// Best Jaro-Winkler similarity between `needle` and either the whole
+/// `haystack` or any run of consecutive words with the same word count.
+pub fn best_jaro_winkler(needle: &str, haystack: &str) -> f64 {
+    let mut best = strsim::jaro_winkler(needle, haystack);
+
+    let window = needle.split_whitespace().count().max(1);
+    let words: Vec<&str> = haystack.split_whitespace().collect();
+    if words.len() > window {
+        for run in words.windows(window) {
+            best = best.max(strsim::jaro_winkler(needle, &run.join(" ")));
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substring_distance() {
+        assert_eq!(substring_distance("John", "John Doe"), 0);
+        assert_eq!(substring_distance("Jon Doe", "John Doe"), 1);
+        assert_eq!(substring_distance("Smtih", "Jane Smith"), 2);
+        assert_eq!(substring_distance("abc", ""), 3);
+        assert_eq!(substring_distance("", "anything"), 0);
+    }
+
+    #[test]
+    fn test_best_jaro_winkler() {
+        assert_eq!(best_jaro_winkler("Laptop", "Laptop"), 1.0);
+        assert!(best_jaro_winkler("Jon Doe", "Mr John Doe") > 0.9);
+        assert!(best_jaro_winkler("Jon Doe", "Marketing") < 0.7);
+    }
+}
@@ -0,0 +1,634 @@
+use crate::address::sheet_cell_reference;
+use crate::parallel::collect_workbooks;
+use crate::stream::{stream_extent, stream_rows};
+use crate::text::normalize_for_search;
+use crate::{format_cell, open_excel_file};
+use anyhow::{Context, Result};
+use calamine::Reader;
+use rayon::prelude::*;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+/// Index file used when no `--index` path is given.
+pub const DEFAULT_INDEX_PATH: &str = ".xlsq-index.sqlite";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        modified INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        hash TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cells (
+        id INTEGER PRIMARY KEY,
+        file_id INTEGER NOT NULL,
+        sheet_index INTEGER NOT NULL,
+        sheet TEXT NOT NULL,
+        row INTEGER NOT NULL,
+        col INTEGER NOT NULL,
+        value TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS cells_file ON cells(file_id);
+    CREATE TABLE IF NOT EXISTS postings (
+        token TEXT NOT NULL,
+        cell_id INTEGER NOT NULL,
+        PRIMARY KEY (token, cell_id)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS postings_cell ON postings(cell_id);
+";
+
+/// Split text into the lowercase words the index is keyed by.
+pub fn tokenize(value: &str) -> BTreeSet<String> {
+    normalize_for_search(value, false, false)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// One cell to index.
+struct IndexedCell {
+    sheet_index: usize,
+    sheet: String,
+    row: usize,
+    col: usize,
+    value: String,
+}
+
+/// A new or changed file to read, with its state and content hash.
+type StaleFile = (PathBuf, FileState, String);
+
+/// What is known about a file on disk, to tell whether it changed.
+struct FileState {
+    modified: i64,
+    size: i64,
+}
+
+fn file_state(path: &Path) -> Result<FileState> {
+    let metadata = fs::metadata(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as i64);
+    Ok(FileState {
+        modified,
+        size: metadata.len() as i64,
+    })
+}
+
+fn file_hash(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Cells handed to the writer at a time, which bounds what a reader holds.
+const BATCH_SIZE: usize = 10_000;
+
+/// Read every non-empty cell of a workbook, streaming each sheet and
+/// passing the cells on in batches. Stops early once `emit` breaks.
+fn read_cells(
+    path: &Path,
+    mut emit: impl FnMut(Vec<IndexedCell>) -> ControlFlow<()>,
+) -> Result<()> {
+    let mut workbook = open_excel_file(path)?;
+    let mut cells = Vec::new();
+    for (sheet_index, sheet) in workbook.sheet_names().into_iter().enumerate() {
+        let extent = stream_extent(&mut workbook, &sheet)?;
+        let (origin_row, origin_col) = extent.origin;
+        let mut stopped = false;
+        stream_rows(&mut workbook, &sheet, &extent, |row_idx, row| {
+            for (col_idx, cell) in row.iter().enumerate() {
+                let value = format_cell(cell);
+                if !value.is_empty() {
+                    cells.push(IndexedCell {
+                        sheet_index,
+                        sheet: sheet.clone(),
+                        row: origin_row + row_idx,
+                        col: origin_col + col_idx,
+                        value,
+                    });
+                }
+            }
+            if cells.len() >= BATCH_SIZE && emit(std::mem::take(&mut cells)).is_break() {
+                stopped = true;
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        })?;
+        if stopped {
+            return Ok(());
+        }
+    }
+    if !cells.is_empty() {
+        let _ = emit(cells);
+    }
+    Ok(())
+}
+
+/// What a reader thread reports about the stale file at an index.
+enum ReadEvent {
+    Cells(usize, Vec<IndexedCell>),
+    Done(usize, Result<()>),
+}
+
+/// Counts from bringing an index up to date.
+#[derive(Debug, Default)]
+pub struct RefreshSummary {
+    /// Files read and (re)indexed.
+    pub indexed: usize,
+    pub unchanged: usize,
+    /// Files that disappeared and were dropped from the index.
+    pub removed: usize,
+    /// Files that could not be read; they are retried on the next refresh.
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+/// A cell found by `SearchIndex::search`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexHit {
+    pub path: PathBuf,
+    /// Cell reference such as `Employees!C3`.
+    pub reference: String,
+    pub value: String,
+}
+
+/// On-disk inverted index of the cell values of every workbook below a
+/// directory. Cells are keyed by the words they contain, so a search only
+/// reads the cells that share words with the term. Files are tracked by
+/// modification time and size, with a content hash to skip files that were
+/// touched but not changed.
+pub struct SearchIndex {
+    conn: Connection,
+}
+
+impl SearchIndex {
+    /// Open an existing index.
+    pub fn open(index_path: &Path) -> Result<Self> {
+        if !index_path.exists() {
+            anyhow::bail!(
+                "No index at {:?}. Create one with `xlsq index build <DIR>`",
+                index_path
+            );
+        }
+        let conn = Connection::open(index_path)
+            .with_context(|| format!("Failed to open index {:?}", index_path))?;
+        Ok(SearchIndex { conn })
+    }
+
+    /// Open the index at `index_path` for `root`, creating it if needed.
+    pub fn create(index_path: &Path, root: &Path) -> Result<Self> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Failed to read directory {:?}", root))?;
+        let conn = Connection::open(index_path)
+            .with_context(|| format!("Failed to create index {:?}", index_path))?;
+        conn.execute_batch(SCHEMA)?;
+
+        let previous: Option<String> = conn
+            .query_row("SELECT value FROM meta WHERE key = 'root'", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let root_text = root.to_string_lossy();
+        if previous.as_deref() != Some(root_text.as_ref()) {
+            // A different directory: start from scratch
+            conn.execute_batch("DELETE FROM postings; DELETE FROM cells; DELETE FROM files;")?;
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('root', ?1)",
+                params![root_text],
+            )?;
+        }
+        Ok(SearchIndex { conn })
+    }
+
+    /// Directory the index covers.
+    pub fn root(&self) -> Result<PathBuf> {
+        let root: String =
+            self.conn
+                .query_row("SELECT value FROM meta WHERE key = 'root'", [], |row| {
+                    row.get(0)
+                })?;
+        Ok(PathBuf::from(root))
+    }
+
+    /// Bring the index up to date with the workbooks below its directory:
+    /// new and changed files are read (in parallel), deleted ones dropped,
+    /// and unchanged ones left alone. A file that fails part way keeps the
+    /// cells indexed for it before.
+    pub fn refresh(&mut self) -> Result<RefreshSummary> {
+        let workbooks = collect_workbooks(&[self.root()?])?;
+        let mut summary = RefreshSummary::default();
+
+        let mut known: HashMap<PathBuf, (i64, i64, i64, String)> = HashMap::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT id, path, modified, size, hash FROM files")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    PathBuf::from(row.get::<_, String>(1)?),
+                    (row.get(0)?, row.get(2)?, row.get(3)?, row.get(4)?),
+                ))
+            })?;
+            for row in rows {
+                let (path, state) = row?;
+                known.insert(path, state);
+            }
+        }
+
+        // Decide what to read; hashing only happens for files that look changed
+        let mut stale: Vec<StaleFile> = Vec::new();
+        let mut touched = Vec::new();
+        for path in &workbooks {
+            let state = match file_state(path) {
+                Ok(state) => state,
+                Err(err) => {
+                    summary.failed.push((path.clone(), err));
+                    continue;
+                }
+            };
+            match known.remove(path) {
+                Some((_, modified, size, _))
+                    if modified == state.modified && size == state.size =>
+                {
+                    summary.unchanged += 1
+                }
+                Some((id, _, _, hash)) => match file_hash(path) {
+                    Ok(new_hash) if new_hash == hash => {
+                        touched.push((id, state));
+                        summary.unchanged += 1;
+                    }
+                    Ok(new_hash) => stale.push((path.clone(), state, new_hash)),
+                    Err(err) => summary.failed.push((path.clone(), err)),
+                },
+                None => match file_hash(path) {
+                    Ok(hash) => stale.push((path.clone(), state, hash)),
+                    Err(err) => summary.failed.push((path.clone(), err)),
+                },
+            }
+        }
+
+        let tx = self.conn.transaction()?;
+        for (id, state) in touched {
+            tx.execute(
+                "UPDATE files SET modified = ?1, size = ?2 WHERE id = ?3",
+                params![state.modified, state.size, id],
+            )?;
+        }
+        // Whatever is left in `known` no longer exists
+        for (id, ..) in known.values() {
+            remove_file(&tx, *id)?;
+            summary.removed += 1;
+        }
+
+        // Stale files are read in parallel and their cells written as they
+        // arrive, so memory is bounded by the batches in flight rather than
+        // by the size of the files
+        let (sender, receiver) = mpsc::sync_channel(rayon::current_num_threads());
+        thread::scope(|scope| -> Result<()> {
+            let readers = &stale;
+            scope.spawn(move || {
+                readers.par_iter().enumerate().for_each_with(
+                    sender,
+                    |sender, (index, (path, ..))| {
+                        let result = read_cells(path, |cells| {
+                            match sender.send(ReadEvent::Cells(index, cells)) {
+                                Ok(()) => ControlFlow::Continue(()),
+                                Err(_) => ControlFlow::Break(()),
+                            }
+                        });
+                        let _ = sender.send(ReadEvent::Done(index, result));
+                    },
+                );
+            });
+
+            let mut pending: HashMap<usize, PendingFile> = HashMap::new();
+            for event in receiver {
+                match event {
+                    ReadEvent::Cells(index, cells) => {
+                        let file = match pending.get(&index) {
+                            Some(&file) => file,
+                            None => {
+                                let file = begin_file(&tx, &stale[index])?;
+                                pending.insert(index, file);
+                                file
+                            }
+                        };
+                        add_cells(&tx, -file.id, cells)?;
+                    }
+                    ReadEvent::Done(index, Ok(())) => {
+                        let file = match pending.remove(&index) {
+                            Some(file) => file,
+                            None => begin_file(&tx, &stale[index])?,
+                        };
+                        finish_file(&tx, file, &stale[index])?;
+                        summary.indexed += 1;
+                    }
+                    ReadEvent::Done(index, Err(err)) => {
+                        // Drop what was written and keep what was indexed
+                        // before; the file is read again next time
+                        if let Some(file) = pending.remove(&index) {
+                            abandon_file(&tx, file)?;
+                        }
+                        summary.failed.push((stale[index].0.clone(), err));
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        tx.commit()?;
+
+        Ok(summary)
+    }
+
+    /// Cells containing `term`, ordered by file, sheet and position. Words
+    /// of the term are looked up as prefixes of indexed words, so the term
+    /// has to start at a word boundary in the cell ("eng" finds
+    /// "Engineering", "neer" does not); candidates are then checked with the
+    /// same comparison as `search`. A term with no words (only punctuation,
+    /// say) is an error, since it would match every cell.
+    pub fn search(&self, term: &str, case_sensitive: bool) -> Result<Vec<IndexHit>> {
+        let tokens: Vec<String> = tokenize(term)
+            .into_iter()
+            .map(|token| format!("{}*", token))
+            .collect();
+        if tokens.is_empty() {
+            anyhow::bail!("Search term {:?} has no words to look up", term);
+        }
+
+        // Cells that have a word starting with each word of the term
+        let candidates = vec!["SELECT cell_id FROM postings WHERE token GLOB ?"; tokens.len()];
+        let filter = format!("WHERE c.id IN ({})", candidates.join(" INTERSECT "));
+        let sql = format!(
+            "SELECT f.path, c.sheet, c.row, c.col, c.value
+             FROM cells c JOIN files f ON f.id = c.file_id
+             {}
+             ORDER BY f.path, c.sheet_index, c.row, c.col",
+            filter
+        );
+
+        let needle = normalize_for_search(term, case_sensitive, false);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(tokens.iter()), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut hits = Vec::new();
+        for row in rows {
+            let (path, sheet, row, col, value) = row?;
+            if normalize_for_search(&value, case_sensitive, false).contains(&needle) {
+                hits.push(IndexHit {
+                    path: PathBuf::from(path),
+                    reference: sheet_cell_reference(&sheet, row as usize, col as usize),
+                    value,
+                });
+            }
+        }
+        Ok(hits)
+    }
+}
+
+/// A file being (re)indexed. Its new cells are written under the negated
+/// file id, so the cells indexed before stay in place until the whole file
+/// has been read.
+#[derive(Clone, Copy)]
+struct PendingFile {
+    id: i64,
+    /// Whether the file was indexed before this refresh.
+    existed: bool,
+}
+
+fn begin_file(tx: &rusqlite::Transaction, (path, state, hash): &StaleFile) -> Result<PendingFile> {
+    let path_text = path.to_string_lossy();
+    let previous: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM files WHERE path = ?1",
+            params![path_text],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(id) = previous {
+        return Ok(PendingFile { id, existed: true });
+    }
+    tx.execute(
+        "INSERT INTO files (path, modified, size, hash) VALUES (?1, ?2, ?3, ?4)",
+        params![path_text, state.modified, state.size, hash],
+    )?;
+    Ok(PendingFile {
+        id: tx.last_insert_rowid(),
+        existed: false,
+    })
+}
+
+/// Swap the cells indexed before for the ones just read.
+fn finish_file(
+    tx: &rusqlite::Transaction,
+    file: PendingFile,
+    (_, state, hash): &StaleFile,
+) -> Result<()> {
+    remove_cells(tx, file.id)?;
+    tx.execute(
+        "UPDATE cells SET file_id = ?1 WHERE file_id = ?2",
+        params![file.id, -file.id],
+    )?;
+    tx.execute(
+        "UPDATE files SET modified = ?1, size = ?2, hash = ?3 WHERE id = ?4",
+        params![state.modified, state.size, hash, file.id],
+    )?;
+    Ok(())
+}
+
+/// Drop the cells read from a file that then failed.
+fn abandon_file(tx: &rusqlite::Transaction, file: PendingFile) -> Result<()> {
+    remove_cells(tx, -file.id)?;
+    if !file.existed {
+        remove_file(tx, file.id)?;
+    }
+    Ok(())
+}
+
+fn add_cells(tx: &rusqlite::Transaction, file_id: i64, cells: Vec<IndexedCell>) -> Result<()> {
+    let mut insert_cell = tx.prepare_cached(
+        "INSERT INTO cells (file_id, sheet_index, sheet, row, col, value)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    let mut insert_posting =
+        tx.prepare_cached("INSERT OR IGNORE INTO postings (token, cell_id) VALUES (?1, ?2)")?;
+    for cell in cells {
+        insert_cell.execute(params![
+            file_id,
+            cell.sheet_index as i64,
+            cell.sheet,
+            cell.row as i64,
+            cell.col as i64,
+            cell.value
+        ])?;
+        let cell_id = tx.last_insert_rowid();
+        for token in tokenize(&cell.value) {
+            insert_posting.execute(params![token, cell_id])?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_cells(tx: &rusqlite::Transaction, file_id: i64) -> Result<()> {
+    tx.execute(
+        "DELETE FROM postings WHERE cell_id IN (SELECT id FROM cells WHERE file_id = ?1)",
+        params![file_id],
+    )?;
+    tx.execute("DELETE FROM cells WHERE file_id = ?1", params![file_id])?;
+    Ok(())
+}
+
+fn remove_file(tx: &rusqlite::Transaction, file_id: i64) -> Result<()> {
+    remove_cells(tx, file_id)?;
+    tx.execute("DELETE FROM files WHERE id = ?1", params![file_id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_workbook(path: &Path, name: &str) -> Result<()> {
+        use xlsxwriter::*;
+
+        let workbook = Workbook::new(path.to_str().unwrap())?;
+        let mut worksheet = workbook.add_worksheet(Some("People"))?;
+        worksheet.write_string(0, 0, "Name", None)?;
+        worksheet.write_string(1, 0, name, None)?;
+        worksheet.write_string(1, 1, "Engineering", None)?;
+        workbook.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<String> = tokenize("INV-2041, Straße").into_iter().collect();
+        assert_eq!(tokens, vec!["2041", "inv", "strasse"]);
+    }
+
+    #[test]
+    fn test_build_search_and_refresh() {
+        let dir = TempDir::new().unwrap();
+        let data = dir.path().join("data");
+        fs::create_dir(&data).unwrap();
+        write_workbook(&data.join("a.xlsx"), "Ada Lovelace").unwrap();
+        write_workbook(&data.join("b.xlsx"), "Grace Hopper").unwrap();
+        let index_path = dir.path().join("index.sqlite");
+
+        let mut index = SearchIndex::create(&index_path, &data).unwrap();
+        let summary = index.refresh().unwrap();
+        assert_eq!((summary.indexed, summary.unchanged), (2, 0));
+
+        let hits = index.search("hopper", false).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].reference, "People!A2");
+        assert_eq!(hits[0].value, "Grace Hopper");
+        assert!(hits[0].path.ends_with("b.xlsx"));
+
+        // Prefix of a word, then a term spanning two words
+        assert_eq!(index.search("engin", false).unwrap().len(), 2);
+        assert_eq!(index.search("ada love", false).unwrap().len(), 1);
+        assert!(index.search("Hopper", true).unwrap().len() == 1);
+        assert!(index.search("HOPPER", true).unwrap().is_empty());
+
+        // Nothing changed: nothing is read again
+        let summary = index.refresh().unwrap();
+        assert_eq!((summary.indexed, summary.unchanged), (0, 2));
+
+        // A changed file is re-read and a deleted one dropped
+        write_workbook(&data.join("a.xlsx"), "Alan Turing").unwrap();
+        fs::remove_file(data.join("b.xlsx")).unwrap();
+        let summary = index.refresh().unwrap();
+        assert_eq!((summary.indexed, summary.removed), (1, 1));
+        assert!(index.search("hopper", false).unwrap().is_empty());
+        assert_eq!(index.search("turing", false).unwrap().len(), 1);
+
+        // A file that cannot be read is reported, and retried next time
+        fs::write(data.join("a.xlsx"), "not a workbook").unwrap();
+        let summary = index.refresh().unwrap();
+        assert_eq!((summary.indexed, summary.failed.len()), (0, 1));
+        assert_eq!(index.refresh().unwrap().failed.len(), 1);
+        assert!(index.search("turing", false).unwrap().len() == 1);
+        assert!(index.search(" -, ", false).is_err());
+    }
+
+    #[test]
+    fn test_failed_reread_keeps_previous_cells() {
+        use std::io::Write;
+        use xlsxwriter::*;
+
+        let dir = TempDir::new().unwrap();
+        let data = dir.path().join("data");
+        fs::create_dir(&data).unwrap();
+        let path = data.join("a.xlsx");
+        write_workbook(&path, "Ada Lovelace").unwrap();
+        let mut index = SearchIndex::create(&dir.path().join("index.sqlite"), &data).unwrap();
+        index.refresh().unwrap();
+
+        // A full batch of cells is read before the broken second sheet fails
+        let scratch = dir.path().join("scratch.xlsx");
+        let workbook = Workbook::new(scratch.to_str().unwrap()).unwrap();
+        let mut filler = workbook.add_worksheet(Some("Filler")).unwrap();
+        for row in 0..BATCH_SIZE as u32 {
+            filler.write_string(row, 0, "Padding", None).unwrap();
+        }
+        let mut people = workbook.add_worksheet(Some("People")).unwrap();
+        people.write_string(0, 0, "Grace Hopper", None).unwrap();
+        workbook.close().unwrap();
+        let mut archive = zip::ZipArchive::new(fs::File::open(&scratch).unwrap()).unwrap();
+        let mut writer = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        for idx in 0..archive.len() {
+            let mut part = archive.by_index(idx).unwrap();
+            if part.name() != "xl/worksheets/sheet2.xml" {
+                writer.raw_copy_file(part).unwrap();
+                continue;
+            }
+            let mut xml = String::new();
+            part.read_to_string(&mut xml).unwrap();
+            writer
+                .start_file(part.name(), zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer
+                .write_all(xml.replacen("<c ", "<c r=\"!\" ", 1).as_bytes())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let summary = index.refresh().unwrap();
+        assert_eq!((summary.indexed, summary.failed.len()), (0, 1));
+        assert_eq!(index.search("lovelace", false).unwrap().len(), 1);
+        assert!(index.search("padding", false).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,406 @@
+use crate::address::cell_address;
+use crate::package::Package;
+use crate::stream::{sheet_extent, SheetExtent};
+use crate::XlsqError;
+use anyhow::{Context, Result};
+use calamine::{Reader, SheetType, SheetVisible, Xlsx};
+use quick_xml::events::Event;
+use serde_json::{Map, Value};
+use std::io::{Read, Seek};
+
+/// Document properties from `docProps/core.xml` and `docProps/app.xml`.
+/// Properties the workbook doesn't set are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentProperties {
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub author: Option<String>,
+    pub last_modified_by: Option<String>,
+    pub created: Option<String>,
+    pub modified: Option<String>,
+    pub application: Option<String>,
+}
+
+/// Position, kind, state and used range of a sheet, as listed by `xlsq sheets`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetSummary {
+    /// Index in workbook order, as accepted by `--sheet`.
+    pub index: usize,
+    pub name: String,
+    pub kind: SheetType,
+    pub visibility: SheetVisible,
+    /// Declared used range; `None` for chart sheets and other non-worksheets.
+    pub extent: Option<SheetExtent>,
+}
+
+impl SheetSummary {
+    /// One-line description, e.g. `0: Sales (worksheet, visible) A1:D4, 4 rows x 4 columns`.
+    pub fn describe(&self) -> String {
+        let mut line = format!(
+            "{}: {} ({}, {})",
+            self.index,
+            self.name,
+            kind_name(self.kind),
+            visibility_name(self.visibility)
+        );
+        if let Some(extent) = &self.extent {
+            line.push_str(&format!(
+                " {}, {} rows x {} columns",
+                extent_range(extent),
+                extent.height,
+                extent.width
+            ));
+        }
+        line
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        object.insert("index".to_string(), Value::from(self.index));
+        object.insert("name".to_string(), Value::from(self.name.clone()));
+        object.insert("kind".to_string(), Value::from(kind_name(self.kind)));
+        object.insert(
+            "visibility".to_string(),
+            Value::from(visibility_name(self.visibility)),
+        );
+        let dimensions = match &self.extent {
+            Some(extent) => {
+                let mut dimensions = Map::new();
+                dimensions.insert("range".to_string(), Value::from(extent_range(extent)));
+                dimensions.insert("rows".to_string(), Value::from(extent.height));
+                dimensions.insert("columns".to_string(), Value::from(extent.width));
+                Value::Object(dimensions)
+            }
+            None => Value::Null,
+        };
+        object.insert("dimensions".to_string(), dimensions);
+        Value::Object(object)
+    }
+}
+
+/// Summarize every sheet in workbook order, using each sheet's declared
+/// dimension where it can be trusted (see `sheet_extent`).
+pub fn sheet_summaries<RS: Read + Seek>(workbook: &mut Xlsx<RS>) -> Result<Vec<SheetSummary>> {
+    let metadata = workbook.sheets_metadata().to_vec();
+    let mut sheets = Vec::with_capacity(metadata.len());
+    for (index, sheet) in metadata.into_iter().enumerate() {
+        let extent = match sheet.typ {
+            SheetType::WorkSheet => Some(sheet_extent(workbook, &sheet.name)?),
+            _ => None,
+        };
+        sheets.push(SheetSummary {
+            index,
+            name: sheet.name,
+            kind: sheet.typ,
+            visibility: sheet.visible,
+            extent,
+        });
+    }
+    Ok(sheets)
+}
+
+/// What `xlsq info` reports about one sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetInfo {
+    pub summary: SheetSummary,
+    pub formulas: usize,
+    pub merged_regions: usize,
+}
+
+/// Summary of a workbook for `xlsq info`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkbookInfo {
+    pub properties: DocumentProperties,
+    pub sheets: Vec<SheetInfo>,
+    pub defined_names: usize,
+}
+
+impl WorkbookInfo {
+    pub fn formulas(&self) -> usize {
+        self.sheets.iter().map(|sheet| sheet.formulas).sum()
+    }
+
+    pub fn merged_regions(&self) -> usize {
+        self.sheets.iter().map(|sheet| sheet.merged_regions).sum()
+    }
+
+    fn count_visibility(&self, visibility: SheetVisible) -> usize {
+        self.sheets
+            .iter()
+            .filter(|sheet| sheet.summary.visibility == visibility)
+            .count()
+    }
+}
+
+/// Read the document properties of a workbook. calamine doesn't expose
+/// them, so the parts are read straight from the zip package.
+pub fn read_document_properties(package: &mut Package) -> Result<DocumentProperties> {
+    let mut properties = DocumentProperties::default();
+
+    if let Some(core) = package.read_part("docProps/core.xml")? {
+        parse_properties(&core, |name, value| {
+            let field = match name {
+                b"title" => &mut properties.title,
+                b"subject" => &mut properties.subject,
+                b"creator" => &mut properties.author,
+                b"lastModifiedBy" => &mut properties.last_modified_by,
+                b"created" => &mut properties.created,
+                b"modified" => &mut properties.modified,
+                _ => return,
+            };
+            *field = Some(value);
+        })?;
+    }
+    if let Some(app) = package.read_part("docProps/app.xml")? {
+        parse_properties(&app, |name, value| {
+            if name == b"Application" {
+                properties.application = Some(value);
+            }
+        })?;
+    }
+
+    Ok(properties)
+}
+
+/// Call `visit` with the local name and text of every non-empty leaf element.
+fn parse_properties(xml: &str, mut visit: impl FnMut(&[u8], String)) -> Result<()> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut element: Option<Vec<u8>> = None;
+    loop {
+        match reader
+            .read_event()
+            .context("Malformed document properties")?
+        {
+            Event::Start(start) => element = Some(start.local_name().as_ref().to_vec()),
+            Event::Text(text) => {
+                if let Some(name) = &element {
+                    let value = text.unescape().context("Malformed document properties")?;
+                    let value = value.trim();
+                    if !value.is_empty() {
+                        visit(name, value.to_string());
+                    }
+                }
+            }
+            Event::End(_) => element = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Gather sheet states, used ranges and formula, merged region and defined
+/// name counts. Used ranges come from each sheet's declared dimension when
+/// it can be trusted, and formulas are counted from the sheet XML, so cell
+/// values are only read for sheets that need measuring.
+pub fn workbook_info<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    package: &mut Package,
+    properties: DocumentProperties,
+) -> Result<WorkbookInfo> {
+    let mut sheets = Vec::new();
+    for summary in sheet_summaries(workbook)? {
+        let mut info = SheetInfo {
+            summary,
+            formulas: 0,
+            merged_regions: 0,
+        };
+        if info.summary.kind == SheetType::WorkSheet {
+            let name = info.summary.name.clone();
+            info.formulas = count_formulas(package, &name)?;
+            if let Some(merged) = workbook.worksheet_merge_cells(&name) {
+                info.merged_regions = merged
+                    .map_err(|source| XlsqError::UnreadableSheet {
+                        sheet: name.clone(),
+                        source,
+                    })?
+                    .len();
+            }
+        }
+        sheets.push(info);
+    }
+
+    Ok(WorkbookInfo {
+        properties,
+        sheets,
+        defined_names: workbook.defined_names().len(),
+    })
+}
+
+/// Number of cells in a sheet holding a formula. Every such cell has an
+/// `<f>` element, shared formulas included, so the values aren't parsed.
+/// Formulas outside `<sheetData>` (in extension lists, say) aren't cells.
+fn count_formulas(package: &mut Package, sheet_name: &str) -> Result<usize> {
+    let Some(sheet_part) = package.sheet_part(sheet_name)? else {
+        return Ok(0);
+    };
+    let Some(xml) = package.read_part(&sheet_part)? else {
+        return Ok(0);
+    };
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut in_sheet_data = false;
+    let mut formulas = 0;
+    loop {
+        let event = reader
+            .read_event()
+            .with_context(|| format!("Malformed {}", sheet_part))?;
+        match event {
+            Event::Start(element) if element.local_name().as_ref() == b"sheetData" => {
+                in_sheet_data = true
+            }
+            Event::End(element) if element.local_name().as_ref() == b"sheetData" => {
+                in_sheet_data = false
+            }
+            Event::Start(element) | Event::Empty(element)
+                if in_sheet_data && element.local_name().as_ref() == b"f" =>
+            {
+                formulas += 1
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(formulas)
+}
+
+pub fn visibility_name(visibility: SheetVisible) -> &'static str {
+    match visibility {
+        SheetVisible::Visible => "visible",
+        SheetVisible::Hidden => "hidden",
+        SheetVisible::VeryHidden => "very hidden",
+    }
+}
+
+pub fn kind_name(kind: SheetType) -> &'static str {
+    match kind {
+        SheetType::WorkSheet => "worksheet",
+        SheetType::ChartSheet => "chart sheet",
+        SheetType::MacroSheet => "macro sheet",
+        SheetType::DialogSheet => "dialog sheet",
+        SheetType::Vba => "VBA module",
+    }
+}
+
+/// A1-style used range, e.g. "A1:D4", or just "B2" for a single cell.
+pub fn extent_range(extent: &SheetExtent) -> String {
+    let (row, col) = extent.origin;
+    let start = cell_address(row, col);
+    if extent.height <= 1 && extent.width <= 1 {
+        return start;
+    }
+    let end = cell_address(row + extent.height - 1, col + extent.width - 1);
+    format!("{}:{}", start, end)
+}
+
+pub fn print_info(info: &WorkbookInfo) {
+    let properties = &info.properties;
+    for (label, value) in [
+        ("Title", &properties.title),
+        ("Subject", &properties.subject),
+        ("Author", &properties.author),
+        ("Last modified by", &properties.last_modified_by),
+        ("Created", &properties.created),
+        ("Modified", &properties.modified),
+        ("Application", &properties.application),
+    ] {
+        println!("{}: {}", label, value.as_deref().unwrap_or("(not set)"));
+    }
+    println!();
+
+    println!(
+        "Sheets: {} ({} visible, {} hidden, {} very hidden)",
+        info.sheets.len(),
+        info.count_visibility(SheetVisible::Visible),
+        info.count_visibility(SheetVisible::Hidden),
+        info.count_visibility(SheetVisible::VeryHidden)
+    );
+    for sheet in &info.sheets {
+        println!("  {}", sheet.summary.describe());
+    }
+    println!();
+
+    println!("Formulas: {}", info.formulas());
+    println!("Merged regions: {}", info.merged_regions());
+    println!("Defined names: {}", info.defined_names);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::open_excel_file;
+    use std::fs::File;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_core_properties() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <dc:title>Q1 &amp; Q2 Budget</dc:title>
+  <dc:creator>Ada</dc:creator>
+  <cp:keywords></cp:keywords>
+  <dcterms:created xsi:type="dcterms:W3CDTF">2024-01-02T03:04:05Z</dcterms:created>
+</cp:coreProperties>"#;
+
+        let mut seen = Vec::new();
+        parse_properties(xml, |name, value| {
+            seen.push((String::from_utf8_lossy(name).into_owned(), value))
+        })
+        .unwrap();
+        assert_eq!(
+            seen,
+            vec![
+                ("title".to_string(), "Q1 & Q2 Budget".to_string()),
+                ("creator".to_string(), "Ada".to_string()),
+                ("created".to_string(), "2024-01-02T03:04:05Z".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extent_range() {
+        let extent = SheetExtent {
+            origin: (1, 1),
+            height: 4,
+            width: 3,
+            cells: None,
+        };
+        assert_eq!(extent_range(&extent), "B2:D5");
+        assert_eq!(extent_range(&SheetExtent::default()), "A1");
+    }
+
+    #[test]
+    fn test_workbook_info() {
+        use xlsxwriter::*;
+
+        let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let path = temp_file.path().to_str().unwrap();
+        let workbook = Workbook::new(path).unwrap();
+        let mut totals = workbook.add_worksheet(Some("Totals")).unwrap();
+        totals.write_number(0, 0, 1.0, None).unwrap();
+        totals.write_number(1, 0, 2.0, None).unwrap();
+        totals.write_formula(2, 0, "=SUM(A1:A2)", None).unwrap();
+        totals.merge_range(0, 1, 1, 2, "Merged", None).unwrap();
+        let mut notes = workbook.add_worksheet(Some("Notes")).unwrap();
+        notes.write_string(0, 0, "hidden", None).unwrap();
+        notes.hide();
+        workbook.define_name("Inputs", "=Totals!$A$1:$A$2").unwrap();
+        workbook.close().unwrap();
+
+        let mut package = Package::new(File::open(path).unwrap()).unwrap();
+        let properties = read_document_properties(&mut package).unwrap();
+        assert!(properties.created.is_some());
+        assert_eq!(properties.title, None);
+
+        let mut workbook = open_excel_file(path).unwrap();
+        let info = workbook_info(&mut workbook, &mut package, properties).unwrap();
+        assert_eq!(info.sheets.len(), 2);
+        assert_eq!(
+            info.sheets[0].summary.describe(),
+            "0: Totals (worksheet, visible) A1:C3, 3 rows x 3 columns"
+        );
+        assert_eq!(info.sheets[1].summary.visibility, SheetVisible::Hidden);
+        assert_eq!(info.formulas(), 1);
+        assert_eq!(info.merged_regions(), 1);
+        assert_eq!(info.defined_names, 1);
+    }
+}
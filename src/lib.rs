@@ -1,7 +1,43 @@
-use anyhow::{Context, Result};
-use calamine::{open_workbook, Data, Reader, Xlsx};
+use anyhow::Result;
+use calamine::{open_workbook, Data, Range, Reader, SheetVisible, Xlsx};
+use comments::Comment;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek};
+use std::ops::ControlFlow;
 use std::path::Path;
 
+pub mod address;
+pub mod calc;
+pub mod cell;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod comments;
+pub mod deps;
+mod error;
+pub mod export;
+pub mod formula;
+mod functions;
+mod fuzzy;
+#[cfg(feature = "sqlite")]
+pub mod index;
+pub mod info;
+pub mod links;
+mod number_format;
+pub mod package;
+pub mod parallel;
+pub mod search;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stream;
+pub mod style;
+pub mod styles;
+pub mod text;
+pub mod validation;
+
+pub use error::XlsqError;
+pub use search::{search_in_sheet, search_sheet_to, MatchMode, SearchOptions, SearchOutput};
+pub use style::Style;
+
 pub fn format_cell(cell: &Data) -> String {
     match cell {
         Data::Empty => String::new(),
@@ -22,10 +58,10 @@ pub fn format_cell(cell: &Data) -> String {
     }
 }
 
-pub fn get_sheet_name(
-    workbook: &Xlsx<std::io::BufReader<std::fs::File>>,
+pub fn get_sheet_name<RS: Read + Seek>(
+    workbook: &Xlsx<RS>,
     identifier: &str,
-) -> Result<String> {
+) -> Result<String, XlsqError> {
     let sheet_names = workbook.sheet_names();
 
     // Try to parse as index first
@@ -33,11 +69,10 @@ pub fn get_sheet_name(
         if index < sheet_names.len() {
             return Ok(sheet_names[index].clone());
         } else {
-            return Err(anyhow::anyhow!(
-                "Sheet index {} out of range. Available sheets: 0-{}",
+            return Err(XlsqError::SheetIndexOutOfRange {
                 index,
-                sheet_names.len() - 1
-            ));
+                count: sheet_names.len(),
+            });
         }
     }
 
@@ -46,138 +81,277 @@ pub fn get_sheet_name(
         return Ok(identifier.to_string());
     }
 
-    Err(anyhow::anyhow!(
-        "Sheet '{}' not found. Available sheets: {:?}",
-        identifier,
-        sheet_names
-    ))
+    Err(XlsqError::SheetNotFound {
+        name: identifier.to_string(),
+        available: sheet_names,
+    })
 }
 
-pub fn list_sheets(workbook: &Xlsx<std::io::BufReader<std::fs::File>>) -> Result<()> {
+/// List the sheets with their kind, visibility and used range, as text or
+/// as a JSON array. Hidden sheets keep their workbook index when
+/// `visible_only` leaves them out, so indices still work with `--sheet`.
+pub fn list_sheets<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    visible_only: bool,
+    json: bool,
+) -> Result<()> {
+    let sheets: Vec<_> = info::sheet_summaries(workbook)?
+        .into_iter()
+        .filter(|sheet| !visible_only || sheet.visibility == SheetVisible::Visible)
+        .collect();
+
+    if json {
+        let sheets: Vec<_> = sheets.iter().map(|sheet| sheet.to_json()).collect();
+        println!("{}", serde_json::to_string_pretty(&sheets)?);
+        return Ok(());
+    }
+
     println!("Available sheets:");
-    for (index, sheet_name) in workbook.sheet_names().iter().enumerate() {
-        println!("  {}: {}", index, sheet_name);
+    for sheet in &sheets {
+        println!("  {}", sheet.describe());
     }
     Ok(())
 }
 
-pub fn show_sheet(
-    workbook: &mut Xlsx<std::io::BufReader<std::fs::File>>,
-    sheet_identifier: &str,
-    max_rows: usize,
-) -> Result<()> {
-    let sheet_name = get_sheet_name(workbook, sheet_identifier)?;
-
-    if let Ok(range) = workbook.worksheet_range(&sheet_name) {
-        println!("Sheet: {}", sheet_name);
-        println!(
-            "Dimensions: {} rows x {} columns",
-            range.height(),
-            range.width()
-        );
-        println!();
-
-        let rows_to_show = if max_rows == 0 {
-            range.height()
-        } else {
-            max_rows.min(range.height())
-        };
-
-        for (row_idx, row) in range.rows().enumerate().take(rows_to_show) {
-            print!("Row {}: ", row_idx + 1);
-            for (col_idx, cell) in row.iter().enumerate() {
-                if col_idx > 0 {
-                    print!(" | ");
-                }
-                print!("{}", format_cell(cell));
-            }
-            println!();
-        }
-
-        if rows_to_show < range.height() {
-            println!("\n... and {} more rows", range.height() - rows_to_show);
-        }
-    } else {
-        println!("Could not read sheet: {}", sheet_name);
-    }
+fn print_sheet_header(sheet_name: &str, height: usize, width: usize) {
+    println!("Sheet: {}", sheet_name);
+    println!("Dimensions: {} rows x {} columns", height, width);
+    println!();
+}
 
-    Ok(())
+/// How `show_sheet` lays out the rows of a sheet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// One line per row, cells separated by `|`.
+    #[default]
+    Rows,
+    /// One block of `Header: value` lines per row, like psql's `\x`.
+    Vertical,
+    /// Rows and columns swapped: one line per column.
+    Transposed,
 }
 
-pub fn search_in_sheet(
-    workbook: &mut Xlsx<std::io::BufReader<std::fs::File>>,
+/// Print the first `max_rows` rows of a sheet (all rows for 0). Rows are
+/// streamed, so only the rows shown are ever read. Cells with one of
+/// `comments` get a numbered marker, and the comments are listed below.
+///
+/// With `Layout::Vertical` the first row supplies the labels and `max_rows`
+/// counts the records below it. `Layout::Transposed` holds the rows shown
+/// in memory, since each output line needs a cell from every one of them.
+pub fn show_sheet<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
     sheet_identifier: &str,
-    search_value: &str,
-    case_sensitive: bool,
+    max_rows: usize,
+    comments: &[Comment],
+    layout: Layout,
+    style: Style,
 ) -> Result<()> {
     let sheet_name = get_sheet_name(workbook, sheet_identifier)?;
-
-    if let Ok(range) = workbook.worksheet_range(&sheet_name) {
-        println!("Searching for '{}' in sheet: {}", search_value, sheet_name);
-        println!();
-
-        let mut matches_found = 0;
-        let search_term = if case_sensitive {
-            search_value.to_string()
-        } else {
-            search_value.to_lowercase()
-        };
-
-        for (row_idx, row) in range.rows().enumerate() {
-            let mut row_matches = false;
-            let mut matching_columns = Vec::new();
-
-            // Check each cell in the row for matches
-            for (col_idx, cell) in row.iter().enumerate() {
-                let cell_value = format_cell(cell);
-                let comparison_value = if case_sensitive {
-                    cell_value.clone()
-                } else {
-                    cell_value.to_lowercase()
-                };
-
-                if comparison_value.contains(&search_term) {
-                    row_matches = true;
-                    matching_columns.push(col_idx);
+    let extent = stream::stream_extent(workbook, &sheet_name)?;
+    let commented: HashMap<(usize, usize), &Comment> = comments
+        .iter()
+        .map(|comment| ((comment.row, comment.col), comment))
+        .collect();
+
+    let mut rows_read = 0;
+    let mut shown = 0;
+    let mut truncated = false;
+    let mut footnotes = Vec::new();
+    let mut labels: Vec<String> = Vec::new();
+    let mut label_width = 0;
+    let mut columns: Vec<Vec<String>> = Vec::new();
+    let mut row_numbers = Vec::new();
+    stream::stream_rows(workbook, &sheet_name, &extent, |row_idx, row| {
+        if rows_read == 0 {
+            print_sheet_header(&sheet_name, extent.height, extent.width);
+        }
+        if max_rows != 0 && shown == max_rows {
+            truncated = true;
+            return ControlFlow::Break(());
+        }
+        rows_read += 1;
+
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(col_idx, cell)| {
+                let mut text = format_cell(cell);
+                let position = (extent.origin.0 + row_idx, extent.origin.1 + col_idx);
+                if let Some(comment) = commented.get(&position) {
+                    footnotes.push(*comment);
+                    text.push_str(&format!(" [{}]", footnotes.len()));
                 }
-            }
-
-            if row_matches {
-                matches_found += 1;
-                println!("Match {} - Row {}: ", matches_found, row_idx + 1);
-
-                for (col_idx, cell) in row.iter().enumerate() {
+                text
+            })
+            .collect();
+        // Numbered as in Excel, like `search` matches
+        let row_number = extent.origin.0 + row_idx + 1;
+
+        match layout {
+            Layout::Rows => {
+                print!("Row {}: ", style.row_number(&row_number.to_string()));
+                for (col_idx, text) in cells.iter().enumerate() {
                     if col_idx > 0 {
                         print!(" | ");
                     }
-                    let cell_value = format_cell(cell);
-
-                    // Highlight matching cells
-                    if matching_columns.contains(&col_idx) {
-                        print!("***{}***", cell_value);
+                    // The first row is treated as the header
+                    if row_idx == 0 {
+                        print!("{}", style.header(text));
                     } else {
-                        print!("{}", cell_value);
+                        print!("{}", text);
                     }
                 }
                 println!();
-                println!();
+            }
+            Layout::Vertical if row_idx == 0 => {
+                // Blank headers fall back to the column letter
+                labels = cells
+                    .into_iter()
+                    .enumerate()
+                    .map(|(col_idx, text)| {
+                        if text.is_empty() {
+                            address::column_name(extent.origin.1 + col_idx)
+                        } else {
+                            text
+                        }
+                    })
+                    .collect();
+                label_width = labels
+                    .iter()
+                    .map(|label| label.chars().count())
+                    .max()
+                    .unwrap_or_default();
+                return ControlFlow::Continue(());
+            }
+            Layout::Vertical => {
+                println!("-[ Row {} ]-", style.row_number(&row_number.to_string()));
+                for (col_idx, text) in cells.iter().enumerate() {
+                    let label = labels
+                        .get(col_idx)
+                        .cloned()
+                        .unwrap_or_else(|| address::column_name(extent.origin.1 + col_idx));
+                    let padded = format!("{:<1$}", label, label_width);
+                    println!("{}: {}", style.header(&padded), text);
+                }
+            }
+            Layout::Transposed => {
+                row_numbers.push(row_number);
+                if columns.len() < cells.len() {
+                    columns.resize(cells.len(), vec![String::new(); shown]);
+                }
+                for (col_idx, column) in columns.iter_mut().enumerate() {
+                    column.push(cells.get(col_idx).cloned().unwrap_or_default());
+                }
             }
         }
+        shown += 1;
+        ControlFlow::Continue(())
+    })?;
 
-        if matches_found == 0 {
-            println!("No matches found for '{}'", search_value);
-        } else {
-            println!("Found {} matching row(s)", matches_found);
+    if rows_read == 0 {
+        print_sheet_header(&sheet_name, 0, 0);
+    }
+    if !row_numbers.is_empty() {
+        let numbers: Vec<String> = row_numbers
+            .iter()
+            .map(|number| style.row_number(&number.to_string()))
+            .collect();
+        println!("Row: {}", numbers.join(" | "));
+    }
+    for (col_idx, column) in columns.iter().enumerate() {
+        let name = address::column_name(extent.origin.1 + col_idx);
+        print!("Column {}: ", style.row_number(&name));
+        for (idx, text) in column.iter().enumerate() {
+            if idx > 0 {
+                print!(" | ");
+            }
+            if idx == 0 {
+                print!("{}", style.header(text));
+            } else {
+                print!("{}", text);
+            }
+        }
+        println!();
+    }
+    if truncated {
+        // The declared extent gives the total without reading the rest
+        match extent.height.checked_sub(rows_read) {
+            Some(remaining) if remaining > 0 => println!("\n... and {} more rows", remaining),
+            _ => println!("\n... and more rows"),
         }
-    } else {
-        println!("Could not read sheet: {}", sheet_name);
     }
 
+    if !footnotes.is_empty() {
+        println!("\nComments:");
+        for (number, comment) in footnotes.iter().enumerate() {
+            let author = comment
+                .author
+                .as_ref()
+                .map(|author| format!(" ({})", author))
+                .unwrap_or_default();
+            println!(
+                "  [{}] {}{}: {}",
+                number + 1,
+                comment.address(),
+                author,
+                comment.text.replace('\n', "\n      ")
+            );
+        }
+    }
     Ok(())
 }
 
-pub fn open_excel_file<P: AsRef<Path>>(path: P) -> Result<Xlsx<std::io::BufReader<std::fs::File>>> {
-    open_workbook(&path).with_context(|| format!("Failed to open Excel file: {:?}", path.as_ref()))
+/// Read a whole sheet, reporting calamine's failure as `XlsqError::UnreadableSheet`.
+pub fn read_sheet<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+) -> Result<Range<Data>, XlsqError> {
+    workbook
+        .worksheet_range(sheet_name)
+        .map_err(|source| XlsqError::UnreadableSheet {
+            sheet: sheet_name.to_string(),
+            source,
+        })
+}
+
+/// Formatted values of a rectangular block of a sheet in row-major order.
+/// `start` and `end` are absolute 0-based (row, column) corners; cells
+/// outside the used range are empty. Error cells give the bare error, such
+/// as `#DIV/0!`.
+pub fn range_values(
+    range: &Range<Data>,
+    start: (usize, usize),
+    end: (usize, usize),
+) -> Vec<String> {
+    (start.0..=end.0)
+        .flat_map(|row| (start.1..=end.1).map(move |col| (row, col)))
+        .map(
+            |(row, col)| match range.get_value((row as u32, col as u32)) {
+                Some(Data::Error(e)) => e.to_string(),
+                Some(cell) => format_cell(cell),
+                None => String::new(),
+            },
+        )
+        .collect()
+}
+
+pub fn open_excel_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<Xlsx<std::io::BufReader<std::fs::File>>, XlsqError> {
+    open_workbook(&path).map_err(|source| XlsqError::Open {
+        path: path.as_ref().to_path_buf(),
+        source,
+    })
+}
+
+/// Open a workbook from any seekable reader, e.g. an upload held in memory.
+pub fn open_excel_reader<RS: Read + Seek>(reader: RS) -> Result<Xlsx<RS>, XlsqError> {
+    Xlsx::new(reader).map_err(|source| XlsqError::OpenReader { source })
+}
+
+/// Open a workbook from bytes already in memory.
+pub fn open_excel_bytes(bytes: &[u8]) -> Result<Xlsx<Cursor<&[u8]>>, XlsqError> {
+    open_excel_reader(Cursor::new(bytes))
 }
 
 #[cfg(test)]
@@ -186,6 +360,26 @@ mod tests {
     use calamine::Data;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn test_range_values() {
+        let mut range = Range::new((1, 1), (2, 2));
+        range.set_value((1, 1), Data::String("host".to_string()));
+        range.set_value((1, 2), Data::String("db.internal".to_string()));
+        range.set_value((2, 1), Data::String("port".to_string()));
+        range.set_value((2, 2), Data::Float(5432.0));
+
+        assert_eq!(range_values(&range, (1, 2), (1, 2)), vec!["db.internal"]);
+        assert_eq!(
+            range_values(&range, (1, 1), (2, 2)),
+            vec!["host", "db.internal", "port", "5432"]
+        );
+        // Outside the used range
+        assert_eq!(range_values(&range, (0, 0), (0, 1)), vec!["", ""]);
+
+        range.set_value((2, 2), Data::Error(calamine::CellErrorType::Div0));
+        assert_eq!(range_values(&range, (2, 2), (2, 2)), vec!["#DIV/0!"]);
+    }
+
     #[test]
     fn test_format_cell_string() {
         let cell = Data::String("Hello World".to_string());
@@ -237,7 +431,15 @@ mod tests {
         // Test invalid index
         let result = get_sheet_name(&workbook, "99");
         assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("out of range"));
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("out of range"));
+        assert!(matches!(
+            err,
+            XlsqError::SheetIndexOutOfRange {
+                index: 99,
+                count: 1
+            }
+        ));
     }
 
     #[test]
@@ -250,7 +452,11 @@ mod tests {
 
         let result = get_sheet_name(&workbook, "NonExistentSheet");
         assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("not found"));
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        assert!(
+            matches!(err, XlsqError::SheetNotFound { ref name, .. } if name == "NonExistentSheet")
+        );
     }
 
     #[test]
@@ -276,6 +482,33 @@ mod tests {
         assert_eq!(format_cell(cell), "30");
     }
 
+    #[test]
+    fn test_open_missing_file_error() {
+        let err = match open_excel_file("does-not-exist.xlsx") {
+            Ok(_) => panic!("Expected an error for a missing file"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, XlsqError::Open { .. }));
+        assert!(err.to_string().contains("Failed to open Excel file"));
+    }
+
+    #[test]
+    fn test_open_excel_bytes() {
+        let temp_file = create_test_excel_file().expect("Failed to create test file");
+        let bytes = std::fs::read(temp_file.path()).unwrap();
+
+        let mut workbook = open_excel_bytes(&bytes).unwrap();
+        assert_eq!(get_sheet_name(&workbook, "0").unwrap(), "Sheet1");
+        let range = read_sheet(&mut workbook, "Sheet1").unwrap();
+        assert_eq!(format_cell(range.get((2, 0)).unwrap()), "Bob");
+
+        let err = match open_excel_bytes(b"not a workbook") {
+            Ok(_) => panic!("Expected an error for invalid bytes"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, XlsqError::OpenReader { .. }));
+    }
+
     fn create_test_excel_file() -> Result<NamedTempFile> {
         use xlsxwriter::*;
 
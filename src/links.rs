@@ -0,0 +1,343 @@
+use crate::address::{parse_cell_address, sheet_cell_reference, unquote_sheet_name};
+use crate::package::{attribute, for_each_element, Package};
+use crate::{format_cell, read_sheet};
+use anyhow::{Context, Result};
+use calamine::{Data, Range, Reader, Xlsx};
+use quick_xml::events::BytesStart;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Seek};
+
+/// Where a hyperlink points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// A web or mail address, e.g. `https://...` or `mailto:...`.
+    Url(String),
+    /// A path to another file.
+    File(String),
+    /// A place inside this workbook: `Sheet!A1`, a range or a defined name.
+    Internal(String),
+}
+
+impl LinkTarget {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LinkTarget::Url(_) => "url",
+            LinkTarget::File(_) => "file",
+            LinkTarget::Internal(_) => "internal",
+        }
+    }
+}
+
+impl fmt::Display for LinkTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkTarget::Url(target) | LinkTarget::File(target) | LinkTarget::Internal(target) => {
+                f.write_str(target)
+            }
+        }
+    }
+}
+
+/// A hyperlink on a cell (or on the top-left cell of a range).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperlink {
+    pub sheet: String,
+    /// 0-based worksheet row and column of the linked cell.
+    pub row: usize,
+    pub col: usize,
+    /// Text shown for the link: its display text, else the cell's value.
+    pub display: String,
+    pub target: LinkTarget,
+}
+
+impl Hyperlink {
+    pub fn reference(&self) -> String {
+        sheet_cell_reference(&self.sheet, self.row, self.col)
+    }
+}
+
+/// A `<hyperlink>` element of a worksheet part, before its relationship is
+/// looked up.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RawLink {
+    pub(crate) row: usize,
+    pub(crate) col: usize,
+    relationship: Option<String>,
+    location: Option<String>,
+    pub(crate) display: Option<String>,
+}
+
+impl RawLink {
+    /// Read a `<hyperlink>` element; `None` when its `ref` isn't a cell.
+    pub(crate) fn from_element(element: &BytesStart) -> Result<Option<Self>> {
+        let reference = attribute(element, b"ref")?.unwrap_or_default();
+        let first_cell = reference.split(':').next().unwrap_or_default();
+        let Some((row, col)) = parse_cell_address(first_cell) else {
+            return Ok(None);
+        };
+        Ok(Some(RawLink {
+            row,
+            col,
+            relationship: attribute(element, b"id")?,
+            location: attribute(element, b"location")?.filter(|location| !location.is_empty()),
+            display: attribute(element, b"display")?.filter(|display| !display.is_empty()),
+        }))
+    }
+
+    /// Where the link points, given the sheet's hyperlink relationships;
+    /// `None` when it points nowhere.
+    pub(crate) fn target(&self, targets: &HashMap<String, String>) -> Option<LinkTarget> {
+        let external = self
+            .relationship
+            .as_ref()
+            .and_then(|id| targets.get(id))
+            .cloned();
+        match (external, self.location.clone()) {
+            (Some(external), Some(location)) => {
+                Some(external_target(format!("{}#{}", external, location)))
+            }
+            (Some(external), None) => Some(external_target(external)),
+            (None, Some(location)) => Some(LinkTarget::Internal(location)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Targets of a worksheet part's hyperlink relationships, by id.
+pub(crate) fn hyperlink_targets(
+    package: &mut Package,
+    sheet_part: &str,
+) -> Result<HashMap<String, String>> {
+    Ok(package
+        .relationships(sheet_part)?
+        .into_iter()
+        .filter(|relationship| relationship.kind == "hyperlink")
+        .map(|relationship| (relationship.id, relationship.target))
+        .collect())
+}
+
+/// Read the hyperlinks of the given sheets, in sheet order and then in the
+/// order each sheet lists them.
+pub fn read_links<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    package: &mut Package,
+    sheet_names: &[String],
+) -> Result<Vec<Hyperlink>> {
+    let mut links = Vec::new();
+    for sheet_name in sheet_names {
+        let Some(sheet_part) = package.sheet_part(sheet_name)? else {
+            continue;
+        };
+        let Some(xml) = package.read_part(&sheet_part)? else {
+            continue;
+        };
+        let raw_links =
+            parse_hyperlinks(&xml).with_context(|| format!("Malformed {}", sheet_part))?;
+        if raw_links.is_empty() {
+            continue;
+        }
+
+        let targets = hyperlink_targets(package, &sheet_part)?;
+        // Cell values are only needed for links without their own display text
+        let mut values: Option<Range<Data>> = None;
+
+        for raw in raw_links {
+            let Some(target) = raw.target(&targets) else {
+                continue;
+            };
+            let display = match raw.display {
+                Some(display) => display,
+                None => {
+                    if values.is_none() {
+                        values = Some(read_sheet(workbook, sheet_name)?);
+                    }
+                    values
+                        .as_ref()
+                        .and_then(|range| range.get_value((raw.row as u32, raw.col as u32)))
+                        .map(format_cell)
+                        .unwrap_or_default()
+                }
+            };
+
+            links.push(Hyperlink {
+                sheet: sheet_name.clone(),
+                row: raw.row,
+                col: raw.col,
+                display,
+                target,
+            });
+        }
+    }
+    Ok(links)
+}
+
+fn parse_hyperlinks(xml: &str) -> Result<Vec<RawLink>> {
+    let mut links = Vec::new();
+    for_each_element(xml, |element| {
+        if element.local_name().as_ref() == b"hyperlink" {
+            links.extend(RawLink::from_element(element)?);
+        }
+        Ok(())
+    })?;
+    Ok(links)
+}
+
+/// Tell URLs from file paths by their scheme. A single letter before the
+/// colon is a Windows drive, not a scheme.
+fn external_target(target: String) -> LinkTarget {
+    let scheme_len = target.find(':').filter(|&len| len > 1).filter(|&len| {
+        let scheme = &target[..len];
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    match scheme_len {
+        Some(_) if !target.starts_with("file:") => LinkTarget::Url(target),
+        _ => LinkTarget::File(target),
+    }
+}
+
+/// Why an internal link target doesn't resolve, or `None` when it does.
+/// Targets are `Sheet!A1`, `'Sheet name'!A1:B2`, a defined name, or a range
+/// on the link's own sheet.
+pub fn check_internal_target(
+    location: &str,
+    sheet_names: &[String],
+    defined_names: &[String],
+) -> Option<String> {
+    let location = location.trim_start_matches('#');
+    let has_name = |name: &str| defined_names.iter().any(|n| n.eq_ignore_ascii_case(name));
+
+    let Some((sheet, range)) = location.rsplit_once('!') else {
+        if is_range(location) || has_name(location) {
+            return None;
+        }
+        return Some(format!("no range or defined name '{}'", location));
+    };
+
+    let sheet = unquote_sheet_name(sheet);
+    // Excel matches sheet names without regard to case
+    if !sheet_names
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(&sheet))
+    {
+        return Some(format!("sheet '{}' does not exist", sheet));
+    }
+    if is_range(range) || has_name(range) {
+        return None;
+    }
+    Some(format!("'{}' is not a valid range", range))
+}
+
+fn is_range(range: &str) -> bool {
+    let mut cells = range.split(':');
+    let valid = |cell: Option<&str>| {
+        cell.and_then(parse_cell_address)
+            .is_some_and(|(row, col)| row < 1_048_576 && col < 16_384)
+    };
+    match (cells.next(), cells.next(), cells.next()) {
+        (first, None, None) => valid(first),
+        (first, Some(last), None) => valid(first) && valid(Some(last)),
+        _ => false,
+    }
+}
+
+/// Print every link as `Sheet!A1: display -> target (kind)`.
+pub fn print_links(links: &[Hyperlink]) {
+    if links.is_empty() {
+        println!("No hyperlinks found.");
+        return;
+    }
+    for link in links {
+        println!(
+            "  {}: {} -> {} ({})",
+            link.reference(),
+            link.display,
+            link.target,
+            link.target.kind()
+        );
+    }
+}
+
+/// Check every internal link and print the broken ones. Returns how many
+/// were broken.
+pub fn check_internal_links<RS: Read + Seek>(workbook: &Xlsx<RS>, links: &[Hyperlink]) -> usize {
+    let sheet_names = workbook.sheet_names();
+    let defined_names: Vec<String> = workbook
+        .defined_names()
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let mut checked = 0;
+    let mut broken = 0;
+    for link in links {
+        let LinkTarget::Internal(location) = &link.target else {
+            continue;
+        };
+        checked += 1;
+        if let Some(problem) = check_internal_target(location, &sheet_names, &defined_names) {
+            broken += 1;
+            println!(
+                "  Broken: {} -> {}: {}",
+                link.reference(),
+                location,
+                problem
+            );
+        }
+    }
+    println!("{} internal link(s) checked, {} broken", checked, broken);
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_external_target() {
+        assert_eq!(
+            external_target("https://example.com/a".to_string()),
+            LinkTarget::Url("https://example.com/a".to_string())
+        );
+        assert_eq!(
+            external_target("mailto:ops@example.com".to_string()),
+            LinkTarget::Url("mailto:ops@example.com".to_string())
+        );
+        assert_eq!(
+            external_target("C:\\Reports\\q1.xlsx".to_string()),
+            LinkTarget::File("C:\\Reports\\q1.xlsx".to_string())
+        );
+        assert_eq!(
+            external_target("../shared/rates.xlsx".to_string()),
+            LinkTarget::File("../shared/rates.xlsx".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_internal_target() {
+        let sheets = vec!["Summary".to_string(), "Q1 Data".to_string()];
+        let names = vec!["Rates".to_string()];
+        let check = |location: &str| check_internal_target(location, &sheets, &names);
+
+        assert_eq!(check("Summary!A1"), None);
+        assert_eq!(check("#'Q1 Data'!B2:C10"), None);
+        assert_eq!(check("summary!$A$1"), None);
+        assert_eq!(check("Rates"), None);
+        assert_eq!(check("D4"), None);
+        assert_eq!(
+            check("Missing!A1"),
+            Some("sheet 'Missing' does not exist".to_string())
+        );
+        assert_eq!(
+            check("Summary!ZZZZ1"),
+            Some("'ZZZZ1' is not a valid range".to_string())
+        );
+        assert_eq!(
+            check("OldName"),
+            Some("no range or defined name 'OldName'".to_string())
+        );
+    }
+}
@@ -1,15 +1,39 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{bail, Context, Result};
+use calamine::{Reader, Xlsx};
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::io::{IsTerminal, Read, Seek};
 use std::path::PathBuf;
-use xlsq::{list_sheets, open_excel_file, search_in_sheet, show_sheet};
+use std::process::ExitCode;
+use xlsq::address::{parse_range_reference, RangeReference};
+use xlsq::calc::{Model, Value};
+use xlsq::cell::{inspect_cell, print_cell_details};
+use xlsq::comments::{print_comments, read_comments};
+use xlsq::deps::{DependencyGraph, Direction, Node};
+use xlsq::export::{export_all_sheets, export_sheet};
+use xlsq::info::{print_info, read_document_properties, workbook_info};
+use xlsq::links::{check_internal_links, print_links, read_links};
+use xlsq::package::Package;
+use xlsq::parallel::{collect_workbooks, search_workbooks, SheetSelection, WorkbookSource};
+use xlsq::validation::{check_sheet, print_validations, print_violations, read_validations};
+use xlsq::{
+    get_sheet_name, list_sheets, open_excel_bytes, open_excel_file, range_values, read_sheet,
+    show_sheet, Layout, MatchMode, SearchOptions, SearchOutput, Style,
+};
 
 #[derive(Parser)]
 #[command(name = "xlsq")]
 #[command(about = "A CLI tool for reading and searching Excel files")]
+#[command(after_help = EXIT_CODES_HELP)]
 struct Cli {
-    /// Path to the Excel file
+    /// Path to the Excel file ("-" to read it from stdin). Search accepts
+    /// the flag several times, and directories of workbooks
     #[arg(short, long)]
-    file: PathBuf,
+    file: Vec<PathBuf>,
+
+    /// When to color the output (auto: only on a terminal and without NO_COLOR)
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -17,8 +41,17 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// List all sheets in the workbook
-    Sheets,
+    /// List all sheets with their kind, visibility and dimensions
+    Sheets {
+        /// Leave out hidden and very hidden sheets
+        #[arg(long)]
+        visible_only: bool,
+        /// Print the list as a JSON array
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show document properties, sheet states and workbook statistics
+    Info,
     /// Display contents of a sheet
     Show {
         /// Sheet name or index (0-based)
@@ -27,38 +60,774 @@ enum Commands {
         /// Number of rows to display (0 for all)
         #[arg(short, long, default_value = "10")]
         rows: usize,
+        /// Mark cells that have a comment and list the comments below
+        #[arg(long)]
+        with_comments: bool,
+        /// Print each row as a block of `Header: value` lines
+        #[arg(long, conflicts_with = "transpose")]
+        vertical: bool,
+        /// Swap rows and columns, printing one line per column
+        #[arg(long)]
+        transpose: bool,
+    },
+    /// List the hyperlinks of the workbook with their cell, text and target
+    Links {
+        /// Only list the links of this sheet (name or index)
+        #[arg(short, long)]
+        sheet: Option<String>,
+        /// Check that internal links point to existing sheets, ranges or names
+        #[arg(long)]
+        check_internal: bool,
+    },
+    /// List data-validation rules, or check cell values against them
+    Validation {
+        /// Only this sheet (name or index) instead of every sheet
+        #[arg(short, long)]
+        sheet: Option<String>,
+        /// Report cells whose current value breaks a rule
+        #[arg(long)]
+        validate: bool,
+    },
+    /// Show everything about one cell: value, type, formula, style, comment and link
+    Cell {
+        /// Cell reference such as `Employees!D3` (first sheet when no sheet is given)
+        reference: String,
+    },
+    /// Print the raw value of a cell, or of each cell in a range row by row, one per line
+    Get {
+        /// Cell or range such as `Sheet1!B2` or `Sheet1!B2:B10` (first sheet when no sheet is given)
+        reference: String,
+    },
+    /// Recalculate formulas, optionally with changed inputs, and print the results
+    Calc {
+        /// Change an input cell before calculating, e.g. `Inputs!B2=0.05` (repeatable)
+        #[arg(long = "set", value_name = "CELL=VALUE")]
+        set: Vec<String>,
+        /// Cell or range to calculate and print, e.g. `Summary!C9` (repeatable)
+        #[arg(long = "get", value_name = "CELL", required = true)]
+        get: Vec<String>,
+    },
+    /// Show what a formula is calculated from, or what is calculated from a cell
+    Deps {
+        /// Cell, range or defined name to start from, e.g. `Summary!C9` (whole workbook when left out)
+        reference: Option<String>,
+        /// Follow the cells, ranges and names it is calculated from (the default)
+        #[arg(long, conflicts_with = "dependents")]
+        precedents: bool,
+        /// Follow the formulas calculated from it
+        #[arg(long)]
+        dependents: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DepsFormat::Tree)]
+        format: DepsFormat,
+    },
+    /// List the comments (notes) of a sheet with their cell and author
+    Comments {
+        /// Sheet name or index (0-based)
+        #[arg(short, long, default_value = "0")]
+        sheet: String,
     },
     /// Search for a value and display matching rows
-    Search {
-        /// Value to search for
-        value: String,
-        /// Sheet name or index to search in (default: first sheet)
+    Search(SearchArgs),
+    /// Export a sheet (or every sheet) to CSV, TSV or JSON files
+    Export {
+        /// Sheet name or index to export (default: first sheet)
         #[arg(short, long, default_value = "0")]
         sheet: String,
+        /// Export every sheet into its own file inside the output directory
+        #[arg(long)]
+        all_sheets: bool,
+        /// Output file, or output directory with --all-sheets (format chosen by extension)
+        #[arg(short, long)]
+        output: PathBuf,
+        /// File name template for --all-sheets, using {file} and {sheet} placeholders
+        #[arg(long, default_value = "{file}_{sheet}.csv")]
+        name_template: String,
+        /// Overwrite existing output files
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write every sheet into its own table of a new SQLite database
+    #[cfg(feature = "sqlite")]
+    ToSqlite {
+        /// Workbook and SQLite database to create (`book.xlsx out.db`), or
+        /// only the database when the workbook is given with --file
+        #[arg(value_name = "PATH", num_args = 1..=2, required = true)]
+        paths: Vec<PathBuf>,
+        /// Overwrite the database if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Build or query a persistent search index of a directory of workbooks
+    #[cfg(feature = "sqlite")]
+    Index {
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(Subcommand)]
+enum IndexCommand {
+    /// Index every .xlsx file below a directory (only changed files are re-read)
+    Build {
+        /// Directory of workbooks to index
+        dir: PathBuf,
+        /// Path of the index file
+        #[arg(long, default_value = xlsq::index::DEFAULT_INDEX_PATH)]
+        index: PathBuf,
+    },
+    /// Find cells containing a term, refreshing stale files first
+    Search {
+        /// Value to search for
+        term: String,
+        /// Path of the index file
+        #[arg(long, default_value = xlsq::index::DEFAULT_INDEX_PATH)]
+        index: PathBuf,
         /// Case-sensitive search
         #[arg(short, long)]
         case_sensitive: bool,
+        /// Answer from the index as it is, without checking for changed files
+        #[arg(long)]
+        no_refresh: bool,
     },
 }
 
-fn main() -> Result<()> {
+#[derive(Args)]
+struct SearchArgs {
+    /// Value to search for
+    #[arg(required_unless_present_any = ["between", "date_between"])]
+    value: Option<String>,
+    /// Sheet name or index to search in (default: first sheet)
+    #[arg(short, long, default_value = "0")]
+    sheet: String,
+    /// Search every sheet of each workbook
+    #[arg(long, conflicts_with = "sheet")]
+    all_sheets: bool,
+    /// Case-sensitive search
+    #[arg(short, long)]
+    case_sensitive: bool,
+    /// Ignore accents and other diacritics ("munchen" finds "München")
+    #[arg(long)]
+    ignore_accents: bool,
+    /// Fuzzy search that tolerates typos, listing the closest rows first
+    #[arg(long)]
+    fuzzy: bool,
+    /// Similarity measure used by --fuzzy
+    #[arg(long, value_enum, default_value_t = FuzzyAlgorithm::Levenshtein)]
+    algorithm: FuzzyAlgorithm,
+    /// Maximum number of edits allowed by --fuzzy (Levenshtein)
+    #[arg(long, default_value_t = 2)]
+    max_distance: usize,
+    /// Minimum similarity between 0 and 1 required by --fuzzy (Jaro-Winkler)
+    #[arg(long, default_value_t = 0.85, value_parser = parse_similarity)]
+    min_similarity: f64,
+    /// Match numeric cells between MIN and MAX (inclusive) instead of text
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["MIN", "MAX"],
+        allow_negative_numbers = true,
+        conflicts_with_all = ["value", "fuzzy", "date_between"]
+    )]
+    between: Option<Vec<f64>>,
+    /// Match date cells between START and END (inclusive, YYYY-MM-DD)
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["START", "END"],
+        conflicts_with_all = ["value", "fuzzy"]
+    )]
+    date_between: Option<Vec<NaiveDate>>,
+    /// Print NUM rows of context after each matching row
+    #[arg(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
+    /// Print NUM rows of context before each matching row
+    #[arg(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
+    /// Print NUM rows of context before and after each matching row
+    #[arg(short = 'C', long, value_name = "NUM")]
+    context: Option<usize>,
+    /// Only print the number of matching rows
+    #[arg(long)]
+    count: bool,
+    /// Only print the address and value of each matching cell
+    #[arg(short = 'o', long, conflicts_with = "count")]
+    only_matching: bool,
+    /// Only print the name of the file if it has a matching row
+    #[arg(short = 'l', long, conflicts_with = "count")]
+    files_with_matches: bool,
+    /// Select the rows that do not match
+    #[arg(short = 'v', long)]
+    invert_match: bool,
+    /// Stop after NUM matching rows
+    #[arg(short = 'm', long, value_name = "NUM")]
+    max_count: Option<usize>,
+}
+
+fn parse_similarity(value: &str) -> Result<f64, String> {
+    let similarity: f64 = value
+        .parse()
+        .map_err(|_| format!("{} is not a number", value))?;
+    if !(0.0..=1.0).contains(&similarity) {
+        return Err(format!("{} is not between 0 and 1", value));
+    }
+    Ok(similarity)
+}
+
+impl SearchArgs {
+    fn options(&self, style: Style) -> Result<SearchOptions> {
+        let mode = if let Some(bounds) = &self.between {
+            let (min, max) = (bounds[0], bounds[1]);
+            if min > max {
+                bail!("--between expects MIN <= MAX, got {} and {}", min, max);
+            }
+            MatchMode::NumberRange { min, max }
+        } else if let Some(bounds) = &self.date_between {
+            let (start, end) = (bounds[0], bounds[1]);
+            if start > end {
+                bail!(
+                    "--date-between expects START <= END, got {} and {}",
+                    start,
+                    end
+                );
+            }
+            MatchMode::DateRange { start, end }
+        } else {
+            match (self.fuzzy, self.algorithm) {
+                (false, _) => MatchMode::Substring,
+                (true, FuzzyAlgorithm::Levenshtein) => MatchMode::Levenshtein {
+                    max_distance: self.max_distance,
+                },
+                (true, FuzzyAlgorithm::JaroWinkler) => MatchMode::JaroWinkler {
+                    min_similarity: self.min_similarity,
+                },
+            }
+        };
+        let output = if self.files_with_matches {
+            SearchOutput::FilesWithMatches
+        } else if self.count {
+            SearchOutput::Count
+        } else if self.only_matching {
+            SearchOutput::OnlyMatching
+        } else {
+            SearchOutput::Rows
+        };
+
+        Ok(SearchOptions {
+            case_sensitive: self.case_sensitive,
+            ignore_accents: self.ignore_accents,
+            mode,
+            invert: self.invert_match,
+            max_count: self.max_count,
+            // -A and -B take precedence over -C, as in grep
+            before_context: self.before_context.or(self.context).unwrap_or(0),
+            after_context: self.after_context.or(self.context).unwrap_or(0),
+            output,
+            style,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum FuzzyAlgorithm {
+    Levenshtein,
+    JaroWinkler,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum DepsFormat {
+    /// Indented tree
+    Tree,
+    /// Graphviz DOT
+    Dot,
+    /// JSON nodes and edges
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve `auto` against the terminal and the NO_COLOR convention
+    /// (https://no-color.org); an explicit `always` wins over NO_COLOR.
+    fn style(self) -> Style {
+        let color = match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        };
+        Style::new(color)
+    }
+}
+
+/// Exit codes listed at the end of `--help`, following grep.
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Success (for search: at least one match)
+  1  search found no matches, or a check found problems
+  2  Error (the message on stderr says which)";
+
+/// Exit code for a search that ran fine but matched nothing (like grep).
+const EXIT_NO_MATCH: u8 = 1;
+/// Exit code for a check that ran fine but found problems.
+const EXIT_CHECK_FAILED: u8 = 1;
+/// Exit code for every error.
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    let mut workbook = open_excel_file(&cli.file)?;
+    match run(cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode> {
+    let style = cli.color.style();
 
-    match cli.command {
-        Some(Commands::Sheets) => list_sheets(&workbook),
-        Some(Commands::Show { sheet, rows }) => show_sheet(&mut workbook, &sheet, rows),
-        Some(Commands::Search {
-            value,
-            sheet,
+    let command = match cli.command {
+        Some(Commands::Search(args)) => return run_search(&cli.file, &args, style),
+        #[cfg(feature = "sqlite")]
+        Some(Commands::Index { command }) => return run_index(command),
+        command => command,
+    };
+    let files = match &command {
+        #[cfg(feature = "sqlite")]
+        Some(Commands::ToSqlite { paths, .. }) if paths.len() == 2 => {
+            if !cli.file.is_empty() {
+                bail!("Give the workbook either with --file or before the database, not both");
+            }
+            vec![paths[0].clone()]
+        }
+        _ => cli.file,
+    };
+    let file = match files.as_slice() {
+        [file] => file,
+        [] => bail!("--file is required"),
+        _ => bail!("Only search accepts more than one --file"),
+    };
+
+    let source = if file.as_os_str() == "-" {
+        WorkbookSource::Memory {
+            name: "(standard input)".to_string(),
+            bytes: read_stdin()?,
+        }
+    } else {
+        WorkbookSource::File(file.clone())
+    };
+    match &source {
+        WorkbookSource::File(path) => {
+            let file_stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "workbook".to_string());
+            let mut workbook = open_excel_file(path)?;
+            run_command(&mut workbook, &source, command, &file_stem, style)
+        }
+        WorkbookSource::Memory { bytes, .. } => {
+            let mut workbook = open_excel_bytes(bytes)?;
+            run_command(&mut workbook, &source, command, "stdin", style)
+        }
+    }
+}
+
+fn read_stdin() -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut buffer)
+        .context("Failed to read workbook from stdin")?;
+    Ok(buffer)
+}
+
+/// Search every given workbook (directories are searched for `.xlsx` files)
+/// in parallel. Any failure makes the search exit with an error, after
+/// every other failure has been printed.
+fn run_search(files: &[PathBuf], args: &SearchArgs, style: Style) -> Result<ExitCode> {
+    if files.is_empty() {
+        bail!("--file is required");
+    }
+    let options = args.options(style)?;
+    let sheets = if args.all_sheets {
+        SheetSelection::All
+    } else {
+        SheetSelection::One(args.sheet.clone())
+    };
+
+    let mut sources = Vec::new();
+    for path in collect_workbooks(files)? {
+        if path.as_os_str() == "-" {
+            sources.push(WorkbookSource::Memory {
+                name: "(standard input)".to_string(),
+                bytes: read_stdin()?,
+            });
+        } else {
+            sources.push(WorkbookSource::File(path));
+        }
+    }
+
+    let value = args.value.clone().unwrap_or_default();
+    let mut report = search_workbooks(&sources, &sheets, &value, &options)?;
+    if !report.errors.is_empty() {
+        let first = report.errors.remove(0);
+        for err in report.errors {
+            eprintln!("Error: {:?}", err);
+        }
+        return Err(first);
+    }
+    if report.matches == 0 {
+        return Ok(ExitCode::from(EXIT_NO_MATCH));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the files an index refresh could not read; they are retried next time.
+#[cfg(feature = "sqlite")]
+fn report_refresh_failures(summary: &xlsq::index::RefreshSummary) {
+    for (path, err) in &summary.failed {
+        eprintln!("Warning: skipped {:?}: {:#}", path, err);
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn run_index(command: IndexCommand) -> Result<ExitCode> {
+    use xlsq::index::SearchIndex;
+
+    match command {
+        IndexCommand::Build { dir, index } => {
+            let mut search_index = SearchIndex::create(&index, &dir)?;
+            let summary = search_index.refresh()?;
+            report_refresh_failures(&summary);
+            println!(
+                "Indexed {} file(s) into {:?} ({} unchanged, {} removed, {} skipped)",
+                summary.indexed,
+                index,
+                summary.unchanged,
+                summary.removed,
+                summary.failed.len()
+            );
+        }
+        IndexCommand::Search {
+            term,
+            index,
             case_sensitive,
-        }) => search_in_sheet(&mut workbook, &sheet, &value, case_sensitive),
+            no_refresh,
+        } => {
+            let mut search_index = SearchIndex::open(&index)?;
+            if !no_refresh {
+                report_refresh_failures(&search_index.refresh()?);
+            }
+            let hits = search_index.search(&term, case_sensitive)?;
+            for hit in &hits {
+                println!("{}:{}: {}", hit.path.display(), hit.reference, hit.value);
+            }
+            if hits.is_empty() {
+                return Ok(ExitCode::from(EXIT_NO_MATCH));
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Escape backslashes, tabs and line breaks so a value printed by `get` or
+/// `calc` stays on one line.
+fn escape_line(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Split a reference such as `Sheet1!B2:B10` into the sheet it names (the
+/// first sheet when it names none) and its range.
+fn resolve_reference<RS: Read + Seek>(
+    workbook: &Xlsx<RS>,
+    reference: &str,
+) -> Result<(String, RangeReference)> {
+    let range = parse_range_reference(reference)
+        .with_context(|| format!("Invalid cell reference {:?}", reference))?;
+    let sheet_name = match &range.sheet {
+        Some(sheet) => get_sheet_name(workbook, sheet)?,
+        None => get_sheet_name(workbook, "0")?,
+    };
+    Ok((sheet_name, range))
+}
+
+/// The graph node a `deps` argument stands for: a defined name, a cell or a range.
+fn deps_node<RS: Read + Seek>(
+    workbook: &Xlsx<RS>,
+    graph: &DependencyGraph,
+    reference: &str,
+) -> Result<Node> {
+    if let Some(node) = graph.defined_name(reference) {
+        return Ok(node);
+    }
+    let (sheet, range) = resolve_reference(workbook, reference)?;
+    Ok(if range.start == range.end {
+        Node::Cell {
+            sheet,
+            row: range.start.0,
+            col: range.start.1,
+        }
+    } else {
+        Node::Range {
+            sheet,
+            start: range.start,
+            end: range.end,
+        }
+    })
+}
+
+/// Check every sheet's values against its validation rules, print the
+/// violations and return how many there were.
+fn run_validate<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    package: &mut Package,
+    sheet_names: &[String],
+) -> Result<usize> {
+    let (mut checked, mut skipped, mut violations) = (0, 0, 0);
+    for sheet_name in sheet_names {
+        let rules = read_validations(package, sheet_name)?;
+        let check = check_sheet(workbook, sheet_name, &rules)?;
+        print_violations(&check);
+        checked += check.checked;
+        skipped += check.skipped_rules;
+        violations += check.violations.len();
+    }
+    println!("{} cell(s) checked, {} violation(s)", checked, violations);
+    if skipped > 0 {
+        println!(
+            "{} rule(s) with custom formulas or formula bounds were not checked",
+            skipped
+        );
+    }
+    Ok(violations)
+}
+
+fn run_command<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    source: &WorkbookSource,
+    command: Option<Commands>,
+    file_stem: &str,
+    style: Style,
+) -> Result<ExitCode> {
+    match command {
+        Some(Commands::Sheets { visible_only, json }) => list_sheets(workbook, visible_only, json)?,
+        Some(Commands::Info) => {
+            let mut package = source.package()?;
+            let properties = read_document_properties(&mut package)?;
+            print_info(&workbook_info(workbook, &mut package, properties)?)
+        }
+        Some(Commands::Show {
+            sheet,
+            rows,
+            with_comments,
+            vertical,
+            transpose,
+        }) => {
+            let comments = if with_comments {
+                let sheet_name = get_sheet_name(workbook, &sheet)?;
+                read_comments(&mut source.package()?, &sheet_name)?
+            } else {
+                Vec::new()
+            };
+            let layout = if vertical {
+                Layout::Vertical
+            } else if transpose {
+                Layout::Transposed
+            } else {
+                Layout::Rows
+            };
+            show_sheet(workbook, &sheet, rows, &comments, layout, style)?
+        }
+        Some(Commands::Links {
+            sheet,
+            check_internal,
+        }) => {
+            let sheet_names = match sheet {
+                Some(sheet) => vec![get_sheet_name(workbook, &sheet)?],
+                None => workbook.sheet_names(),
+            };
+            let links = read_links(workbook, &mut source.package()?, &sheet_names)?;
+            if check_internal {
+                if check_internal_links(workbook, &links) > 0 {
+                    return Ok(ExitCode::from(EXIT_CHECK_FAILED));
+                }
+            } else {
+                print_links(&links);
+            }
+        }
+        Some(Commands::Validation { sheet, validate }) => {
+            let sheet_names = match sheet {
+                Some(sheet) => vec![get_sheet_name(workbook, &sheet)?],
+                None => workbook.sheet_names(),
+            };
+            let mut package = source.package()?;
+            if validate {
+                if run_validate(workbook, &mut package, &sheet_names)? > 0 {
+                    return Ok(ExitCode::from(EXIT_CHECK_FAILED));
+                }
+            } else {
+                for (idx, sheet_name) in sheet_names.iter().enumerate() {
+                    if idx > 0 {
+                        println!();
+                    }
+                    print_validations(sheet_name, &read_validations(&mut package, sheet_name)?);
+                }
+            }
+        }
+        Some(Commands::Cell { reference }) => {
+            let (sheet_name, range) = resolve_reference(workbook, &reference)?;
+            if range.start != range.end {
+                bail!(
+                    "Expected a single cell such as Sheet1!B2, got {:?}",
+                    reference
+                );
+            }
+            let (row, col) = range.start;
+            let details = inspect_cell(&mut source.package()?, &sheet_name, row, col)?;
+            print_cell_details(&details)
+        }
+        Some(Commands::Get { reference }) => {
+            let (sheet_name, range) = resolve_reference(workbook, &reference)?;
+            let values = read_sheet(workbook, &sheet_name)?;
+            for value in range_values(&values, range.start, range.end) {
+                println!("{}", escape_line(&value));
+            }
+        }
+        Some(Commands::Calc { set, get }) => {
+            let mut inputs = Vec::new();
+            for assignment in &set {
+                let (reference, value) = assignment
+                    .split_once('=')
+                    .with_context(|| format!("Expected CELL=VALUE, got {:?}", assignment))?;
+                let (sheet_name, range) = resolve_reference(workbook, reference)?;
+                if range.start != range.end {
+                    bail!("--set takes a single cell, got {:?}", reference);
+                }
+                inputs.push((sheet_name, range.start, Value::from_input(value)));
+            }
+            let targets = get
+                .iter()
+                .map(|reference| resolve_reference(workbook, reference))
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut model = Model::new(workbook);
+            for (sheet_name, (row, col), value) in inputs {
+                model.set_value(&sheet_name, row, col, value);
+            }
+            for (sheet_name, range) in targets {
+                for row in model.evaluate_range(&sheet_name, range.start, range.end)? {
+                    for value in row {
+                        println!("{}", escape_line(&value.to_string()));
+                    }
+                }
+            }
+        }
+        Some(Commands::Deps {
+            reference,
+            dependents,
+            format,
+            ..
+        }) => {
+            let graph = DependencyGraph::build(workbook)?;
+            if !graph.unparsed().is_empty() {
+                eprintln!(
+                    "Warning: skipped {} formula(s) that could not be parsed",
+                    graph.unparsed().len()
+                );
+            }
+            let direction = if dependents {
+                Direction::Dependents
+            } else {
+                Direction::Precedents
+            };
+            let start = match &reference {
+                Some(reference) => Some(deps_node(workbook, &graph, reference)?),
+                None => None,
+            };
+            match format {
+                DepsFormat::Tree => {
+                    let roots = match start {
+                        Some(node) => vec![node],
+                        None => graph.roots(direction),
+                    };
+                    if roots.is_empty() {
+                        println!("No formulas found.");
+                    }
+                    for (idx, root) in roots.iter().enumerate() {
+                        if idx > 0 {
+                            println!();
+                        }
+                        print!("{}", graph.tree(root, direction));
+                    }
+                }
+                DepsFormat::Dot | DepsFormat::Json => {
+                    let nodes = match &start {
+                        Some(node) => graph.reachable(node, direction),
+                        None => graph.nodes(),
+                    };
+                    if format == DepsFormat::Dot {
+                        print!("{}", graph.to_dot(&nodes));
+                    } else {
+                        println!("{}", serde_json::to_string_pretty(&graph.to_json(&nodes))?);
+                    }
+                }
+            }
+        }
+        Some(Commands::Comments { sheet }) => {
+            let sheet_name = get_sheet_name(workbook, &sheet)?;
+            let comments = read_comments(&mut source.package()?, &sheet_name)?;
+            print_comments(&sheet_name, &comments)
+        }
+        Some(Commands::Export {
+            sheet,
+            all_sheets,
+            output,
+            name_template,
+            force,
+        }) => {
+            if all_sheets {
+                export_all_sheets(workbook, file_stem, &output, &name_template, force)?
+            } else {
+                export_sheet(workbook, &sheet, &output, force)?
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        Some(Commands::ToSqlite { paths, force }) => {
+            // The database is always last, after the workbook if it was given
+            let output = paths.last().expect("clap requires at least one path");
+            xlsq::sqlite::workbook_to_sqlite(workbook, output, force)?
+        }
+        Some(Commands::Search(_)) => unreachable!("search is handled by run_search"),
+        #[cfg(feature = "sqlite")]
+        Some(Commands::Index { .. }) => unreachable!("index is handled by run_index"),
         None => {
             // Default behavior: show first 10 rows of first sheet
-            show_sheet(&mut workbook, "0", 10)
+            show_sheet(workbook, "0", 10, &[], Layout::Rows, style)?
         }
     }
+
+    Ok(ExitCode::SUCCESS)
 }
 
 #[cfg(test)]
@@ -67,7 +836,21 @@ mod tests {
     use clap::Parser;
 
     #[test]
-    fn test_cli_parsing() {
+    fn test_help_lists_exit_codes() {
+        let help = <Cli as clap::CommandFactory>::command()
+            .render_help()
+            .to_string();
+        assert!(help.contains("2  Error"));
+    }
+
+    #[test]
+    fn test_escape_line() {
+        assert_eq!(escape_line("db.internal"), "db.internal");
+        assert_eq!(escape_line("a\tb\r\nc\\d"), "a\\tb\\r\\nc\\\\d");
+    }
+
+    #[test]
+    fn test_sheets_parsing() {
         // Test basic file argument
         let args = vec!["xlsq", "-f", "test.xlsx"];
         let cli = Cli::try_parse_from(args);
@@ -78,38 +861,324 @@ mod tests {
         let cli = Cli::try_parse_from(args);
         assert!(cli.is_ok());
         if let Ok(parsed) = cli {
-            assert!(matches!(parsed.command, Some(Commands::Sheets)));
+            assert!(matches!(parsed.command, Some(Commands::Sheets { .. })));
         }
 
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "sheets",
+            "--visible-only",
+            "--json",
+        ];
+        let parsed = Cli::try_parse_from(args).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Commands::Sheets {
+                visible_only: true,
+                json: true
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validation_parsing() {
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "validation",
+            "-s",
+            "Intake",
+            "--validate",
+        ];
+        let parsed = Cli::try_parse_from(args).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Commands::Validation {
+                sheet: Some(_),
+                validate: true
+            })
+        ));
+    }
+
+    #[test]
+    fn test_calc_parsing() {
+        let args = vec![
+            "xlsq",
+            "-f",
+            "model.xlsx",
+            "calc",
+            "--set",
+            "Inputs!B2=0.05",
+            "--set",
+            "Inputs!B3=120",
+            "--get",
+            "Summary!C9",
+        ];
+        let parsed = Cli::try_parse_from(args).unwrap();
+        if let Some(Commands::Calc { set, get }) = parsed.command {
+            assert_eq!(set, vec!["Inputs!B2=0.05", "Inputs!B3=120"]);
+            assert_eq!(get, vec!["Summary!C9"]);
+        } else {
+            panic!("Expected Calc command");
+        }
+        // --get is required
+        let args = vec!["xlsq", "-f", "model.xlsx", "calc", "--set", "A1=1"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_deps_parsing() {
+        let args = vec![
+            "xlsq",
+            "-f",
+            "model.xlsx",
+            "deps",
+            "Summary!C9",
+            "--dependents",
+            "--format",
+            "dot",
+        ];
+        let parsed = Cli::try_parse_from(args).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Commands::Deps {
+                reference: Some(_),
+                precedents: false,
+                dependents: true,
+                format: DepsFormat::Dot,
+            })
+        ));
+        let args = vec![
+            "xlsq",
+            "-f",
+            "model.xlsx",
+            "deps",
+            "Summary!C9",
+            "--precedents",
+            "--dependents",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_show_parsing() {
         // Test show command with options
         let args = vec!["xlsq", "-f", "test.xlsx", "show", "-s", "1", "-r", "5"];
         let cli = Cli::try_parse_from(args);
         assert!(cli.is_ok());
         if let Ok(parsed) = cli {
-            if let Some(Commands::Show { sheet, rows }) = parsed.command {
+            if let Some(Commands::Show { sheet, rows, .. }) = parsed.command {
                 assert_eq!(sheet, "1");
                 assert_eq!(rows, 5);
             } else {
                 panic!("Expected Show command");
             }
         }
+    }
 
+    #[test]
+    fn test_search_parsing() {
         // Test search command
         let args = vec!["xlsq", "-f", "test.xlsx", "search", "test", "-c"];
         let cli = Cli::try_parse_from(args);
         assert!(cli.is_ok());
         if let Ok(parsed) = cli {
-            if let Some(Commands::Search {
+            if let Some(Commands::Search(SearchArgs {
                 value,
                 case_sensitive,
                 ..
-            }) = parsed.command
+            })) = parsed.command
             {
-                assert_eq!(value, "test");
+                assert_eq!(value.as_deref(), Some("test"));
                 assert!(case_sensitive);
             } else {
                 panic!("Expected Search command");
             }
         }
+
+        // Test fuzzy search options
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "search",
+            "Jon Doe",
+            "--fuzzy",
+            "--algorithm",
+            "jaro-winkler",
+            "--min-similarity",
+            "0.9",
+        ];
+        let cli = Cli::try_parse_from(args);
+        assert!(cli.is_ok());
+        if let Ok(parsed) = cli {
+            if let Some(Commands::Search(SearchArgs {
+                fuzzy,
+                algorithm,
+                min_similarity,
+                max_distance,
+                ..
+            })) = parsed.command
+            {
+                assert!(fuzzy);
+                assert_eq!(algorithm, FuzzyAlgorithm::JaroWinkler);
+                assert_eq!(min_similarity, 0.9);
+                assert_eq!(max_distance, 2);
+            } else {
+                panic!("Expected Search command");
+            }
+        }
+
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "search",
+            "Jon",
+            "--fuzzy",
+            "--min-similarity",
+            "85",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+
+        // Test range searches
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "search",
+            "--between",
+            "-10",
+            "5000",
+        ];
+        let cli = Cli::try_parse_from(args);
+        assert!(cli.is_ok());
+        if let Ok(parsed) = cli {
+            if let Some(Commands::Search(SearchArgs { value, between, .. })) = parsed.command {
+                assert_eq!(value, None);
+                assert_eq!(between, Some(vec![-10.0, 5000.0]));
+            } else {
+                panic!("Expected Search command");
+            }
+        }
+
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "search",
+            "--date-between",
+            "2024-01-01",
+            "2024-03-31",
+        ];
+        let cli = Cli::try_parse_from(args);
+        assert!(cli.is_ok());
+
+        // Test grep-style search controls
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "search",
+            "Engineering",
+            "-v",
+            "-C",
+            "2",
+            "-A",
+            "1",
+            "-m",
+            "3",
+        ];
+        let cli = Cli::try_parse_from(args);
+        assert!(cli.is_ok());
+        if let Ok(parsed) = cli {
+            if let Some(Commands::Search(SearchArgs {
+                invert_match,
+                context,
+                after_context,
+                before_context,
+                max_count,
+                ..
+            })) = parsed.command
+            {
+                assert!(invert_match);
+                assert_eq!(context, Some(2));
+                assert_eq!(after_context, Some(1));
+                assert_eq!(before_context, None);
+                assert_eq!(max_count, Some(3));
+            } else {
+                panic!("Expected Search command");
+            }
+        }
+        let args = vec!["xlsq", "-f", "test.xlsx", "search", "x", "--count", "-l"];
+        assert!(Cli::try_parse_from(args).is_err());
+
+        // --color is accepted after the subcommand too
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "search",
+            "x",
+            "--color",
+            "always",
+        ];
+        let parsed = Cli::try_parse_from(args).expect("Failed to parse --color");
+        assert_eq!(parsed.color, ColorChoice::Always);
+        assert_eq!(ColorChoice::Never.style(), Style::new(false));
+
+        // A value or a range is required, but not both
+        let args = vec!["xlsq", "-f", "test.xlsx", "search"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "search",
+            "x",
+            "--between",
+            "1",
+            "2",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_export_parsing() {
+        // Test export command
+        let args = vec![
+            "xlsq",
+            "-f",
+            "test.xlsx",
+            "export",
+            "--all-sheets",
+            "-o",
+            "outdir",
+            "--name-template",
+            "{sheet}.json",
+            "--force",
+        ];
+        let cli = Cli::try_parse_from(args);
+        assert!(cli.is_ok());
+        if let Ok(parsed) = cli {
+            if let Some(Commands::Export {
+                all_sheets,
+                output,
+                name_template,
+                force,
+                ..
+            }) = parsed.command
+            {
+                assert!(all_sheets);
+                assert_eq!(output, PathBuf::from("outdir"));
+                assert_eq!(name_template, "{sheet}.json");
+                assert!(force);
+            } else {
+                panic!("Expected Export command");
+            }
+        }
     }
 }
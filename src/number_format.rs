@@ -0,0 +1,570 @@
+//! Render values through Excel number format codes such as `#,##0.00`,
+//! `0%` or `yyyy-mm-dd`, to show a cell the way Excel does.
+
+use crate::calc::format_number;
+use calamine::{Data, ExcelDateTimeType};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+/// One piece of a format section.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    /// `0`, `#` or `?`.
+    Digit(char),
+    Point,
+    Comma,
+    Percent,
+    /// `E+` or `E-`.
+    Exponent(char),
+    /// A run of `y`, `m`, `d`, `h` or `s`, lower case, e.g. "mmm".
+    Date(String),
+    /// `[h]`, `[mm]` or `[ss]`: time elapsed rather than time of day.
+    Elapsed(String),
+    AmPm(String),
+    /// `@`, the cell's text.
+    Text,
+    General,
+}
+
+/// A value formatted with a number format code, as Excel would show it.
+/// Codes this doesn't understand (fractions, for one) fall back to the
+/// General format.
+pub fn format_with_code(value: &Data, code: &str) -> String {
+    let sections = split_sections(code);
+    let number = match value {
+        Data::Empty => return String::new(),
+        Data::String(text) => {
+            // The fourth section formats text; a lone `@` section too
+            let section = sections
+                .get(3)
+                .or_else(|| sections.first().filter(|section| section.contains('@')));
+            return match section {
+                Some(section) => render_text(&tokenize(section), text),
+                None => text.clone(),
+            };
+        }
+        Data::Bool(true) => return "TRUE".to_string(),
+        Data::Bool(false) => return "FALSE".to_string(),
+        Data::Error(err) => return err.to_string(),
+        Data::DateTimeIso(text) | Data::DurationIso(text) => return text.clone(),
+        Data::Float(number) => *number,
+        Data::Int(number) => *number as f64,
+        Data::DateTime(datetime) => datetime.as_f64(),
+    };
+    let date_of = || match value {
+        Data::DateTime(datetime) if !datetime.is_duration() => datetime.as_datetime(),
+        _ => datetime_from_serial(number),
+    };
+
+    // Positive; negative; zero. A negative section shows the value without its sign
+    let (section, number, signed) = match sections.len() {
+        _ if number > 0.0 => (sections[0], number, true),
+        1 => (sections[0], number, true),
+        _ if number < 0.0 => (sections[1], -number, false),
+        2 => (sections[0], number, true),
+        _ => (sections[2], number, true),
+    };
+    let tokens = tokenize(section);
+    if tokens
+        .iter()
+        .any(|token| matches!(token, Token::Date(_) | Token::Elapsed(_) | Token::AmPm(_)))
+    {
+        return match date_of() {
+            Some(datetime) => render_date(&tokens, datetime, number),
+            None => format_number(number),
+        };
+    }
+    render_number(&tokens, number, signed)
+}
+
+/// Whether a number shown in this format is a date or time of day
+/// (`DateTime`) or elapsed time (`TimeDelta`), as calamine decides when it
+/// reads the cell; `None` for other formats. Only the first section counts.
+pub fn date_kind(code: &str) -> Option<ExcelDateTimeType> {
+    let tokens = tokenize(split_sections(code)[0]);
+    if tokens
+        .iter()
+        .any(|token| matches!(token, Token::Elapsed(_)))
+    {
+        Some(ExcelDateTimeType::TimeDelta)
+    } else if tokens
+        .iter()
+        .any(|token| matches!(token, Token::Date(_) | Token::AmPm(_)))
+    {
+        Some(ExcelDateTimeType::DateTime)
+    } else {
+        None
+    }
+}
+
+/// Split a code into its `;`-separated sections, leaving quoted text alone.
+fn split_sections(code: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (at, c) in code.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                sections.push(&code[start..at]);
+                start = at + 1;
+            }
+            _ => {}
+        }
+    }
+    sections.push(&code[start..]);
+    sections
+}
+
+fn tokenize(section: &str) -> Vec<Token> {
+    let chars: Vec<char> = section.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..].iter().collect::<String>().to_ascii_lowercase();
+        i += 1;
+        match c {
+            '"' => {
+                let text: String = chars[i..].iter().take_while(|&&c| c != '"').collect();
+                i += text.chars().count() + 1;
+                tokens.push(Token::Literal(text));
+            }
+            '\\' => {
+                if let Some(&next) = chars.get(i) {
+                    tokens.push(Token::Literal(next.to_string()));
+                    i += 1;
+                }
+            }
+            // Padding as wide as the next character, and repeated fill
+            '_' => {
+                tokens.push(Token::Literal(" ".to_string()));
+                i += 1;
+            }
+            '*' => i += 1,
+            '[' => {
+                let inner: String = chars[i..].iter().take_while(|&&c| c != ']').collect();
+                i += inner.chars().count() + 1;
+                let lower = inner.to_ascii_lowercase();
+                let elapsed = ["h", "m", "s"]
+                    .iter()
+                    .any(|unit| !lower.is_empty() && lower.trim_start_matches(unit).is_empty());
+                if elapsed {
+                    tokens.push(Token::Elapsed(lower));
+                } else if let Some(currency) = inner.strip_prefix('$') {
+                    // Currency and locale, e.g. [$€-407]
+                    let symbol = currency.split('-').next().unwrap_or_default();
+                    tokens.push(Token::Literal(symbol.to_string()));
+                }
+                // Colors and conditions don't change the text
+            }
+            '0' | '#' | '?' => tokens.push(Token::Digit(c)),
+            '.' => tokens.push(Token::Point),
+            ',' => tokens.push(Token::Comma),
+            '%' => tokens.push(Token::Percent),
+            '@' => tokens.push(Token::Text),
+            'E' | 'e' if matches!(chars.get(i), Some('+' | '-')) => {
+                tokens.push(Token::Exponent(chars[i]));
+                i += 1;
+            }
+            _ if rest.starts_with("am/pm") => {
+                tokens.push(Token::AmPm(chars[i - 1..i + 4].iter().collect()));
+                i += 4;
+            }
+            _ if rest.starts_with("a/p") => {
+                tokens.push(Token::AmPm(chars[i - 1..i + 2].iter().collect()));
+                i += 2;
+            }
+            _ if rest.starts_with("general") => {
+                tokens.push(Token::General);
+                i += 6;
+            }
+            'y' | 'Y' | 'm' | 'M' | 'd' | 'D' | 'h' | 'H' | 's' | 'S' => {
+                let lower = c.to_ascii_lowercase();
+                let mut run = lower.to_string();
+                while chars.get(i).map(char::to_ascii_lowercase) == Some(lower) {
+                    run.push(lower);
+                    i += 1;
+                }
+                tokens.push(Token::Date(run));
+            }
+            _ => tokens.push(Token::Literal(c.to_string())),
+        }
+    }
+    tokens
+}
+
+fn render_text(tokens: &[Token], text: &str) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Text | Token::General => text.to_string(),
+            Token::Literal(literal) => literal.clone(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+fn render_number(tokens: &[Token], number: f64, signed: bool) -> String {
+    if tokens.contains(&Token::General) || !tokens.iter().any(|t| matches!(t, Token::Digit(_))) {
+        // No placeholders: General, or only text such as "-" for zeros
+        let general = if signed {
+            format_number(number)
+        } else {
+            format_number(number.abs())
+        };
+        return tokens
+            .iter()
+            .map(|token| match token {
+                Token::Literal(literal) => literal.clone(),
+                Token::General | Token::Text => general.clone(),
+                Token::Percent => "%".to_string(),
+                _ => String::new(),
+            })
+            .collect();
+    }
+    // Fractions are beyond this renderer
+    if tokens.contains(&Token::Literal("/".to_string())) {
+        return format_number(number);
+    }
+
+    let exponent_at = tokens
+        .iter()
+        .position(|token| matches!(token, Token::Exponent(_)));
+    let mantissa = &tokens[..exponent_at.unwrap_or(tokens.len())];
+    let point_at = mantissa.iter().position(|token| *token == Token::Point);
+    let (integer_part, fraction_part) = match point_at {
+        Some(at) => (&mantissa[..at], &mantissa[at + 1..]),
+        None => (mantissa, &mantissa[..0]),
+    };
+    let digits = |part: &[Token], kinds: &[char]| {
+        part.iter()
+            .filter(|token| matches!(token, Token::Digit(c) if kinds.contains(c)))
+            .count()
+    };
+    let last_digit = mantissa
+        .iter()
+        .rposition(|token| matches!(token, Token::Digit(_)))
+        .unwrap_or(0);
+    let first_digit = mantissa
+        .iter()
+        .position(|token| matches!(token, Token::Digit(_)))
+        .unwrap_or(0);
+    // Commas right after the last placeholder divide by 1000; between
+    // placeholders they group thousands
+    let scaling = mantissa[last_digit + 1..]
+        .iter()
+        .take_while(|token| **token == Token::Comma)
+        .count();
+    let grouped = integer_part
+        .iter()
+        .enumerate()
+        .any(|(at, token)| *token == Token::Comma && at > first_digit && at < last_digit);
+
+    let mut value = number.abs() / 1000f64.powi(scaling as i32);
+    value *= 100f64.powi(tokens.iter().filter(|t| **t == Token::Percent).count() as i32);
+    let decimals = digits(fraction_part, &['0', '#', '?']);
+    let required_decimals = digits(fraction_part, &['0', '?']);
+    let mut exponent = 0;
+    if exponent_at.is_some() && value != 0.0 {
+        // Scientific: as many integer digits as placeholders before the point
+        let integer_digits = digits(integer_part, &['0', '#', '?']).max(1) as i32;
+        exponent = value.log10().floor() as i32 - (integer_digits - 1);
+        value /= 10f64.powi(exponent);
+    }
+
+    // Half away from zero, as Excel rounds
+    let factor = 10f64.powi(decimals as i32);
+    let rounded = format!("{:.*}", decimals, (value * factor).round() / factor);
+    let (integer, fraction) = rounded.split_once('.').unwrap_or((&rounded, ""));
+    let mut fraction = fraction.to_string();
+    while fraction.len() > required_decimals && fraction.ends_with('0') {
+        fraction.pop();
+    }
+    let mut integer = if integer == "0" {
+        String::new()
+    } else {
+        integer.to_string()
+    };
+    let min_integer = digits(integer_part, &['0']);
+    while integer.len() < min_integer {
+        integer.insert(0, '0');
+    }
+    if grouped {
+        integer = group_thousands(&integer);
+    }
+
+    let negative = signed
+        && number < 0.0
+        && (integer.trim_start_matches('0').len() + fraction.trim_end_matches('0').len()) > 0;
+    let mut out = String::new();
+    if negative {
+        out.push('-');
+    }
+    let mut integer_written = false;
+    let mut fraction_written = false;
+    for (at, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Literal(literal) => out.push_str(literal),
+            Token::Percent => out.push('%'),
+            Token::Point if Some(at) == point_at => out.push('.'),
+            // All the digits of a part go where its first placeholder is
+            Token::Digit(_) if exponent_at.is_none_or(|e| at < e) => {
+                if point_at.is_none_or(|point| at < point) {
+                    if !integer_written {
+                        out.push_str(&integer);
+                    }
+                    integer_written = true;
+                } else {
+                    if !fraction_written {
+                        out.push_str(&fraction);
+                    }
+                    fraction_written = true;
+                }
+            }
+            Token::Exponent(sign) => {
+                let width = digits(&tokens[at + 1..], &['0', '#', '?']);
+                let sign = match (exponent < 0, sign) {
+                    (true, _) => "-",
+                    (false, '+') => "+",
+                    _ => "",
+                };
+                out.push_str(&format!(
+                    "E{}{:0width$}",
+                    sign,
+                    exponent.unsigned_abs(),
+                    width = width
+                ));
+                break;
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+    for (at, c) in digits.chars().enumerate() {
+        if at > 0 && (digits.len() - at).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Date and time of a serial number in the 1900 date system.
+fn datetime_from_serial(serial: f64) -> Option<NaiveDateTime> {
+    if !(0.0..2_958_466.0).contains(&serial) {
+        return None;
+    }
+    // Excel counts a 29 February 1900 that never was
+    let base = if serial < 61.0 {
+        NaiveDate::from_ymd_opt(1899, 12, 31)?
+    } else {
+        NaiveDate::from_ymd_opt(1899, 12, 30)?
+    };
+    let milliseconds = (serial * 86_400_000.0).round() as i64;
+    base.and_hms_opt(0, 0, 0)?
+        .checked_add_signed(Duration::milliseconds(milliseconds))
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+fn render_date(tokens: &[Token], datetime: NaiveDateTime, serial: f64) -> String {
+    let twelve_hour = tokens.iter().any(|token| matches!(token, Token::AmPm(_)));
+    // Fractional seconds follow the seconds, as in `ss.00`
+    let fraction_digits = tokens
+        .windows(2)
+        .position(|pair| {
+            matches!(&pair[0], Token::Date(run) if run.starts_with('s')) && pair[1] == Token::Point
+        })
+        .map(|at| {
+            tokens[at + 2..]
+                .iter()
+                .take_while(|token| matches!(token, Token::Digit('0')))
+                .count()
+        })
+        .unwrap_or(0);
+    // Round to what is shown, so 10:59:59.7 shows as 11:00:00
+    let unit = 10f64.powi(fraction_digits as i32);
+    let millis = datetime.and_utc().timestamp_subsec_millis() as f64 / 1000.0;
+    let shown = (millis * unit).round() / unit;
+    let datetime = datetime.with_nanosecond(0).unwrap_or(datetime)
+        + Duration::milliseconds((shown * 1000.0) as i64);
+
+    let date_tokens: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Date(run) | Token::Elapsed(run) => Some(run.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut date_index: usize = 0;
+    let mut out = String::new();
+    let mut skip_fraction = false;
+    for token in tokens {
+        match token {
+            Token::Literal(literal) => out.push_str(literal),
+            Token::Date(run) => {
+                let previous = date_index.checked_sub(1).and_then(|at| date_tokens.get(at));
+                let next = date_tokens.get(date_index + 1);
+                date_index += 1;
+                let len = run.len();
+                let text = match run.as_bytes()[0] {
+                    b'y' if len <= 2 => format!("{:02}", datetime.year() % 100),
+                    b'y' => format!("{:04}", datetime.year()),
+                    // After hours or before seconds, m is minutes
+                    b'm' if len <= 2
+                        && (previous.is_some_and(|p| p.starts_with('h'))
+                            || next.is_some_and(|n| n.starts_with('s'))) =>
+                    {
+                        pad(datetime.minute(), len)
+                    }
+                    b'm' => {
+                        let name = MONTHS[datetime.month0() as usize];
+                        match len {
+                            1 | 2 => pad(datetime.month(), len),
+                            3 => name[..3].to_string(),
+                            5 => name[..1].to_string(),
+                            _ => name.to_string(),
+                        }
+                    }
+                    b'd' => match len {
+                        1 | 2 => pad(datetime.day(), len),
+                        3 => datetime.format("%a").to_string(),
+                        _ => datetime.format("%A").to_string(),
+                    },
+                    b'h' if twelve_hour => pad((datetime.hour() + 11) % 12 + 1, len),
+                    b'h' => pad(datetime.hour(), len),
+                    _ => {
+                        skip_fraction = fraction_digits > 0;
+                        pad(datetime.second(), len)
+                    }
+                };
+                out.push_str(&text);
+            }
+            Token::Point if skip_fraction => {
+                let fraction = format!("{:.*}", fraction_digits, shown % 1.0);
+                out.push_str(fraction.trim_start_matches('0'));
+            }
+            Token::Digit(_) if skip_fraction => {}
+            Token::Point => out.push('.'),
+            Token::Comma => out.push(','),
+            Token::Percent => out.push('%'),
+            Token::Digit(digit) => out.push(*digit),
+            Token::Elapsed(run) => {
+                date_index += 1;
+                let units = match run.as_bytes()[0] {
+                    b'h' => 24.0,
+                    b'm' => 1440.0,
+                    _ => 86_400.0,
+                };
+                out.push_str(&pad((serial * units + 1e-9).floor() as u32, run.len()));
+            }
+            Token::AmPm(marker) => {
+                let pm = datetime.hour() >= 12;
+                let text = match (marker.len(), pm) {
+                    (5, false) => "AM",
+                    (5, true) => "PM",
+                    (_, false) => "A",
+                    (_, true) => "P",
+                };
+                out.push_str(text);
+            }
+            _ => {}
+        }
+        if !matches!(token, Token::Point | Token::Digit(_) | Token::Date(_)) {
+            skip_fraction = false;
+        }
+    }
+    out
+}
+
+fn pad(value: u32, width: usize) -> String {
+    format!("{:0width$}", value, width = width.min(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_codes() {
+        let float = Data::Float;
+        assert_eq!(format_with_code(&float(1234.5), "General"), "1234.5");
+        assert_eq!(format_with_code(&float(1234.5), "0"), "1235");
+        assert_eq!(format_with_code(&float(1234.5), "0.00"), "1234.50");
+        assert_eq!(
+            format_with_code(&float(1234567.891), "#,##0.00"),
+            "1,234,567.89"
+        );
+        assert_eq!(format_with_code(&float(-1234.5), "#,##0"), "-1,235");
+        assert_eq!(format_with_code(&float(0.125), "0.0%"), "12.5%");
+        assert_eq!(format_with_code(&float(0.5), "#.00"), ".50");
+        assert_eq!(format_with_code(&float(12345.0), "0.00E+00"), "1.23E+04");
+        assert_eq!(
+            format_with_code(&float(1500000.0), "#,##0.0,,\"M\""),
+            "1.5M"
+        );
+        assert_eq!(
+            format_with_code(&float(-42.0), "#,##0.00;[Red](#,##0.00)"),
+            "(42.00)"
+        );
+        assert_eq!(format_with_code(&float(0.0), "0;-0;\"zero\""), "zero");
+        assert_eq!(
+            format_with_code(&float(12.0), "[$€-407] #,##0.00"),
+            "€ 12.00"
+        );
+        assert_eq!(
+            format_with_code(&Data::String("Ada".to_string()), "\"Name: \"@"),
+            "Name: Ada"
+        );
+    }
+
+    #[test]
+    fn test_date_codes() {
+        // 31 March 2024, 14:05:09
+        let serial = 45382.0 + (14.0 * 3600.0 + 5.0 * 60.0 + 9.0) / 86_400.0;
+        let value = Data::Float(serial);
+        assert_eq!(format_with_code(&value, "yyyy-mm-dd"), "2024-03-31");
+        assert_eq!(format_with_code(&value, "d-mmm-yy"), "31-Mar-24");
+        assert_eq!(format_with_code(&value, "dddd, mmmm d"), "Sunday, March 31");
+        assert_eq!(format_with_code(&value, "h:mm AM/PM"), "2:05 PM");
+        assert_eq!(format_with_code(&value, "hh:mm:ss"), "14:05:09");
+        assert_eq!(format_with_code(&Data::Float(1.5), "[h]:mm"), "36:00");
+        assert_eq!(
+            format_with_code(&Data::Float(61.0), "yyyy-mm-dd"),
+            "1900-03-01"
+        );
+        assert_eq!(
+            format_with_code(&Data::Float(0.5 + 0.25 / 86_400.0), "ss.00"),
+            "00.25"
+        );
+    }
+
+    #[test]
+    fn test_date_kind() {
+        assert_eq!(date_kind("yyyy-mm-dd"), Some(ExcelDateTimeType::DateTime));
+        assert_eq!(date_kind("h:mm AM/PM"), Some(ExcelDateTimeType::DateTime));
+        assert_eq!(date_kind("[h]:mm:ss"), Some(ExcelDateTimeType::TimeDelta));
+        assert_eq!(date_kind("#,##0.00"), None);
+        assert_eq!(date_kind("\"days\" 0"), None);
+        assert_eq!(date_kind("General;yyyy"), None);
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use std::io::{Read, Seek};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Anything a workbook package can be read from.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// A link from one package part to another part or to an external target,
+/// from a `_rels/*.rels` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relationship {
+    pub id: String,
+    /// Last segment of the relationship type URI, e.g. "comments" or "hyperlink".
+    pub kind: String,
+    /// Part path inside the package, or the target as written when external.
+    pub target: String,
+    pub external: bool,
+}
+
+/// The raw parts of an `.xlsx` zip package, for the pieces calamine
+/// doesn't read (document properties, comments, ...).
+pub struct Package<'a> {
+    archive: ZipArchive<Box<dyn ReadSeek + 'a>>,
+}
+
+impl<'a> Package<'a> {
+    pub fn new<RS: Read + Seek + 'a>(reader: RS) -> Result<Self> {
+        let reader: Box<dyn ReadSeek + 'a> = Box::new(reader);
+        let archive = ZipArchive::new(reader).context("Failed to read workbook package")?;
+        Ok(Package { archive })
+    }
+
+    /// Text of a part, or `None` when the package doesn't contain it.
+    pub fn read_part(&mut self, name: &str) -> Result<Option<String>> {
+        let mut part = match self.archive.by_name(name) {
+            Ok(part) => part,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(err) => return Err(err).with_context(|| format!("Failed to read {}", name)),
+        };
+        let mut xml = String::new();
+        part.read_to_string(&mut xml)
+            .with_context(|| format!("Failed to read {}", name))?;
+        Ok(Some(xml))
+    }
+
+    /// Relationships of a part; empty when it has none.
+    pub fn relationships(&mut self, part: &str) -> Result<Vec<Relationship>> {
+        let (folder, file_name) = part.rsplit_once('/').unwrap_or(("", part));
+        let rels_path = if folder.is_empty() {
+            format!("_rels/{}.rels", file_name)
+        } else {
+            format!("{}/_rels/{}.rels", folder, file_name)
+        };
+        let Some(xml) = self.read_part(&rels_path)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut relationships = Vec::new();
+        for_each_element(&xml, |element| {
+            if element.local_name().as_ref() != b"Relationship" {
+                return Ok(());
+            }
+            let id = attribute(element, b"Id")?.unwrap_or_default();
+            let kind = attribute(element, b"Type")?.unwrap_or_default();
+            let target = attribute(element, b"Target")?.unwrap_or_default();
+            let external = attribute(element, b"TargetMode")?.as_deref() == Some("External");
+            relationships.push(Relationship {
+                id,
+                kind: kind.rsplit('/').next().unwrap_or_default().to_string(),
+                target: if external {
+                    target
+                } else {
+                    resolve_target(folder, &target)
+                },
+                external,
+            });
+            Ok(())
+        })
+        .with_context(|| format!("Malformed {}", rels_path))?;
+        Ok(relationships)
+    }
+
+    /// Path of the worksheet part holding the named sheet.
+    pub fn sheet_part(&mut self, sheet_name: &str) -> Result<Option<String>> {
+        let Some(workbook) = self.read_part("xl/workbook.xml")? else {
+            return Ok(None);
+        };
+        let mut sheet_id = None;
+        for_each_element(&workbook, |element| {
+            if element.local_name().as_ref() == b"sheet"
+                && attribute(element, b"name")?.as_deref() == Some(sheet_name)
+            {
+                sheet_id = attribute(element, b"id")?;
+            }
+            Ok(())
+        })
+        .context("Malformed xl/workbook.xml")?;
+
+        let Some(sheet_id) = sheet_id else {
+            return Ok(None);
+        };
+        Ok(self
+            .relationships("xl/workbook.xml")?
+            .into_iter()
+            .find(|relationship| relationship.id == sheet_id)
+            .map(|relationship| relationship.target))
+    }
+}
+
+/// Resolve a relationship target against the folder of its source part.
+fn resolve_target(folder: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut segments: Vec<&str> = folder.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Call `visit` with every start and empty element of a document.
+pub(crate) fn for_each_element(
+    xml: &str,
+    mut visit: impl FnMut(&BytesStart) -> Result<()>,
+) -> Result<()> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element) => visit(&element)?,
+            Event::Eof => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Unescaped value of the attribute with this local name, ignoring any prefix.
+pub(crate) fn attribute(element: &BytesStart, name: &[u8]) -> Result<Option<String>> {
+    for attr in element.attributes() {
+        let attr = attr?;
+        if attr.key.local_name().as_ref() == name {
+            let value = std::str::from_utf8(&attr.value)?;
+            return Ok(Some(quick_xml::escape::unescape(value)?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_target() {
+        assert_eq!(
+            resolve_target("xl", "worksheets/sheet1.xml"),
+            "xl/worksheets/sheet1.xml"
+        );
+        assert_eq!(
+            resolve_target("xl/worksheets", "../comments1.xml"),
+            "xl/comments1.xml"
+        );
+        assert_eq!(
+            resolve_target("xl/worksheets", "/xl/comments2.xml"),
+            "xl/comments2.xml"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use crate::package::Package;
+use crate::search::{search_sheet_to, SearchOptions, SearchOutput};
+use crate::{open_excel_bytes, open_excel_file};
+use anyhow::{Context, Result};
+use calamine::{Reader, Xlsx};
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use walkdir::WalkDir;
+
+/// A workbook to search: a file on disk, or bytes already read into memory
+/// (e.g. from stdin).
+pub enum WorkbookSource {
+    File(PathBuf),
+    Memory { name: String, bytes: Vec<u8> },
+}
+
+impl WorkbookSource {
+    /// Name used for the workbook in search output.
+    pub fn name(&self) -> String {
+        match self {
+            WorkbookSource::File(path) => path.display().to_string(),
+            WorkbookSource::Memory { name, .. } => name.clone(),
+        }
+    }
+
+    /// The raw zip package, for the parts calamine doesn't read.
+    pub fn package(&self) -> Result<Package<'_>> {
+        match self {
+            WorkbookSource::File(path) => {
+                let file =
+                    File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+                Package::new(BufReader::new(file))
+            }
+            WorkbookSource::Memory { bytes, .. } => Package::new(Cursor::new(bytes.as_slice())),
+        }
+    }
+
+    /// Search the selected sheets one after another with a single handle,
+    /// so the workbook and its shared strings are only parsed once. Each
+    /// sheet's output goes to `emit` as soon as it is done. Fails only when
+    /// the workbook can't be opened.
+    fn search_sheets(
+        &self,
+        sheets: &SheetSelection,
+        search_value: &str,
+        options: &SearchOptions,
+        source: Option<&str>,
+        emit: impl FnMut(Vec<u8>, Result<usize>),
+    ) -> Result<()> {
+        match self {
+            WorkbookSource::File(path) => {
+                let mut workbook = open_excel_file(path)?;
+                search_each(&mut workbook, sheets, search_value, options, source, emit);
+            }
+            WorkbookSource::Memory { bytes, .. } => {
+                let mut workbook = open_excel_bytes(bytes)?;
+                search_each(&mut workbook, sheets, search_value, options, source, emit);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn search_each<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheets: &SheetSelection,
+    search_value: &str,
+    options: &SearchOptions,
+    source: Option<&str>,
+    mut emit: impl FnMut(Vec<u8>, Result<usize>),
+) {
+    let sheet_names = match sheets {
+        SheetSelection::One(sheet) => vec![sheet.clone()],
+        SheetSelection::All => workbook.sheet_names(),
+    };
+    for sheet in sheet_names {
+        let mut buffer = Vec::new();
+        let result = search_sheet_to(workbook, &sheet, search_value, options, source, &mut buffer);
+        emit(buffer, result);
+    }
+}
+
+/// What a workbook's search reports back, in sheet order.
+enum SearchEvent {
+    Sheet(usize, Vec<u8>, Result<usize>),
+    /// Every sheet of the workbook is done, or it could not be opened.
+    Done(usize, Result<()>),
+}
+
+/// Which sheets of each workbook to search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SheetSelection {
+    /// One sheet, by name or index.
+    One(String),
+    All,
+}
+
+/// Outcome of searching several workbooks.
+#[derive(Debug, Default)]
+pub struct SearchReport {
+    /// Selected rows across every sheet that could be searched.
+    pub matches: usize,
+    /// Workbooks or sheets that failed, in the order they were given.
+    pub errors: Vec<anyhow::Error>,
+}
+
+/// Expand directories into the `.xlsx` files below them, sorted by path so
+/// results come out in a stable order. Other paths are kept as given.
+pub fn collect_workbooks(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut workbooks = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            workbooks.push(path.clone());
+            continue;
+        }
+
+        let mut found = Vec::new();
+        for entry in WalkDir::new(path) {
+            let entry = entry.with_context(|| format!("Failed to read directory {:?}", path))?;
+            if entry.file_type().is_file() && is_workbook(entry.path()) {
+                found.push(entry.into_path());
+            }
+        }
+        found.sort();
+        workbooks.extend(found);
+    }
+    Ok(workbooks)
+}
+
+fn is_workbook(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    // Skip the "~$book.xlsx" lock files Excel leaves next to open workbooks
+    !name.starts_with("~$")
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xlsx"))
+}
+
+/// Search the selected sheets of several workbooks on a thread pool, one
+/// workbook per task. Each sheet is searched into a buffer that is printed
+/// as soon as everything before it has been, in workbook order, then sheet
+/// order, so the output is the same as a sequential search and starts
+/// before the last workbook is done. A workbook that fails is reported in
+/// the returned errors without stopping the others.
+pub fn search_workbooks(
+    sources: &[WorkbookSource],
+    sheets: &SheetSelection,
+    search_value: &str,
+    options: &SearchOptions,
+) -> Result<SearchReport> {
+    // Name the workbook in the output only when there is more than one
+    let names: Vec<Option<String>> = sources
+        .iter()
+        .map(|source| (sources.len() > 1).then(|| source.name()))
+        .collect();
+    let with_name = |err: anyhow::Error, idx: usize| match &names[idx] {
+        Some(name) => err.context(format!("While searching {}", name)),
+        None => err,
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    let mut errors: Vec<Vec<anyhow::Error>> = sources.iter().map(|_| Vec::new()).collect();
+    let mut per_source = vec![0; sources.len()];
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| -> Result<()> {
+        let names = &names;
+        scope.spawn(move || {
+            sources
+                .par_iter()
+                .enumerate()
+                .for_each_with(sender, |sender, (idx, source)| {
+                    let result = source.search_sheets(
+                        sheets,
+                        search_value,
+                        options,
+                        names[idx].as_deref(),
+                        |buffer, result| {
+                            let _ = sender.send(SearchEvent::Sheet(idx, buffer, result));
+                        },
+                    );
+                    let _ = sender.send(SearchEvent::Done(idx, result));
+                });
+        });
+
+        // Output of later workbooks waits here until the earlier ones are done
+        let mut pending: Vec<VecDeque<(Vec<u8>, Result<usize>)>> =
+            sources.iter().map(|_| VecDeque::new()).collect();
+        let mut finished = vec![false; sources.len()];
+        let mut current = 0;
+        let mut printed = 0;
+        for event in receiver {
+            match event {
+                SearchEvent::Sheet(idx, buffer, result) => pending[idx].push_back((buffer, result)),
+                SearchEvent::Done(idx, result) => {
+                    if let Err(err) = result {
+                        errors[idx].push(with_name(err, idx));
+                    }
+                    finished[idx] = true;
+                }
+            }
+            while current < sources.len() {
+                while let Some((buffer, result)) = pending[current].pop_front() {
+                    if printed > 0 && options.output == SearchOutput::Rows {
+                        writeln!(stdout)?;
+                    }
+                    stdout.write_all(&buffer)?;
+                    stdout.flush()?;
+                    printed += 1;
+                    match result {
+                        Ok(count) => per_source[current] += count,
+                        Err(err) => errors[current].push(with_name(err, current)),
+                    }
+                }
+                if !finished[current] {
+                    break;
+                }
+                current += 1;
+            }
+        }
+        Ok(())
+    })?;
+
+    if options.output == SearchOutput::FilesWithMatches {
+        for (source, count) in sources.iter().zip(&per_source) {
+            if *count > 0 {
+                writeln!(stdout, "{}", source.name())?;
+            }
+        }
+    }
+
+    Ok(SearchReport {
+        matches: per_source.iter().sum(),
+        errors: errors.into_iter().flatten().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collect_workbooks_expands_directories() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        for name in ["b.xlsx", "a.XLSX", "notes.txt", "~$a.xlsx", "nested/c.xlsx"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let single = PathBuf::from("single.xlsx");
+        let found = collect_workbooks(&[single.clone(), dir.path().to_path_buf()]).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                single,
+                dir.path().join("a.XLSX"),
+                dir.path().join("b.xlsx"),
+                dir.path().join("nested/c.xlsx"),
+            ]
+        );
+    }
+}
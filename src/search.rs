@@ -0,0 +1,853 @@
+use crate::address::sheet_cell_reference;
+use crate::fuzzy::{best_jaro_winkler, substring_distance};
+use crate::stream::{stream_extent, stream_rows};
+use crate::style::Style;
+use crate::text::{match_spans, normalize_for_search};
+use crate::{format_cell, get_sheet_name};
+use anyhow::Result;
+use calamine::{Data, DataType, Xlsx};
+use chrono::NaiveDate;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{Read, Seek, Write};
+use std::ops::{ControlFlow, Range};
+
+/// How a cell value is compared against the search term.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    /// The cell contains the search term.
+    Substring,
+    /// Some part of the cell is within `max_distance` edits of the term.
+    Levenshtein { max_distance: usize },
+    /// The cell (or a run of its words) has at least `min_similarity`
+    /// Jaro-Winkler similarity to the term.
+    JaroWinkler { min_similarity: f64 },
+    /// Numeric cells between `min` and `max` (inclusive).
+    NumberRange { min: f64, max: f64 },
+    /// Date cells between `start` and `end` (inclusive).
+    DateRange { start: NaiveDate, end: NaiveDate },
+}
+
+/// What `search_in_sheet` prints for the selected rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchOutput {
+    /// Every selected row, plus any requested context rows.
+    #[default]
+    Rows,
+    /// Only the number of selected rows, as `Sheet: N`.
+    Count,
+    /// One line per matching cell with its address and value.
+    OnlyMatching,
+    /// Nothing at all; the caller only uses the returned count.
+    Quiet,
+    /// Nothing per sheet; `search_workbooks` lists the workbooks with a
+    /// selected row instead.
+    FilesWithMatches,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    /// Compare with accents removed, so "munchen" finds "München".
+    pub ignore_accents: bool,
+    pub mode: MatchMode,
+    /// Select the rows that do *not* match.
+    pub invert: bool,
+    /// Stop after this many selected rows.
+    pub max_count: Option<usize>,
+    /// Rows of context printed before each selected row.
+    pub before_context: usize,
+    /// Rows of context printed after each selected row.
+    pub after_context: usize,
+    pub output: SearchOutput,
+    /// Colors for matches, row numbers and cell references.
+    pub style: Style,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            case_sensitive: false,
+            ignore_accents: false,
+            mode: MatchMode::Substring,
+            invert: false,
+            max_count: None,
+            before_context: 0,
+            after_context: 0,
+            output: SearchOutput::Rows,
+            style: Style::default(),
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Whether rows are listed by score rather than in sheet order.
+    fn is_ranked(&self) -> bool {
+        self.is_fuzzy() && !self.invert
+    }
+
+    fn is_fuzzy(&self) -> bool {
+        matches!(
+            self.mode,
+            MatchMode::Levenshtein { .. } | MatchMode::JaroWinkler { .. }
+        )
+    }
+
+    /// What is being searched for, as shown in the output.
+    fn describe(&self, search_value: &str) -> String {
+        match self.mode {
+            MatchMode::NumberRange { min, max } => format!("values between {} and {}", min, max),
+            MatchMode::DateRange { start, end } => format!("dates between {} and {}", start, end),
+            _ => format!("'{}'", search_value),
+        }
+    }
+}
+
+/// Scores cell values against a search term according to `SearchOptions`.
+pub struct Matcher<'a> {
+    term: String,
+    options: &'a SearchOptions,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(search_value: &str, options: &'a SearchOptions) -> Self {
+        Matcher {
+            term: Self::normalize(search_value, options),
+            options,
+        }
+    }
+
+    fn normalize(value: &str, options: &SearchOptions) -> String {
+        normalize_for_search(value, options.case_sensitive, options.ignore_accents)
+    }
+
+    /// Similarity score in `0.0..=1.0` if the cell matches, `None` otherwise.
+    /// Range searches compare the typed value; the other modes compare the
+    /// cell's text as shown by `format_cell`.
+    pub fn score(&self, cell: &Data) -> Option<f64> {
+        match self.options.mode {
+            MatchMode::NumberRange { min, max } => {
+                let number = match cell {
+                    Data::Int(i) => *i as f64,
+                    Data::Float(f) => *f,
+                    _ => return None,
+                };
+                (min..=max).contains(&number).then_some(1.0)
+            }
+            MatchMode::DateRange { start, end } => {
+                let date = match cell {
+                    Data::DateTime(dt) if dt.is_duration() => return None,
+                    Data::DateTime(_) | Data::DateTimeIso(_) => cell.as_date()?,
+                    _ => return None,
+                };
+                (start..=end).contains(&date).then_some(1.0)
+            }
+            _ => self.score_text(&format_cell(cell)),
+        }
+    }
+
+    /// Score a text value. Exact substring matches always score 1.0.
+    pub fn score_text(&self, value: &str) -> Option<f64> {
+        let value = Self::normalize(value, self.options);
+        // An empty cell is never a near miss of the term
+        if value.is_empty() && self.options.is_fuzzy() {
+            return None;
+        }
+
+        match self.options.mode {
+            MatchMode::Substring => value.contains(&self.term).then_some(1.0),
+            MatchMode::Levenshtein { max_distance } => {
+                let distance = substring_distance(&self.term, &value);
+                let term_len = self.term.chars().count().max(1);
+                // Replacing every character of the term is no match at all
+                (distance <= max_distance && distance < term_len)
+                    .then(|| 1.0 - distance as f64 / term_len as f64)
+            }
+            MatchMode::JaroWinkler { min_similarity } => {
+                let similarity = best_jaro_winkler(&self.term, &value);
+                (similarity >= min_similarity).then_some(similarity)
+            }
+            MatchMode::NumberRange { .. } | MatchMode::DateRange { .. } => None,
+        }
+    }
+
+    /// Parts of a matching cell's text to highlight: each occurrence of the
+    /// term for substring searches, the whole value for the other modes.
+    fn spans(&self, value: &str) -> Vec<Range<usize>> {
+        if self.options.mode == MatchMode::Substring {
+            let spans = match_spans(
+                value,
+                &self.term,
+                self.options.case_sensitive,
+                self.options.ignore_accents,
+            );
+            if !spans.is_empty() {
+                return spans;
+            }
+        }
+        let whole_value = 0..value.len();
+        vec![whole_value]
+    }
+
+    /// Cell text with the matched parts highlighted.
+    fn highlight(&self, value: &str) -> String {
+        let style = self.options.style;
+        let mut highlighted = String::new();
+        let mut last = 0;
+        for span in self.spans(value) {
+            highlighted.push_str(&value[last..span.start]);
+            highlighted.push_str(&style.matched(&value[span.clone()]));
+            last = span.end;
+        }
+        highlighted.push_str(&value[last..]);
+        highlighted
+    }
+}
+
+/// The rows a search keeps from a sheet: the header, the selected rows and
+/// their context. Everything else is dropped as the sheet streams past.
+struct SheetRows<'a> {
+    name: &'a str,
+    /// Worksheet row and column of the first cell of row 0.
+    origin: (usize, usize),
+    header: Vec<Data>,
+    rows: BTreeMap<usize, Vec<Data>>,
+    /// Number of rows read from the sheet.
+    row_count: usize,
+}
+
+impl SheetRows<'_> {
+    /// 1-based worksheet row number, as shown by Excel.
+    fn row_number(&self, row_idx: usize) -> usize {
+        self.origin.0 + row_idx + 1
+    }
+
+    fn reference(&self, row_idx: usize, col_idx: usize) -> String {
+        sheet_cell_reference(self.name, self.origin.0 + row_idx, self.origin.1 + col_idx)
+    }
+
+    /// Header text of a column, taken from the first row.
+    fn header(&self, col_idx: usize) -> Option<String> {
+        self.header
+            .get(col_idx)
+            .map(format_cell)
+            .filter(|header| !header.is_empty())
+    }
+
+    fn row(&self, row_idx: usize) -> &[Data] {
+        self.rows.get(&row_idx).map_or(&[], Vec::as_slice)
+    }
+}
+
+struct RowMatch {
+    row_idx: usize,
+    matching_columns: Vec<usize>,
+    score: f64,
+}
+
+/// Selects rows as a sheet is streamed, remembering only the selected rows
+/// and the context rows around them.
+struct Collector<'a> {
+    matcher: &'a Matcher<'a>,
+    sheet: SheetRows<'a>,
+    matches: Vec<RowMatch>,
+    before_context: usize,
+    after_context: usize,
+    /// The last `before_context` rows that were not kept.
+    recent: VecDeque<(usize, Vec<Data>)>,
+    /// Rows still to keep after the last selected row.
+    after_remaining: usize,
+}
+
+impl<'a> Collector<'a> {
+    fn new(matcher: &'a Matcher<'a>, name: &'a str, origin: (usize, usize)) -> Self {
+        let options = matcher.options;
+        // Context is only printed with the full row output
+        let (before_context, after_context) = match options.output {
+            SearchOutput::Rows => (options.before_context, options.after_context),
+            _ => (0, 0),
+        };
+        Collector {
+            matcher,
+            sheet: SheetRows {
+                name,
+                origin,
+                header: Vec::new(),
+                rows: BTreeMap::new(),
+                row_count: 0,
+            },
+            matches: Vec::new(),
+            before_context,
+            after_context,
+            recent: VecDeque::new(),
+            after_remaining: 0,
+        }
+    }
+
+    fn select(&self, row_idx: usize, row: &[Data]) -> Option<RowMatch> {
+        let mut matching_columns = Vec::new();
+        let mut best_score: f64 = 0.0;
+
+        // Check each cell in the row for matches
+        for (col_idx, cell) in row.iter().enumerate() {
+            if let Some(score) = self.matcher.score(cell) {
+                matching_columns.push(col_idx);
+                best_score = best_score.max(score);
+            }
+        }
+
+        if self.matcher.options.invert {
+            matching_columns.is_empty().then_some(RowMatch {
+                row_idx,
+                matching_columns,
+                score: 1.0,
+            })
+        } else {
+            (!matching_columns.is_empty()).then_some(RowMatch {
+                row_idx,
+                matching_columns,
+                score: best_score,
+            })
+        }
+    }
+
+    /// Record a row that `select` has already scored.
+    fn accept(
+        &mut self,
+        row_idx: usize,
+        row: Vec<Data>,
+        selected: Option<RowMatch>,
+    ) -> ControlFlow<()> {
+        let options = self.matcher.options;
+
+        // Ranked results need every row before the best ones are known
+        let limit_reached = !options.is_ranked() && options.max_count == Some(self.matches.len());
+        if limit_reached && self.after_remaining == 0 {
+            return ControlFlow::Break(());
+        }
+
+        self.sheet.row_count = row_idx + 1;
+        if row_idx == 0 {
+            self.sheet.header = row.clone();
+        }
+
+        match selected.filter(|_| !limit_reached) {
+            Some(row_match) => {
+                self.sheet.rows.extend(self.recent.drain(..));
+                self.sheet.rows.insert(row_idx, row);
+                self.matches.push(row_match);
+                self.after_remaining = self.after_context;
+            }
+            None if self.after_remaining > 0 => {
+                self.sheet.rows.insert(row_idx, row);
+                self.after_remaining -= 1;
+            }
+            None if self.before_context > 0 => {
+                self.recent.push_back((row_idx, row));
+                if self.recent.len() > self.before_context {
+                    self.recent.pop_front();
+                }
+            }
+            None => {}
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Score a batch of rows on the thread pool, then record them in order.
+    fn accept_chunk(&mut self, chunk: &mut Vec<(usize, Vec<Data>)>) -> ControlFlow<()> {
+        let selected: Vec<Option<RowMatch>> = chunk
+            .par_iter()
+            .map(|(row_idx, row)| self.select(*row_idx, row))
+            .collect();
+        for ((row_idx, row), selected) in chunk.drain(..).zip(selected) {
+            self.accept(row_idx, row, selected)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// The kept rows and the selected rows, in the order they are reported.
+    fn finish(mut self) -> (SheetRows<'a>, Vec<RowMatch>) {
+        let options = self.matcher.options;
+        if options.is_ranked() {
+            // Stable sort keeps sheet order between rows with equal scores
+            self.matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        }
+        if let Some(max_count) = options.max_count {
+            self.matches.truncate(max_count);
+        }
+        (self.sheet, self.matches)
+    }
+}
+
+/// Rows scored together on the thread pool while a sheet is streamed.
+const ROW_CHUNK: usize = 4096;
+
+fn write_row(
+    out: &mut dyn Write,
+    row: &[Data],
+    highlighted: &[usize],
+    matcher: &Matcher,
+) -> std::io::Result<()> {
+    for (col_idx, cell) in row.iter().enumerate() {
+        if col_idx > 0 {
+            write!(out, " | ")?;
+        }
+        let cell_value = format_cell(cell);
+
+        // Highlight the matched text
+        if highlighted.contains(&col_idx) {
+            write!(out, "{}", matcher.highlight(&cell_value))?;
+        } else {
+            write!(out, "{}", cell_value)?;
+        }
+    }
+    writeln!(out)
+}
+
+/// Write each matching cell of a row as `Sheet!C3 (Header): value`, or
+/// without the header when `with_header` is false.
+fn write_hits(
+    out: &mut dyn Write,
+    sheet: &SheetRows,
+    row_match: &RowMatch,
+    style: Style,
+    prefix: &str,
+    with_header: bool,
+) -> std::io::Result<()> {
+    let row = sheet.row(row_match.row_idx);
+    for &col_idx in &row_match.matching_columns {
+        let reference = style.reference(&sheet.reference(row_match.row_idx, col_idx));
+        let value = format_cell(&row[col_idx]);
+        match sheet.header(col_idx) {
+            Some(header) if with_header => {
+                writeln!(out, "{}{} ({}): {}", prefix, reference, header, value)?
+            }
+            _ => writeln!(out, "{}{}: {}", prefix, reference, value)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write the selected rows. With context, each block of nearby rows is
+/// written together (like grep) and followed by a blank line; context rows
+/// use the plain `Row N:` layout of `show`. Ranked results merge only
+/// overlapping blocks, which are listed by their best match.
+fn write_matches(
+    out: &mut dyn Write,
+    sheet: &SheetRows,
+    matches: &[RowMatch],
+    matcher: &Matcher,
+) -> std::io::Result<()> {
+    let options = matcher.options;
+    let style = options.style;
+
+    // Inclusive row ranges, each paired with the matches it contains
+    let mut windows: Vec<(usize, usize, usize)> = matches
+        .iter()
+        .enumerate()
+        .map(|(match_idx, row_match)| {
+            let start = row_match.row_idx.saturating_sub(options.before_context);
+            let end = (row_match.row_idx + options.after_context).min(sheet.row_count - 1);
+            (start, end, match_idx)
+        })
+        .collect();
+    // Ranked results are out of sheet order, and adjacent blocks stay apart
+    // so that rows without shared context keep their rank order
+    windows.sort_by_key(|&(start, _, _)| start);
+    let adjacent = usize::from(!options.is_ranked());
+    let mut blocks: Vec<(usize, usize, Vec<usize>)> = Vec::new();
+    for (start, end, match_idx) in windows {
+        match blocks.last_mut() {
+            Some(block) if start <= block.1 + adjacent => {
+                block.1 = block.1.max(end);
+                block.2.push(match_idx);
+            }
+            _ => blocks.push((start, end, vec![match_idx])),
+        }
+    }
+    blocks.sort_by_key(|(_, _, match_indices)| match_indices.iter().min().copied());
+
+    for (start, end, match_indices) in blocks {
+        for row_idx in start..=end {
+            let row = sheet.row(row_idx);
+            let row_number = style.row_number(&sheet.row_number(row_idx).to_string());
+            let own_match = match_indices
+                .iter()
+                .find(|&&match_idx| matches[match_idx].row_idx == row_idx);
+
+            match own_match {
+                Some(&match_idx) => {
+                    let row_match = &matches[match_idx];
+                    if options.is_ranked() {
+                        writeln!(
+                            out,
+                            "Match {} - Row {} (score {:.2}): ",
+                            match_idx + 1,
+                            row_number,
+                            row_match.score
+                        )?;
+                    } else {
+                        writeln!(out, "Match {} - Row {}: ", match_idx + 1, row_number)?;
+                    }
+                    write_row(out, row, &row_match.matching_columns, matcher)?;
+                    write_hits(out, sheet, row_match, style, "  ", true)?;
+                }
+                None => {
+                    write!(out, "Row {}: ", row_number)?;
+                    write_row(out, row, &[], matcher)?;
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Search a sheet and print the selected rows. Returns the number of
+/// selected rows so callers can tell "no match" apart from success.
+/// Fuzzy searches list the best-scoring rows first.
+pub fn search_in_sheet<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_identifier: &str,
+    search_value: &str,
+    options: &SearchOptions,
+) -> Result<usize> {
+    let mut stdout = std::io::stdout().lock();
+    search_sheet_to(
+        workbook,
+        sheet_identifier,
+        search_value,
+        options,
+        None,
+        &mut stdout,
+    )
+}
+
+/// Like `search_in_sheet`, but writes to `out`. When several workbooks are
+/// searched together, `source` names the workbook in the output.
+///
+/// Rows are streamed from the sheet and scored in parallel batches, so
+/// memory stays bounded and large sheets use every core.
+pub fn search_sheet_to<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_identifier: &str,
+    search_value: &str,
+    options: &SearchOptions,
+    source: Option<&str>,
+    out: &mut dyn Write,
+) -> Result<usize> {
+    let sheet_name = get_sheet_name(workbook, sheet_identifier)?;
+    let extent = stream_extent(workbook, &sheet_name)?;
+
+    let matcher = Matcher::new(search_value, options);
+    let mut collector = Collector::new(&matcher, &sheet_name, extent.origin);
+    let mut chunk = Vec::with_capacity(ROW_CHUNK);
+    stream_rows(workbook, &sheet_name, &extent, |row_idx, row| {
+        chunk.push((row_idx, row.to_vec()));
+        if chunk.len() < ROW_CHUNK {
+            return ControlFlow::Continue(());
+        }
+        collector.accept_chunk(&mut chunk)
+    })?;
+    // Whatever is left when the sheet ended
+    let _ = collector.accept_chunk(&mut chunk);
+    let (sheet, matches) = collector.finish();
+
+    let prefix = source
+        .map(|source| format!("{}:", source))
+        .unwrap_or_default();
+    match options.output {
+        SearchOutput::Quiet | SearchOutput::FilesWithMatches => {}
+        SearchOutput::Count => writeln!(out, "{}{}: {}", prefix, sheet_name, matches.len())?,
+        SearchOutput::OnlyMatching => {
+            for row_match in &matches {
+                write_hits(out, &sheet, row_match, options.style, &prefix, false)?;
+            }
+        }
+        SearchOutput::Rows => {
+            let description = options.describe(search_value);
+            match source {
+                Some(source) => writeln!(
+                    out,
+                    "Searching for {} in {}, sheet: {}",
+                    description, source, sheet_name
+                )?,
+                None => writeln!(
+                    out,
+                    "Searching for {} in sheet: {}",
+                    description, sheet_name
+                )?,
+            }
+            writeln!(out)?;
+
+            write_matches(out, &sheet, &matches, &matcher)?;
+
+            if matches.is_empty() {
+                writeln!(out, "No matches found for {}", description)?;
+            } else {
+                writeln!(out, "Found {} matching row(s)", matches.len())?;
+            }
+        }
+    }
+
+    Ok(matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matcher_substring() {
+        let options = SearchOptions::default();
+        let matcher = Matcher::new("engineering", &options);
+        assert_eq!(matcher.score_text("Engineering"), Some(1.0));
+        assert_eq!(matcher.score_text("Marketing"), None);
+
+        let options = SearchOptions {
+            case_sensitive: true,
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("engineering", &options);
+        assert_eq!(matcher.score_text("Engineering"), None);
+    }
+
+    #[test]
+    fn test_matcher_unicode_folding() {
+        let options = SearchOptions::default();
+        let matcher = Matcher::new("strasse", &options);
+        assert_eq!(matcher.score_text("Hauptstraße 5"), Some(1.0));
+
+        let matcher = Matcher::new("munchen", &options);
+        assert_eq!(matcher.score_text("München"), None);
+
+        let options = SearchOptions {
+            ignore_accents: true,
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("munchen", &options);
+        assert_eq!(matcher.score_text("MÜNCHEN GmbH"), Some(1.0));
+    }
+
+    #[test]
+    fn test_matcher_levenshtein() {
+        let options = SearchOptions {
+            mode: MatchMode::Levenshtein { max_distance: 1 },
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("Jon Doe", &options);
+
+        let score = matcher.score_text("John Doe").unwrap();
+        assert!(score > 0.8 && score < 1.0);
+        assert_eq!(matcher.score_text("jon doe"), Some(1.0));
+        assert_eq!(matcher.score_text("Jane Smith"), None);
+
+        // A short term is not within reach of every blank or unrelated cell
+        let options = SearchOptions {
+            mode: MatchMode::Levenshtein { max_distance: 2 },
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("zq", &options);
+        assert_eq!(matcher.score_text(""), None);
+        assert_eq!(matcher.score_text("Ada"), None);
+        assert!(matcher.score_text("zqx").is_some());
+    }
+
+    #[test]
+    fn test_matcher_number_range() {
+        let options = SearchOptions {
+            mode: MatchMode::NumberRange {
+                min: 1000.0,
+                max: 5000.0,
+            },
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("", &options);
+
+        assert_eq!(matcher.score(&Data::Float(1000.0)), Some(1.0));
+        assert_eq!(matcher.score(&Data::Int(4999)), Some(1.0));
+        assert_eq!(matcher.score(&Data::Float(5000.5)), None);
+        // Text that happens to contain digits is not a number
+        assert_eq!(matcher.score(&Data::String("2000".to_string())), None);
+    }
+
+    #[test]
+    fn test_matcher_date_range() {
+        use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+        let options = SearchOptions {
+            mode: MatchMode::DateRange {
+                start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                end: NaiveDate::from_ymd_opt(2024, 3, 31).unwrap(),
+            },
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("", &options);
+
+        // 45366 is 2024-03-15 in the 1900 date system
+        let in_range = ExcelDateTime::new(45366.0, ExcelDateTimeType::DateTime, false);
+        assert_eq!(matcher.score(&Data::DateTime(in_range)), Some(1.0));
+        let after = ExcelDateTime::new(45400.0, ExcelDateTimeType::DateTime, false);
+        assert_eq!(matcher.score(&Data::DateTime(after)), None);
+
+        assert_eq!(
+            matcher.score(&Data::DateTimeIso("2024-01-01T08:30:00".to_string())),
+            Some(1.0)
+        );
+        // Plain numbers are not dates, even if they fall in the serial range
+        assert_eq!(matcher.score(&Data::Float(45366.0)), None);
+    }
+
+    fn sample_rows() -> Vec<Vec<Data>> {
+        ["Alice", "Bob", "Alina", "Carl", "Alfred"]
+            .iter()
+            .map(|name| vec![Data::String(name.to_string())])
+            .collect()
+    }
+
+    #[test]
+    fn test_matcher_highlight() {
+        let options = SearchOptions::default();
+        let matcher = Matcher::new("doe", &options);
+        assert_eq!(matcher.highlight("John Doe"), "John Doe");
+
+        let options = SearchOptions {
+            style: Style::new(true),
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("doe", &options);
+        assert_eq!(matcher.highlight("John Doe"), "John \x1b[1;31mDoe\x1b[0m");
+
+        // Fuzzy matches highlight the whole cell
+        let options = SearchOptions {
+            style: Style::new(true),
+            mode: MatchMode::Levenshtein { max_distance: 1 },
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("Jon", &options);
+        assert_eq!(matcher.highlight("John"), "\x1b[1;31mJohn\x1b[0m");
+    }
+
+    /// Feed rows through a `Collector` as `search_in_sheet` does and return
+    /// the selected row indices and the rows that were kept.
+    fn collect(rows: &[Vec<Data>], options: &SearchOptions) -> (Vec<usize>, Vec<usize>) {
+        let matcher = Matcher::new("al", options);
+        let mut collector = Collector::new(&matcher, "Sheet1", (0, 0));
+        for (row_idx, row) in rows.iter().enumerate() {
+            let selected = collector.select(row_idx, row);
+            if collector.accept(row_idx, row.clone(), selected).is_break() {
+                break;
+            }
+        }
+        let (sheet, matches) = collector.finish();
+        (
+            matches.iter().map(|m| m.row_idx).collect(),
+            sheet.rows.keys().copied().collect(),
+        )
+    }
+
+    #[test]
+    fn test_collector_invert_and_max_count() {
+        let rows = sample_rows();
+
+        let (found, kept) = collect(&rows, &SearchOptions::default());
+        assert_eq!(found, vec![0, 2, 4]);
+        assert_eq!(kept, vec![0, 2, 4]);
+
+        let options = SearchOptions {
+            invert: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(collect(&rows, &options).0, vec![1, 3]);
+
+        let options = SearchOptions {
+            max_count: Some(2),
+            ..SearchOptions::default()
+        };
+        assert_eq!(collect(&rows, &options).0, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_collector_keeps_context_rows() {
+        let rows = sample_rows();
+
+        let options = SearchOptions {
+            before_context: 1,
+            after_context: 1,
+            max_count: Some(1),
+            ..SearchOptions::default()
+        };
+        let (found, kept) = collect(&rows, &options);
+        assert_eq!(found, vec![0]);
+        // Reading stops after the trailing context of the last match
+        assert_eq!(kept, vec![0, 1]);
+
+        let options = SearchOptions {
+            before_context: 1,
+            ..SearchOptions::default()
+        };
+        let (_, kept) = collect(&rows, &options);
+        assert_eq!(kept, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_ranked_context_blocks_merge() {
+        let rows: Vec<Vec<Data>> = ["Alyce", "Bob", "Alic", "Carl", "Dave", "Eve", "Alice"]
+            .iter()
+            .map(|name| vec![Data::String(name.to_string())])
+            .collect();
+        let options = SearchOptions {
+            mode: MatchMode::Levenshtein { max_distance: 1 },
+            before_context: 1,
+            after_context: 1,
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("Alice", &options);
+        let mut collector = Collector::new(&matcher, "Sheet1", (0, 0));
+        for (row_idx, row) in rows.iter().enumerate() {
+            let selected = collector.select(row_idx, row);
+            let _ = collector.accept(row_idx, row.clone(), selected);
+        }
+        let (sheet, matches) = collector.finish();
+        // The exact match ranks first; the windows of the other two overlap
+        assert_eq!(
+            matches.iter().map(|m| m.row_idx).collect::<Vec<_>>(),
+            vec![6, 0, 2]
+        );
+
+        let mut out = Vec::new();
+        write_matches(&mut out, &sheet, &matches, &matcher).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out
+            .lines()
+            .filter(|line| line.starts_with("Row") || line.starts_with("Match"))
+            .map(|line| line.split(':').next().unwrap())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                "Row 6",
+                "Match 1 - Row 7 (score 1.00)",
+                "Match 2 - Row 1 (score 0.80)",
+                "Row 2",
+                "Match 3 - Row 3 (score 0.80)",
+                "Row 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matcher_jaro_winkler() {
+        let options = SearchOptions {
+            mode: MatchMode::JaroWinkler {
+                min_similarity: 0.9,
+            },
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("Jon Doe", &options);
+
+        assert!(matcher.score_text("John Doe").is_some());
+        assert!(matcher.score_text("Bob Johnson").is_none());
+    }
+}
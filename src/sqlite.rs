@@ -0,0 +1,350 @@
+use crate::export::{cell_timestamp, infer_column_type, json_headers, ColumnType};
+use crate::info::kind_name;
+use crate::{format_cell, read_sheet};
+use anyhow::{bail, Context, Result};
+use calamine::{Data, Reader, SheetType, Xlsx};
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+/// Name of the metadata table describing which sheet went into which table.
+pub const SHEETS_TABLE: &str = "_sheets";
+
+/// Turn a header or sheet name into a plain SQL identifier (`[A-Za-z0-9_]`,
+/// not starting with a digit).
+pub fn sanitize_identifier(name: &str) -> String {
+    let mut identifier = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            identifier.push(c);
+        } else if !identifier.ends_with('_') {
+            identifier.push('_');
+        }
+    }
+
+    let identifier = identifier.trim_matches('_');
+    if identifier.is_empty() {
+        String::new()
+    } else if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", identifier)
+    } else {
+        identifier.to_string()
+    }
+}
+
+fn unique_identifier(name: &str, fallback: &str, used: &mut HashSet<String>) -> String {
+    let base = match sanitize_identifier(name) {
+        identifier if identifier.is_empty() => fallback.to_string(),
+        identifier => identifier,
+    };
+
+    let mut identifier = base.clone();
+    let mut suffix = 2;
+    // SQLite identifiers are case-insensitive
+    while !used.insert(identifier.to_lowercase()) {
+        identifier = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    identifier
+}
+
+fn affinity(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Int | ColumnType::Bool => "INTEGER",
+        ColumnType::Float => "REAL",
+        ColumnType::Timestamp | ColumnType::Utf8 => "TEXT",
+    }
+}
+
+fn sql_value(cell: &Data, column_type: ColumnType) -> Value {
+    match (cell, column_type) {
+        (Data::Empty, _) => Value::Null,
+        (Data::Int(i), ColumnType::Int | ColumnType::Float) => Value::Integer(*i),
+        (Data::Float(f), ColumnType::Int) => Value::Integer(*f as i64),
+        (Data::Float(f), _) => Value::Real(*f),
+        (Data::Bool(b), ColumnType::Bool) => Value::Integer(*b as i64),
+        (Data::DateTime(_) | Data::DateTimeIso(_), ColumnType::Timestamp) => {
+            match cell_timestamp(cell) {
+                Some(dt) => Value::Text(dt.to_string()),
+                None => Value::Text(format_cell(cell)),
+            }
+        }
+        (other, _) => Value::Text(format_cell(other)),
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Write every worksheet of the workbook into its own table of a new SQLite
+/// database, plus a `_sheets` table mapping sheet names to table names.
+/// Chart sheets and other non-worksheets are listed there without a table.
+///
+/// The database is built in a temporary file next to `db_path` and only
+/// renamed over it once complete, so a failed run leaves neither a partial
+/// database nor, with `force`, a missing one behind.
+pub fn workbook_to_sqlite<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    db_path: &Path,
+    force: bool,
+) -> Result<()> {
+    if db_path.exists() && !force {
+        bail!("{:?} already exists. Use --force to overwrite", db_path);
+    }
+
+    let dir = match db_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".xlsq-").suffix(".db");
+    // The permissions a plain create gives, rather than owner-only
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    let temp_file = builder
+        .tempfile_in(dir)
+        .with_context(|| format!("Failed to create SQLite database {:?}", db_path))?;
+    let sheet_count = write_tables(workbook, temp_file.path())?;
+
+    if force {
+        temp_file.persist(db_path)
+    } else {
+        temp_file.persist_noclobber(db_path)
+    }
+    .with_context(|| format!("Failed to write {:?}", db_path))?;
+    println!(
+        "Created {} with {} sheet(s)",
+        db_path.display(),
+        sheet_count
+    );
+
+    Ok(())
+}
+
+/// Fill the empty database at `db_path`, returning the number of sheets.
+fn write_tables<RS: Read + Seek>(workbook: &mut Xlsx<RS>, db_path: &Path) -> Result<usize> {
+    let mut conn = Connection::open(db_path)
+        .with_context(|| format!("Failed to create SQLite database {:?}", db_path))?;
+    let tx = conn.transaction()?;
+
+    tx.execute_batch(&format!(
+        "CREATE TABLE {} (
+            sheet_index INTEGER NOT NULL,
+            sheet_name TEXT NOT NULL,
+            table_name TEXT,
+            row_count INTEGER NOT NULL,
+            column_count INTEGER NOT NULL
+        )",
+        SHEETS_TABLE
+    ))?;
+
+    let mut used_tables = HashSet::from([SHEETS_TABLE.to_string()]);
+    let sheets = workbook.sheets_metadata().to_vec();
+    for (sheet_index, sheet) in sheets.iter().enumerate() {
+        let sheet_name = &sheet.name;
+        if sheet.typ != SheetType::WorkSheet {
+            tx.execute(
+                &format!("INSERT INTO {} VALUES (?1, ?2, NULL, 0, 0)", SHEETS_TABLE),
+                params![sheet_index as i64, sheet_name],
+            )?;
+            println!("Skipped {} '{}'", kind_name(sheet.typ), sheet_name);
+            continue;
+        }
+        let range = read_sheet(workbook, sheet_name)?;
+        let row_count = range.height().saturating_sub(1);
+
+        if range.width() == 0 {
+            tx.execute(
+                &format!("INSERT INTO {} VALUES (?1, ?2, NULL, 0, 0)", SHEETS_TABLE),
+                params![sheet_index as i64, sheet_name],
+            )?;
+            println!("Skipped empty sheet '{}'", sheet_name);
+            continue;
+        }
+
+        let table_name = unique_identifier(
+            sheet_name,
+            &format!("sheet_{}", sheet_index + 1),
+            &mut used_tables,
+        );
+        let body: Vec<&[Data]> = range.rows().skip(1).collect();
+
+        let mut used_columns = HashSet::new();
+        let mut columns = Vec::with_capacity(range.width());
+        for (col_idx, header) in json_headers(&range).iter().enumerate() {
+            let column_name = unique_identifier(
+                header,
+                &format!("column_{}", col_idx + 1),
+                &mut used_columns,
+            );
+            let column_type = infer_column_type(body.iter().map(|row| &row[col_idx]));
+            columns.push((column_name, column_type));
+        }
+
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, column_type)| format!("{} {}", quote(name), affinity(*column_type)))
+            .collect();
+        tx.execute_batch(&format!(
+            "CREATE TABLE {} ({})",
+            quote(&table_name),
+            definitions.join(", ")
+        ))?;
+
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+        {
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO {} VALUES ({})",
+                quote(&table_name),
+                placeholders.join(", ")
+            ))?;
+            for row in &body {
+                let values = row
+                    .iter()
+                    .zip(&columns)
+                    .map(|(cell, (_, column_type))| sql_value(cell, *column_type));
+                insert.execute(rusqlite::params_from_iter(values))?;
+            }
+        }
+
+        tx.execute(
+            &format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5)", SHEETS_TABLE),
+            params![
+                sheet_index as i64,
+                sheet_name,
+                table_name,
+                row_count as i64,
+                columns.len() as i64
+            ],
+        )?;
+        println!(
+            "Wrote sheet '{}' to table '{}' ({} rows)",
+            sheet_name, table_name, row_count
+        );
+    }
+
+    tx.commit()?;
+    conn.close().map_err(|(_, err)| err)?;
+
+    Ok(sheets.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_identifier() {
+        assert_eq!(sanitize_identifier("Unit Price ($)"), "Unit_Price");
+        assert_eq!(sanitize_identifier("2024 Sales"), "_2024_Sales");
+        assert_eq!(sanitize_identifier("  Name  "), "Name");
+        assert_eq!(sanitize_identifier("---"), "");
+    }
+
+    #[test]
+    fn test_unique_identifier() {
+        let mut used = HashSet::from([SHEETS_TABLE.to_string()]);
+        assert_eq!(unique_identifier("Name", "column_1", &mut used), "Name");
+        assert_eq!(unique_identifier("name", "column_2", &mut used), "name_2");
+        assert_eq!(unique_identifier("%", "column_3", &mut used), "column_3");
+        assert_eq!(unique_identifier("_sheets", "sheet_4", &mut used), "sheets");
+    }
+
+    #[test]
+    fn test_sql_value_affinity() {
+        assert_eq!(
+            sql_value(&Data::Float(3.0), ColumnType::Int),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            sql_value(&Data::Float(3.5), ColumnType::Float),
+            Value::Real(3.5)
+        );
+        assert_eq!(
+            sql_value(&Data::Bool(true), ColumnType::Bool),
+            Value::Integer(1)
+        );
+        // In a text column a boolean is written as CSV export writes it
+        assert_eq!(
+            sql_value(&Data::Bool(true), ColumnType::Utf8),
+            Value::Text("true".to_string())
+        );
+        assert_eq!(sql_value(&Data::Empty, ColumnType::Utf8), Value::Null);
+        assert_eq!(affinity(ColumnType::Timestamp), "TEXT");
+    }
+
+    #[test]
+    fn test_chart_sheets_get_no_table() {
+        use std::io::Write;
+        use tempfile::{NamedTempFile, TempDir};
+        use xlsxwriter::*;
+
+        let scratch = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let workbook = Workbook::new(scratch.path().to_str().unwrap()).unwrap();
+        let mut flags = workbook.add_worksheet(Some("Flags")).unwrap();
+        flags.write_string(0, 0, "Flag", None).unwrap();
+        flags.write_boolean(1, 0, true, None).unwrap();
+        flags.write_string(2, 0, "n/a", None).unwrap();
+        workbook.add_worksheet(Some("Chart")).unwrap();
+        workbook.close().unwrap();
+
+        // Point the second sheet at a chart sheet part instead
+        let book = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let mut archive = zip::ZipArchive::new(scratch.reopen().unwrap()).unwrap();
+        let mut writer = zip::ZipWriter::new(book.reopen().unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for idx in 0..archive.len() {
+            let mut part = archive.by_index(idx).unwrap();
+            if part.name() != "xl/_rels/workbook.xml.rels" {
+                writer.raw_copy_file(part).unwrap();
+                continue;
+            }
+            let mut xml = String::new();
+            part.read_to_string(&mut xml).unwrap();
+            writer.start_file(part.name(), options).unwrap();
+            writer
+                .write_all(
+                    xml.replace("worksheets/sheet2.xml", "chartsheets/sheet1.xml")
+                        .as_bytes(),
+                )
+                .unwrap();
+        }
+        writer
+            .start_file("xl/chartsheets/sheet1.xml", options)
+            .unwrap();
+        writer.write_all(b"<chartsheet/>").unwrap();
+        writer.finish().unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("book.db");
+        let mut workbook = crate::open_excel_file(book.path()).unwrap();
+        workbook_to_sqlite(&mut workbook, &db_path, false).unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let sheets: Vec<(String, Option<String>)> = conn
+            .prepare("SELECT sheet_name, table_name FROM _sheets ORDER BY sheet_index")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(
+            sheets,
+            vec![
+                ("Flags".to_string(), Some("Flags".to_string())),
+                ("Chart".to_string(), None)
+            ]
+        );
+        let flags: Vec<String> = conn
+            .prepare("SELECT Flag FROM Flags")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(flags, vec!["true", "n/a"]);
+    }
+}
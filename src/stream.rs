@@ -0,0 +1,338 @@
+use crate::{read_sheet, XlsqError};
+use calamine::{Data, DataRef, Dimensions, Range, Xlsx, XlsxError};
+use std::io::{Read, Seek};
+use std::ops::ControlFlow;
+
+/// The block of cells a worksheet uses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SheetExtent {
+    /// Worksheet row and column of the top-left cell.
+    pub origin: (usize, usize),
+    pub height: usize,
+    pub width: usize,
+    /// The whole sheet, when `stream_extent` had to load it to measure it.
+    /// `stream_rows` hands these rows out instead of reading them again.
+    pub cells: Option<Range<Data>>,
+}
+
+fn unreadable(sheet_name: &str, source: XlsxError) -> XlsqError {
+    XlsqError::UnreadableSheet {
+        sheet: sheet_name.to_string(),
+        source,
+    }
+}
+
+/// Find the size of a sheet. The `<dimension>` element it declares is used
+/// when it agrees with the first cell, so normally no other cells are read.
+/// Sheets without one (many non-Excel writers leave it out) or with one
+/// the first cell contradicts are measured from their cells instead.
+/// Chart sheets and other non-worksheets have an empty extent.
+pub fn sheet_extent<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+) -> Result<SheetExtent, XlsqError> {
+    measure(workbook, sheet_name, false)
+}
+
+/// The extent to stream a sheet with. Like `sheet_extent`, except that a
+/// sheet without a trustworthy dimension is loaded once and kept in the
+/// extent, rather than scanned here and read again by `stream_rows`.
+pub fn stream_extent<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+) -> Result<SheetExtent, XlsqError> {
+    measure(workbook, sheet_name, true)
+}
+
+fn measure<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+    load: bool,
+) -> Result<SheetExtent, XlsqError> {
+    let mut reader = match workbook.worksheet_cells_reader(sheet_name) {
+        Ok(reader) => reader,
+        Err(XlsxError::NotAWorksheet(_)) => return Ok(SheetExtent::default()),
+        Err(source) => return Err(unreadable(sheet_name, source)),
+    };
+    let declared = reader.dimensions();
+    // Position of the next cell holding data. Styled but empty cells don't
+    // count, as in `worksheet_range`
+    let mut next_data_cell = || -> Result<Option<(u32, u32)>, XlsqError> {
+        while let Some(cell) = reader
+            .next_cell()
+            .map_err(|source| unreadable(sheet_name, source))?
+        {
+            if !matches!(cell.get_value(), DataRef::Empty) {
+                return Ok(Some(cell.get_position()));
+            }
+        }
+        Ok(None)
+    };
+    let declared_extent = SheetExtent {
+        origin: (declared.start.0 as usize, declared.start.1 as usize),
+        height: (declared.end.0 - declared.start.0) as usize + 1,
+        width: (declared.end.1 - declared.start.1) as usize + 1,
+        cells: None,
+    };
+    let Some(first) = next_data_cell()? else {
+        return Ok(declared_extent);
+    };
+    // A missing `<dimension>` reads as A1:A1. Stored in order, the first
+    // cell is on the top row of an accurate one
+    if declared != Dimensions::default()
+        && first.0 == declared.start.0
+        && first.1 >= declared.start.1
+    {
+        return Ok(declared_extent);
+    }
+
+    if load {
+        drop(reader);
+        let range = read_sheet(workbook, sheet_name)?;
+        let origin = range.start().unwrap_or_default();
+        return Ok(SheetExtent {
+            origin: (origin.0 as usize, origin.1 as usize),
+            height: range.height(),
+            width: range.width(),
+            cells: Some(range),
+        });
+    }
+    let (mut start, mut end) = (first, first);
+    while let Some((row, col)) = next_data_cell()? {
+        start = (start.0.min(row), start.1.min(col));
+        end = (end.0.max(row), end.1.max(col));
+    }
+    Ok(SheetExtent {
+        origin: (start.0 as usize, start.1 as usize),
+        height: (end.0 - start.0) as usize + 1,
+        width: (end.1 - start.1) as usize + 1,
+        cells: None,
+    })
+}
+
+/// Stream a sheet to `visit` one row at a time instead of loading it into
+/// a `Range`, so memory stays bounded however large the sheet is. Rows are
+/// numbered from the top of `extent`, as found by `stream_extent`, and
+/// padded to its width; gaps between rows are passed on as empty rows, so
+/// indices line up with `Range::rows`. Returning `ControlFlow::Break` stops
+/// reading.
+///
+/// The rows of an extent that holds its cells come from there instead. A
+/// cell outside the extent is an error rather than being left out.
+pub fn stream_rows<RS, F>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+    extent: &SheetExtent,
+    mut visit: F,
+) -> Result<(), XlsqError>
+where
+    RS: Read + Seek,
+    F: FnMut(usize, &[Data]) -> ControlFlow<()>,
+{
+    if let Some(range) = &extent.cells {
+        for (row_idx, row) in range.rows().enumerate() {
+            if visit(row_idx, row).is_break() {
+                break;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut reader = match workbook.worksheet_cells_reader(sheet_name) {
+        Ok(reader) => reader,
+        Err(XlsxError::NotAWorksheet(_)) => return Ok(()),
+        Err(source) => return Err(unreadable(sheet_name, source)),
+    };
+    let (origin_row, origin_col) = extent.origin;
+    let width = extent.width;
+
+    let mut row_idx = 0;
+    let mut row: Vec<Data> = Vec::new();
+    let mut emit = |row_idx: usize, row: &mut Vec<Data>| {
+        if row.len() < width {
+            row.resize(width, Data::Empty);
+        }
+        let flow = visit(row_idx, row);
+        row.clear();
+        flow
+    };
+
+    while let Some(cell) = reader
+        .next_cell()
+        .map_err(|source| unreadable(sheet_name, source))?
+    {
+        if matches!(cell.get_value(), DataRef::Empty) {
+            continue;
+        }
+        let (cell_row, cell_col) = cell.get_position();
+        let (cell_row, cell_col) = (cell_row as usize, cell_col as usize);
+        // The declared dimension lied after rows were already handed over
+        if cell_row < origin_row + row_idx || cell_col < origin_col {
+            return Err(unreadable(
+                sheet_name,
+                XlsxError::Unexpected("cell outside the sheet's declared dimension"),
+            ));
+        }
+        let (cell_row, cell_col) = (cell_row - origin_row, cell_col - origin_col);
+
+        // Hand over the finished row and any empty rows in between
+        while row_idx < cell_row {
+            if emit(row_idx, &mut row).is_break() {
+                return Ok(());
+            }
+            row_idx += 1;
+        }
+
+        if row.len() <= cell_col {
+            row.resize(cell_col + 1, Data::Empty);
+        }
+        row[cell_col] = Data::from(cell.get_value().clone());
+    }
+
+    if !row.is_empty() {
+        let _ = emit(row_idx, &mut row);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{open_excel_file, read_sheet};
+    use tempfile::NamedTempFile;
+
+    fn create_sparse_excel_file() -> anyhow::Result<NamedTempFile> {
+        use xlsxwriter::*;
+
+        let temp_file = NamedTempFile::with_suffix(".xlsx")?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let workbook = Workbook::new(path)?;
+        let mut worksheet = workbook.add_worksheet(Some("Data"))?;
+
+        // Data starts at B2 and row 4 is left empty
+        worksheet.write_string(1, 1, "Name", None)?;
+        worksheet.write_string(1, 2, "Score", None)?;
+        worksheet.write_string(2, 1, "Ada", None)?;
+        worksheet.write_number(2, 2, 9.5, None)?;
+        worksheet.write_string(4, 1, "Grace", None)?;
+
+        workbook.close()?;
+
+        Ok(temp_file)
+    }
+
+    /// Copy a workbook, rewriting the XML of its first sheet. xlsxwriter
+    /// always writes a `<dimension>` and rows in order.
+    fn with_sheet_xml(workbook: &NamedTempFile, edit: impl Fn(&str) -> String) -> NamedTempFile {
+        use std::fs::File;
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+        use zip::{ZipArchive, ZipWriter};
+
+        let mut archive = ZipArchive::new(File::open(workbook.path()).unwrap()).unwrap();
+        let output = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let mut writer = ZipWriter::new(File::create(output.path()).unwrap());
+        for idx in 0..archive.len() {
+            let mut part = archive.by_index(idx).unwrap();
+            if part.name() != "xl/worksheets/sheet1.xml" {
+                writer.raw_copy_file(part).unwrap();
+                continue;
+            }
+            let mut xml = String::new();
+            part.read_to_string(&mut xml).unwrap();
+            writer
+                .start_file(part.name(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(edit(&xml).as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+        output
+    }
+
+    /// Stream the "Data" sheet and check it against `worksheet_range`.
+    fn assert_streams_like_range(temp_file: &NamedTempFile) -> SheetExtent {
+        let mut workbook = open_excel_file(temp_file.path()).unwrap();
+
+        let measured = sheet_extent(&mut workbook, "Data").unwrap();
+        assert_eq!(measured.origin, (1, 1));
+        assert_eq!((measured.height, measured.width), (4, 2));
+        assert!(measured.cells.is_none());
+
+        let extent = stream_extent(&mut workbook, "Data").unwrap();
+        assert_eq!(extent.origin, measured.origin);
+        assert_eq!((extent.height, extent.width), (4, 2));
+
+        let mut streamed = Vec::new();
+        stream_rows(&mut workbook, "Data", &extent, |row_idx, row| {
+            streamed.push((row_idx, row.to_vec()));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+
+        let range = read_sheet(&mut workbook, "Data").unwrap();
+        let expected: Vec<(usize, Vec<Data>)> =
+            range.rows().map(|row| row.to_vec()).enumerate().collect();
+        assert_eq!(streamed, expected);
+        extent
+    }
+
+    #[test]
+    fn test_stream_rows_matches_range() {
+        let temp_file = create_sparse_excel_file().expect("Failed to create test file");
+        assert!(assert_streams_like_range(&temp_file).cells.is_none());
+    }
+
+    #[test]
+    fn test_stream_rows_without_trusted_dimension() {
+        let temp_file = create_sparse_excel_file().expect("Failed to create test file");
+        let dimension = |xml: &str| {
+            let start = xml.find("<dimension").unwrap();
+            let end = start + xml[start..].find("/>").unwrap() + 2;
+            (start, end)
+        };
+
+        // No <dimension> at all, as many non-Excel writers do
+        let missing = with_sheet_xml(&temp_file, |xml| {
+            let (start, end) = dimension(xml);
+            format!("{}{}", &xml[..start], &xml[end..])
+        });
+        assert!(assert_streams_like_range(&missing).cells.is_some());
+
+        // One that starts after the data
+        let stale = with_sheet_xml(&temp_file, |xml| {
+            let (start, end) = dimension(xml);
+            format!("{}<dimension ref=\"C3:C5\"/>{}", &xml[..start], &xml[end..])
+        });
+        assert!(assert_streams_like_range(&stale).cells.is_some());
+
+        // The last row stored first
+        let shuffled = with_sheet_xml(&temp_file, |xml| {
+            let start = xml.rfind("<row ").unwrap();
+            let end = xml.rfind("</row>").unwrap() + "</row>".len();
+            let without = format!("{}{}", &xml[..start], &xml[end..]);
+            without.replacen(
+                "<sheetData>",
+                &format!("<sheetData>{}", &xml[start..end]),
+                1,
+            )
+        });
+        assert!(assert_streams_like_range(&shuffled).cells.is_some());
+    }
+
+    #[test]
+    fn test_stream_rows_stops_early() {
+        let temp_file = create_sparse_excel_file().expect("Failed to create test file");
+        let mut workbook = open_excel_file(temp_file.path()).unwrap();
+
+        let mut seen = 0;
+        let extent = stream_extent(&mut workbook, "Data").unwrap();
+        stream_rows(&mut workbook, "Data", &extent, |_, _| {
+            seen += 1;
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        assert_eq!(seen, 1);
+    }
+}
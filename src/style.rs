@@ -0,0 +1,59 @@
+/// ANSI styling for terminal output. Every method returns the text unchanged
+/// when color is disabled, so callers can style unconditionally.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub color: bool,
+}
+
+impl Style {
+    pub fn new(color: bool) -> Self {
+        Style { color }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Matched text in search results (bold red, like grep).
+    pub fn matched(&self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+
+    /// Row numbers (green).
+    pub fn row_number(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    /// Header cells (bold).
+    pub fn header(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    /// Cell references such as `Sheet1!B2` (magenta).
+    pub fn reference(&self, text: &str) -> String {
+        self.paint("35", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_disabled_is_plain() {
+        let style = Style::default();
+        assert_eq!(style.matched("abc"), "abc");
+        assert_eq!(style.row_number("3"), "3");
+    }
+
+    #[test]
+    fn test_style_enabled_wraps_text() {
+        let style = Style::new(true);
+        assert_eq!(style.matched("abc"), "\x1b[1;31mabc\x1b[0m");
+        assert_eq!(style.header(""), "");
+    }
+}
@@ -0,0 +1,416 @@
+use crate::package::{attribute, Package};
+use anyhow::{Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+
+/// Formatting applied to a cell, from `xl/styles.xml`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CellStyle {
+    pub number_format_id: u32,
+    /// Format code, e.g. `#,##0.00`; `None` for locale-dependent built-ins.
+    pub number_format: Option<String>,
+    pub font: Font,
+    pub fill: Fill,
+    pub border: Border,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Font {
+    pub name: Option<String>,
+    pub size: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: Option<String>,
+    pub strike: bool,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fill {
+    /// Pattern type, e.g. "solid"; `None` or "none" when unfilled.
+    pub pattern: Option<String>,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+}
+
+/// Line style and color of one side of a border.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Edge {
+    pub style: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Border {
+    pub left: Option<Edge>,
+    pub right: Option<Edge>,
+    pub top: Option<Edge>,
+    pub bottom: Option<Edge>,
+    pub diagonal: Option<Edge>,
+}
+
+impl Font {
+    /// e.g. "Calibri 11, bold, color #FF0000".
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        match (&self.name, &self.size) {
+            (Some(name), Some(size)) => parts.push(format!("{} {}", name, size)),
+            (Some(name), None) => parts.push(name.clone()),
+            (None, Some(size)) => parts.push(format!("size {}", size)),
+            (None, None) => {}
+        }
+        if self.bold {
+            parts.push("bold".to_string());
+        }
+        if self.italic {
+            parts.push("italic".to_string());
+        }
+        if let Some(underline) = &self.underline {
+            parts.push(format!("{} underline", underline));
+        }
+        if self.strike {
+            parts.push("strikethrough".to_string());
+        }
+        if let Some(color) = &self.color {
+            parts.push(format!("color {}", color));
+        }
+        if parts.is_empty() {
+            return "default".to_string();
+        }
+        parts.join(", ")
+    }
+}
+
+impl Fill {
+    pub fn describe(&self) -> String {
+        match self.pattern.as_deref() {
+            None | Some("none") => "none".to_string(),
+            Some(pattern) => {
+                let mut description = pattern.to_string();
+                if let Some(color) = &self.foreground {
+                    description.push_str(&format!(" {}", color));
+                }
+                if let Some(color) = &self.background {
+                    description.push_str(&format!(" on {}", color));
+                }
+                description
+            }
+        }
+    }
+}
+
+impl Border {
+    /// e.g. "left thin, bottom double #000000".
+    pub fn describe(&self) -> String {
+        let edges = [
+            ("left", &self.left),
+            ("right", &self.right),
+            ("top", &self.top),
+            ("bottom", &self.bottom),
+            ("diagonal", &self.diagonal),
+        ];
+        let parts: Vec<String> = edges
+            .iter()
+            .filter_map(|(side, edge)| {
+                let edge = edge.as_ref()?;
+                Some(match &edge.color {
+                    Some(color) => format!("{} {} {}", side, edge.style, color),
+                    None => format!("{} {}", side, edge.style),
+                })
+            })
+            .collect();
+        if parts.is_empty() {
+            return "none".to_string();
+        }
+        parts.join(", ")
+    }
+}
+
+/// Code of a built-in number format, for the ids that are the same in every
+/// locale.
+pub fn builtin_number_format(id: u32) -> Option<&'static str> {
+    Some(match id {
+        0 => "General",
+        1 => "0",
+        2 => "0.00",
+        3 => "#,##0",
+        4 => "#,##0.00",
+        9 => "0%",
+        10 => "0.00%",
+        11 => "0.00E+00",
+        12 => "# ?/?",
+        13 => "# ??/??",
+        14 => "mm-dd-yy",
+        15 => "d-mmm-yy",
+        16 => "d-mmm",
+        17 => "mmm-yy",
+        18 => "h:mm AM/PM",
+        19 => "h:mm:ss AM/PM",
+        20 => "h:mm",
+        21 => "h:mm:ss",
+        22 => "m/d/yy h:mm",
+        37 => "#,##0 ;(#,##0)",
+        38 => "#,##0 ;[Red](#,##0)",
+        39 => "#,##0.00;(#,##0.00)",
+        40 => "#,##0.00;[Red](#,##0.00)",
+        45 => "mm:ss",
+        46 => "[h]:mm:ss",
+        47 => "mmss.0",
+        48 => "##0.0E+0",
+        49 => "@",
+        _ => return None,
+    })
+}
+
+/// Describe a `<color>` element: `#RRGGBB`, a theme color or an indexed one.
+fn color(element: &BytesStart) -> Result<Option<String>> {
+    if let Some(rgb) = attribute(element, b"rgb")? {
+        // ARGB; the alpha byte is not used by Excel
+        let rgb = if rgb.len() == 8 && rgb.is_ascii() {
+            &rgb[2..]
+        } else {
+            &rgb[..]
+        };
+        return Ok(Some(format!("#{}", rgb.to_ascii_uppercase())));
+    }
+    if let Some(theme) = attribute(element, b"theme")? {
+        return Ok(Some(match attribute(element, b"tint")? {
+            Some(tint) => format!("theme {} (tint {})", theme, tint),
+            None => format!("theme {}", theme),
+        }));
+    }
+    if let Some(indexed) = attribute(element, b"indexed")? {
+        return Ok(Some(format!("indexed {}", indexed)));
+    }
+    if attribute(element, b"auto")?.as_deref() == Some("1") {
+        return Ok(Some("automatic".to_string()));
+    }
+    Ok(None)
+}
+
+/// Whether a boolean font property such as `<b/>` or `<b val="0"/>` is on.
+fn enabled(element: &BytesStart) -> Result<bool> {
+    Ok(!matches!(
+        attribute(element, b"val")?.as_deref(),
+        Some("0" | "false")
+    ))
+}
+
+#[derive(Default)]
+struct Stylesheet {
+    number_formats: HashMap<u32, String>,
+    fonts: Vec<Font>,
+    fills: Vec<Fill>,
+    borders: Vec<Border>,
+    /// (numFmtId, fontId, fillId, borderId) of each `cellXfs` entry.
+    cell_formats: Vec<(u32, usize, usize, usize)>,
+}
+
+fn parse_stylesheet(xml: &str) -> Result<Stylesheet> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut sheet = Stylesheet::default();
+    // The list being read and, for borders, the side being read
+    let mut section: Vec<u8> = Vec::new();
+    let mut edge: Option<Vec<u8>> = None;
+
+    loop {
+        let element = match reader.read_event()? {
+            Event::Start(element) => {
+                let name = element.local_name().as_ref().to_vec();
+                if matches!(
+                    name.as_slice(),
+                    b"fonts" | b"fills" | b"borders" | b"cellXfs" | b"cellStyleXfs" | b"dxfs"
+                ) {
+                    section = name;
+                }
+                element
+            }
+            Event::Empty(element) => element,
+            Event::End(element) => {
+                let name = element.local_name();
+                if name.as_ref() == section.as_slice() {
+                    section.clear();
+                } else if edge.as_deref() == Some(name.as_ref()) {
+                    edge = None;
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let name = element.local_name();
+        match (section.as_slice(), name.as_ref()) {
+            (_, b"numFmt") => {
+                let id = attribute(&element, b"numFmtId")?.and_then(|id| id.parse().ok());
+                if let (Some(id), Some(code)) = (id, attribute(&element, b"formatCode")?) {
+                    sheet.number_formats.insert(id, code);
+                }
+            }
+            (b"fonts", b"font") => sheet.fonts.push(Font::default()),
+            (b"fonts", property) => {
+                let Some(font) = sheet.fonts.last_mut() else {
+                    continue;
+                };
+                match property {
+                    b"name" => font.name = attribute(&element, b"val")?,
+                    b"sz" => font.size = attribute(&element, b"val")?,
+                    b"b" => font.bold = enabled(&element)?,
+                    b"i" => font.italic = enabled(&element)?,
+                    b"strike" => font.strike = enabled(&element)?,
+                    b"u" => {
+                        font.underline = match attribute(&element, b"val")? {
+                            Some(style) if style == "none" => None,
+                            Some(style) => Some(style),
+                            None => Some("single".to_string()),
+                        }
+                    }
+                    b"color" => font.color = color(&element)?,
+                    _ => {}
+                }
+            }
+            (b"fills", b"fill") => sheet.fills.push(Fill::default()),
+            (b"fills", property) => {
+                let Some(fill) = sheet.fills.last_mut() else {
+                    continue;
+                };
+                match property {
+                    b"patternFill" => fill.pattern = attribute(&element, b"patternType")?,
+                    b"gradientFill" => fill.pattern = Some("gradient".to_string()),
+                    b"fgColor" => fill.foreground = color(&element)?,
+                    b"bgColor" => fill.background = color(&element)?,
+                    _ => {}
+                }
+            }
+            (b"borders", b"border") => sheet.borders.push(Border::default()),
+            (b"borders", property) => {
+                let Some(border) = sheet.borders.last_mut() else {
+                    continue;
+                };
+                match property {
+                    b"left" | b"start" | b"right" | b"end" | b"top" | b"bottom" | b"diagonal" => {
+                        let slot = match property {
+                            b"left" | b"start" => &mut border.left,
+                            b"right" | b"end" => &mut border.right,
+                            b"top" => &mut border.top,
+                            b"bottom" => &mut border.bottom,
+                            _ => &mut border.diagonal,
+                        };
+                        *slot =
+                            attribute(&element, b"style")?.map(|style| Edge { style, color: None });
+                        edge = Some(property.to_vec());
+                    }
+                    b"color" => {
+                        let slot = match edge.as_deref() {
+                            Some(b"left" | b"start") => &mut border.left,
+                            Some(b"right" | b"end") => &mut border.right,
+                            Some(b"top") => &mut border.top,
+                            Some(b"bottom") => &mut border.bottom,
+                            Some(b"diagonal") => &mut border.diagonal,
+                            _ => continue,
+                        };
+                        if let Some(slot) = slot {
+                            slot.color = color(&element)?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            (b"cellXfs", b"xf") => {
+                let id = |name: &[u8]| -> Result<usize> {
+                    Ok(attribute(&element, name)?
+                        .and_then(|id| id.parse().ok())
+                        .unwrap_or(0))
+                };
+                sheet.cell_formats.push((
+                    id(b"numFmtId")? as u32,
+                    id(b"fontId")?,
+                    id(b"fillId")?,
+                    id(b"borderId")?,
+                ));
+            }
+            _ => {}
+        }
+    }
+    Ok(sheet)
+}
+
+/// Style of the `index`-th cell format (a cell's `s` attribute).
+pub fn read_cell_style(package: &mut Package, index: usize) -> Result<Option<CellStyle>> {
+    let Some(xml) = package.read_part("xl/styles.xml")? else {
+        return Ok(None);
+    };
+    let stylesheet = parse_stylesheet(&xml).context("Malformed xl/styles.xml")?;
+    let Some(&(number_format_id, font, fill, border)) = stylesheet.cell_formats.get(index) else {
+        return Ok(None);
+    };
+    Ok(Some(CellStyle {
+        number_format_id,
+        number_format: stylesheet
+            .number_formats
+            .get(&number_format_id)
+            .cloned()
+            .or_else(|| builtin_number_format(number_format_id).map(str::to_string)),
+        font: stylesheet.fonts.get(font).cloned().unwrap_or_default(),
+        fill: stylesheet.fills.get(fill).cloned().unwrap_or_default(),
+        border: stylesheet.borders.get(border).cloned().unwrap_or_default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLES_XML: &str = r#"<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+  <numFmts count="1"><numFmt numFmtId="164" formatCode="&quot;$&quot;#,##0.00"/></numFmts>
+  <fonts count="2">
+    <font><sz val="11"/><color theme="1"/><name val="Calibri"/></font>
+    <font><b/><i val="0"/><u/><sz val="12"/><color rgb="FFFF0000"/><name val="Arial"/></font>
+  </fonts>
+  <fills count="2">
+    <fill><patternFill patternType="none"/></fill>
+    <fill><patternFill patternType="solid"><fgColor rgb="FFFFFF00"/><bgColor indexed="64"/></patternFill></fill>
+  </fills>
+  <borders count="2">
+    <border><left/><right/><top/><bottom/><diagonal/></border>
+    <border><left style="thin"><color auto="1"/></left><right/><top/><bottom style="double"><color rgb="FF000000"/></bottom><diagonal/></border>
+  </borders>
+  <cellXfs count="2">
+    <xf numFmtId="0" fontId="0" fillId="0" borderId="0"/>
+    <xf numFmtId="164" fontId="1" fillId="1" borderId="1" applyNumberFormat="1"/>
+  </cellXfs>
+</styleSheet>"#;
+
+    #[test]
+    fn test_parse_stylesheet() {
+        let stylesheet = parse_stylesheet(STYLES_XML).unwrap();
+        assert_eq!(stylesheet.cell_formats, vec![(0, 0, 0, 0), (164, 1, 1, 1)]);
+        assert_eq!(
+            stylesheet.number_formats.get(&164).map(String::as_str),
+            Some("\"$\"#,##0.00")
+        );
+        assert_eq!(stylesheet.fonts[0].describe(), "Calibri 11, color theme 1");
+        assert_eq!(
+            stylesheet.fonts[1].describe(),
+            "Arial 12, bold, single underline, color #FF0000"
+        );
+        assert_eq!(stylesheet.fills[0].describe(), "none");
+        assert_eq!(
+            stylesheet.fills[1].describe(),
+            "solid #FFFF00 on indexed 64"
+        );
+        assert_eq!(stylesheet.borders[0].describe(), "none");
+        assert_eq!(
+            stylesheet.borders[1].describe(),
+            "left thin automatic, bottom double #000000"
+        );
+
+        // A malformed color is shown as written rather than sliced mid-character
+        let xml = STYLES_XML.replace("FFFF0000", "aÄ12345");
+        let stylesheet = parse_stylesheet(&xml).unwrap();
+        assert_eq!(
+            stylesheet.fonts[1].describe(),
+            "Arial 12, bold, single underline, color #AÄ12345"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::ops::Range;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Full Unicode case folding, so "Straße" and "STRASSE" compare equal.
+/// Turkish "İ" (which folds to "i" plus a combining dot) and "ı" (which has
+/// no folding) become a plain "i", so "istanbul" finds "İstanbul".
+pub fn fold_case(value: &str) -> String {
+    let folded = caseless::default_case_fold_str(value);
+    let mut result = String::with_capacity(folded.len());
+    let mut chars = folded.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'i' => {
+                chars.next_if_eq(&'\u{307}');
+                result.push('i');
+            }
+            'ı' => result.push('i'),
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+/// Remove accents by decomposing (NFKD) and dropping combining marks, so
+/// "München" becomes "Munchen". Letters with strokes that have no
+/// decomposition (ı, ø, ł, đ, ħ) are mapped to their base letter as well.
+pub fn strip_accents(value: &str) -> String {
+    value
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .map(|c| match c {
+            'ı' => 'i',
+            'ø' => 'o',
+            'Ø' => 'O',
+            'ł' => 'l',
+            'Ł' => 'L',
+            'đ' => 'd',
+            'Đ' => 'D',
+            'ħ' => 'h',
+            'Ħ' => 'H',
+            other => other,
+        })
+        .collect()
+}
+
+/// Bring a cell value or search term into the form used for comparison.
+pub fn normalize_for_search(value: &str, case_sensitive: bool, ignore_accents: bool) -> String {
+    let value = if case_sensitive {
+        value.to_string()
+    } else {
+        fold_case(value)
+    };
+
+    if ignore_accents {
+        strip_accents(&value)
+    } else {
+        // Composed and decomposed forms of the same text should still match
+        value.nfc().collect()
+    }
+}
+
+/// Byte ranges of `haystack` where `needle` (already passed through
+/// `normalize_for_search`) occurs, so the original text can be highlighted.
+/// Each character is normalized together with its combining marks, which
+/// keeps track of where every normalized piece came from.
+pub fn match_spans(
+    haystack: &str,
+    needle: &str,
+    case_sensitive: bool,
+    ignore_accents: bool,
+) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    // (range in the normalized text, range in the original text)
+    let mut clusters: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    let mut normalized = String::new();
+    let mut chars = haystack.char_indices().peekable();
+    while let Some((start, _)) = chars.next() {
+        let mut end = haystack.len();
+        while let Some(&(idx, c)) = chars.peek() {
+            if !is_combining_mark(c) {
+                end = idx;
+                break;
+            }
+            chars.next();
+        }
+        let from = normalized.len();
+        normalized.push_str(&normalize_for_search(
+            &haystack[start..end],
+            case_sensitive,
+            ignore_accents,
+        ));
+        clusters.push((from..normalized.len(), start..end));
+    }
+
+    let mut spans = Vec::new();
+    let mut search_from = 0;
+    while let Some(pos) = normalized[search_from..].find(needle) {
+        let (match_start, match_end) = (search_from + pos, search_from + pos + needle.len());
+        let first = clusters.iter().find(|(n, _)| n.end > match_start);
+        let last = clusters.iter().rev().find(|(n, _)| n.start < match_end);
+        if let (Some((_, first)), Some((_, last))) = (first, last) {
+            spans.push(first.start..last.end);
+        }
+        search_from = match_end;
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_case() {
+        assert_eq!(fold_case("Straße"), fold_case("STRASSE"));
+        assert_eq!(fold_case("ΣΊΣΥΦΟΣ"), fold_case("σίσυφος"));
+        assert_eq!(fold_case("İstanbul"), "istanbul");
+        assert_eq!(fold_case("I\u{307}STANBUL"), "istanbul");
+        assert_eq!(fold_case("Diyarbakır"), "diyarbakir");
+    }
+
+    #[test]
+    fn test_strip_accents() {
+        assert_eq!(strip_accents("München"), "Munchen");
+        assert_eq!(strip_accents("Crème brûlée"), "Creme brulee");
+        assert_eq!(strip_accents("ıstanbul"), "istanbul");
+        assert_eq!(strip_accents("Łódź"), "Lodz");
+    }
+
+    #[test]
+    fn test_normalize_for_search() {
+        assert_eq!(normalize_for_search("MÜNCHEN", false, true), "munchen");
+        assert_eq!(normalize_for_search("İstanbul", false, true), "istanbul");
+        assert_eq!(normalize_for_search("MÜNCHEN", true, false), "MÜNCHEN");
+        // Decomposed input is recomposed when accents are kept
+        assert_eq!(
+            normalize_for_search("Mu\u{308}nchen", false, false),
+            "münchen"
+        );
+    }
+
+    #[test]
+    fn test_match_spans() {
+        assert_eq!(match_spans("John Doe", "doe", false, false), vec![5..8]);
+        assert_eq!(match_spans("abcabc", "bc", true, false), vec![1..3, 4..6]);
+        // Spans point into the original text, accents and all
+        let text = "Café München";
+        let spans = match_spans(text, "munchen", false, true);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(&text[spans[0].clone()], "München");
+        // Case folding can change lengths: "ß" folds to "ss"
+        let text = "Straße 5";
+        let spans = match_spans(text, "strasse", false, false);
+        assert_eq!(&text[spans[0].clone()], "Straße");
+        assert!(match_spans("abc", "x", false, false).is_empty());
+        // Turkish dotted capital I without --ignore-accents
+        let text = "İstanbul";
+        let spans = match_spans(text, "istanbul", false, false);
+        assert_eq!(&text[spans[0].clone()], "İstanbul");
+    }
+}
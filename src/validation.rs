@@ -0,0 +1,673 @@
+use crate::address::{parse_range_reference, sheet_cell_reference, RangeReference};
+use crate::export::cell_timestamp;
+use crate::functions::serial_from_date;
+use crate::package::{attribute, Package};
+use crate::{format_cell, read_sheet};
+use anyhow::{Context, Result};
+use calamine::{Data, Range, Reader, Xlsx};
+use chrono::NaiveTime;
+use quick_xml::events::Event;
+use std::io::{Read, Seek};
+
+/// What a data-validation rule allows, from its `type` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationKind {
+    Any,
+    WholeNumber,
+    Decimal,
+    List,
+    Date,
+    Time,
+    TextLength,
+    Custom,
+}
+
+impl ValidationKind {
+    fn parse(kind: &str) -> Self {
+        match kind {
+            "whole" => ValidationKind::WholeNumber,
+            "decimal" => ValidationKind::Decimal,
+            "list" => ValidationKind::List,
+            "date" => ValidationKind::Date,
+            "time" => ValidationKind::Time,
+            "textLength" => ValidationKind::TextLength,
+            "custom" => ValidationKind::Custom,
+            _ => ValidationKind::Any,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ValidationKind::Any => "any value",
+            ValidationKind::WholeNumber => "whole number",
+            ValidationKind::Decimal => "decimal",
+            ValidationKind::List => "list",
+            ValidationKind::Date => "date",
+            ValidationKind::Time => "time",
+            ValidationKind::TextLength => "text length",
+            ValidationKind::Custom => "custom formula",
+        }
+    }
+}
+
+/// How a value is compared with the rule's bounds; `Between` when unset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Between,
+    NotBetween,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+impl Operator {
+    fn parse(operator: &str) -> Self {
+        match operator {
+            "notBetween" => Operator::NotBetween,
+            "equal" => Operator::Equal,
+            "notEqual" => Operator::NotEqual,
+            "lessThan" => Operator::LessThan,
+            "lessThanOrEqual" => Operator::LessThanOrEqual,
+            "greaterThan" => Operator::GreaterThan,
+            "greaterThanOrEqual" => Operator::GreaterThanOrEqual,
+            _ => Operator::Between,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Operator::Between => "between",
+            Operator::NotBetween => "not between",
+            Operator::Equal => "equal to",
+            Operator::NotEqual => "not equal to",
+            Operator::LessThan => "less than",
+            Operator::LessThanOrEqual => "at most",
+            Operator::GreaterThan => "greater than",
+            Operator::GreaterThanOrEqual => "at least",
+        }
+    }
+
+    fn takes_two(self) -> bool {
+        matches!(self, Operator::Between | Operator::NotBetween)
+    }
+
+    fn allows(self, value: f64, first: f64, second: f64) -> bool {
+        match self {
+            Operator::Between => value >= first && value <= second,
+            Operator::NotBetween => value < first || value > second,
+            Operator::Equal => value == first,
+            Operator::NotEqual => value != first,
+            Operator::LessThan => value < first,
+            Operator::LessThanOrEqual => value <= first,
+            Operator::GreaterThan => value > first,
+            Operator::GreaterThanOrEqual => value >= first,
+        }
+    }
+}
+
+/// A data-validation rule and the cells it applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationRule {
+    /// Applied ranges as written, e.g. "B2:B100 D2".
+    pub sqref: String,
+    pub ranges: Vec<RangeReference>,
+    pub kind: ValidationKind,
+    pub operator: Operator,
+    pub formula1: Option<String>,
+    pub formula2: Option<String>,
+    pub allow_blank: bool,
+    pub error_title: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ValidationRule {
+    /// Options of a list rule written out in the rule itself (`"Yes,No"`).
+    pub fn list_options(&self) -> Option<Vec<String>> {
+        let formula = self.formula1.as_deref()?;
+        let options = formula.strip_prefix('"')?.strip_suffix('"')?;
+        Some(
+            options
+                .replace("\"\"", "\"")
+                .split(',')
+                .map(|option| option.trim().to_string())
+                .collect(),
+        )
+    }
+
+    /// Human-readable summary, e.g. "whole number between 1 and 10".
+    pub fn describe(&self) -> String {
+        let first = self.formula1.as_deref().unwrap_or("?");
+        let mut description = match self.kind {
+            ValidationKind::Any => self.kind.name().to_string(),
+            ValidationKind::List => match self.list_options() {
+                Some(options) => format!("list: {}", options.join(", ")),
+                None => format!("list from {}", first),
+            },
+            ValidationKind::Custom => format!("custom formula: ={}", first),
+            kind if self.operator.takes_two() => format!(
+                "{} {} {} and {}",
+                kind.name(),
+                self.operator.name(),
+                first,
+                self.formula2.as_deref().unwrap_or("?")
+            ),
+            kind => format!("{} {} {}", kind.name(), self.operator.name(), first),
+        };
+        match (&self.error_title, &self.error) {
+            (Some(title), Some(error)) => {
+                description.push_str(&format!(" (error: {}: {})", title, error))
+            }
+            (None, Some(error)) | (Some(error), None) => {
+                description.push_str(&format!(" (error: {})", error))
+            }
+            (None, None) => {}
+        }
+        description
+    }
+
+    fn applies_to(&self, row: usize, col: usize) -> bool {
+        self.ranges.iter().any(|range| range.contains(row, col))
+    }
+}
+
+/// Read the data-validation rules of a sheet, including the extended ones
+/// Excel writes for lists that refer to other sheets.
+pub fn read_validations(package: &mut Package, sheet_name: &str) -> Result<Vec<ValidationRule>> {
+    let Some(sheet_part) = package.sheet_part(sheet_name)? else {
+        return Ok(Vec::new());
+    };
+    let Some(xml) = package.read_part(&sheet_part)? else {
+        return Ok(Vec::new());
+    };
+    parse_validations(&xml).with_context(|| format!("Malformed data validation in {}", sheet_part))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Formula1,
+    Formula2,
+    Sqref,
+}
+
+fn parse_validations(xml: &str) -> Result<Vec<ValidationRule>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut rules = Vec::new();
+    let mut current: Option<ValidationRule> = None;
+    let mut field: Option<Field> = None;
+
+    loop {
+        let (element, is_empty) = match reader.read_event()? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::Text(text) => {
+                if let (Some(rule), Some(field)) = (&mut current, field) {
+                    let text = text.unescape()?;
+                    let target = match field {
+                        Field::Formula1 => rule.formula1.get_or_insert_with(String::new),
+                        Field::Formula2 => rule.formula2.get_or_insert_with(String::new),
+                        Field::Sqref => &mut rule.sqref,
+                    };
+                    target.push_str(&text);
+                }
+                continue;
+            }
+            Event::End(element) => {
+                match element.local_name().as_ref() {
+                    b"formula1" | b"formula2" | b"sqref" => field = None,
+                    b"dataValidation" => {
+                        if let Some(rule) = current.take() {
+                            rules.push(finish_rule(rule));
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        match element.local_name().as_ref() {
+            b"dataValidation" => {
+                let flag = |name: &[u8]| -> Result<bool> {
+                    Ok(matches!(
+                        attribute(&element, name)?.as_deref(),
+                        Some("1" | "true")
+                    ))
+                };
+                let rule = ValidationRule {
+                    sqref: attribute(&element, b"sqref")?.unwrap_or_default(),
+                    ranges: Vec::new(),
+                    kind: ValidationKind::parse(&attribute(&element, b"type")?.unwrap_or_default()),
+                    operator: Operator::parse(
+                        &attribute(&element, b"operator")?.unwrap_or_default(),
+                    ),
+                    formula1: None,
+                    formula2: None,
+                    allow_blank: flag(b"allowBlank")?,
+                    error_title: attribute(&element, b"errorTitle")?.filter(|t| !t.is_empty()),
+                    error: attribute(&element, b"error")?.filter(|e| !e.is_empty()),
+                };
+                if is_empty {
+                    rules.push(finish_rule(rule));
+                } else {
+                    current = Some(rule);
+                }
+            }
+            b"formula1" if !is_empty => field = Some(Field::Formula1),
+            b"formula2" if !is_empty => field = Some(Field::Formula2),
+            b"sqref" if !is_empty => field = Some(Field::Sqref),
+            _ => {}
+        }
+    }
+    Ok(rules)
+}
+
+fn finish_rule(mut rule: ValidationRule) -> ValidationRule {
+    rule.sqref = rule.sqref.trim().to_string();
+    rule.ranges = rule
+        .sqref
+        .split_whitespace()
+        .filter_map(parse_range_reference)
+        .collect();
+    rule
+}
+
+/// A cell whose value a rule doesn't allow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub sheet: String,
+    pub row: usize,
+    pub col: usize,
+    pub value: String,
+    /// Applied range of the broken rule.
+    pub sqref: String,
+    pub rule: String,
+}
+
+impl Violation {
+    pub fn reference(&self) -> String {
+        sheet_cell_reference(&self.sheet, self.row, self.col)
+    }
+}
+
+/// Result of checking one sheet against its rules.
+#[derive(Debug, Default)]
+pub struct SheetCheck {
+    /// Non-empty cells covered by at least one rule that could be checked.
+    pub checked: usize,
+    /// Rules that can't be checked without evaluating formulas.
+    pub skipped_rules: usize,
+    pub violations: Vec<Violation>,
+}
+
+/// What a checkable rule compares values against.
+enum Constraint {
+    Options(Vec<String>),
+    Bounds(f64, f64),
+    Anything,
+}
+
+/// Check the non-empty cells of a sheet against every rule covering them.
+/// Bounds and list sources must be constants, ranges or defined names; rules
+/// that need a formula evaluated are counted as skipped.
+pub fn check_sheet<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+    rules: &[ValidationRule],
+) -> Result<SheetCheck> {
+    let mut check = SheetCheck::default();
+    let mut constraints = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let constraint = constraint(workbook, sheet_name, rule)?;
+        if constraint.is_none() {
+            check.skipped_rules += 1;
+        }
+        constraints.push(constraint);
+    }
+    if constraints.iter().all(Option::is_none) {
+        return Ok(check);
+    }
+
+    let range = read_sheet(workbook, sheet_name)?;
+    let (origin_row, origin_col) = range.start().unwrap_or_default();
+    for (row, col, value) in range.used_cells() {
+        if matches!(value, Data::Empty) {
+            continue;
+        }
+        let (row, col) = (row + origin_row as usize, col + origin_col as usize);
+        let mut covered = false;
+        for (rule, constraint) in rules.iter().zip(&constraints) {
+            let Some(constraint) = constraint.as_ref().filter(|_| rule.applies_to(row, col)) else {
+                continue;
+            };
+            covered = true;
+            if !allows(rule.kind, rule.operator, constraint, value) {
+                check.violations.push(Violation {
+                    sheet: sheet_name.to_string(),
+                    row,
+                    col,
+                    value: format_cell(value),
+                    sqref: rule.sqref.clone(),
+                    rule: rule.describe(),
+                });
+            }
+        }
+        if covered {
+            check.checked += 1;
+        }
+    }
+    Ok(check)
+}
+
+fn constraint<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+    rule: &ValidationRule,
+) -> Result<Option<Constraint>> {
+    let number = |formula: &Option<String>| formula.as_deref().and_then(|f| f.trim().parse().ok());
+    Ok(match rule.kind {
+        ValidationKind::Any => Some(Constraint::Anything),
+        ValidationKind::Custom => None,
+        ValidationKind::List => match rule.list_options() {
+            Some(options) => Some(Constraint::Options(options)),
+            None => list_source(workbook, sheet_name, rule.formula1.as_deref().unwrap_or(""))?
+                .map(Constraint::Options),
+        },
+        _ => match (number(&rule.formula1), number(&rule.formula2)) {
+            (Some(first), Some(second)) => Some(Constraint::Bounds(first, second)),
+            (Some(first), None) if !rule.operator.takes_two() => {
+                Some(Constraint::Bounds(first, first))
+            }
+            _ => None,
+        },
+    })
+}
+
+/// Values of the range (or defined name holding a range) a list rule reads
+/// its options from.
+fn list_source<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheet_name: &str,
+    formula: &str,
+) -> Result<Option<Vec<String>>> {
+    let formula = formula.trim().trim_start_matches('=');
+    let reference = parse_range_reference(formula).or_else(|| {
+        workbook
+            .defined_names()
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(formula))
+            .and_then(|(_, target)| parse_range_reference(target.trim_start_matches('=')))
+    });
+    let Some(reference) = reference else {
+        return Ok(None);
+    };
+
+    let source_sheet = reference.sheet.as_deref().unwrap_or(sheet_name);
+    if !workbook
+        .sheet_names()
+        .iter()
+        .any(|name| name == source_sheet)
+    {
+        return Ok(None);
+    }
+    let range: Range<Data> = read_sheet(workbook, source_sheet)?;
+    let mut options = Vec::new();
+    for row in reference.start.0..=reference.end.0 {
+        for col in reference.start.1..=reference.end.1 {
+            if let Some(value) = range.get_value((row as u32, col as u32)) {
+                if !matches!(value, Data::Empty) {
+                    options.push(format_cell(value));
+                }
+            }
+        }
+    }
+    Ok(Some(options))
+}
+
+fn allows(kind: ValidationKind, operator: Operator, constraint: &Constraint, value: &Data) -> bool {
+    match constraint {
+        Constraint::Anything => true,
+        // List entries are matched without regard to case, as Excel does
+        Constraint::Options(options) => {
+            let text = format_cell(value).to_lowercase();
+            options.iter().any(|option| option.to_lowercase() == text)
+        }
+        Constraint::Bounds(first, second) => {
+            let measured = match kind {
+                ValidationKind::TextLength => Some(format_cell(value).chars().count() as f64),
+                _ => match value {
+                    Data::Int(i) => Some(*i as f64),
+                    Data::Float(f) => Some(*f),
+                    Data::DateTime(dt) => Some(dt.as_f64()),
+                    Data::DateTimeIso(_) => cell_timestamp(value).map(|timestamp| {
+                        let time = timestamp.time() - NaiveTime::MIN;
+                        serial_from_date(timestamp.date())
+                            + time.num_milliseconds() as f64 / 86_400_000.0
+                    }),
+                    _ => None,
+                },
+            };
+            let Some(measured) = measured else {
+                return false;
+            };
+            if kind == ValidationKind::WholeNumber && measured.fract() != 0.0 {
+                return false;
+            }
+            operator.allows(measured, *first, *second)
+        }
+    }
+}
+
+/// Print a sheet's rules as `range: description`.
+pub fn print_validations(sheet_name: &str, rules: &[ValidationRule]) {
+    println!("Validation rules in sheet: {}", sheet_name);
+    if rules.is_empty() {
+        println!("No validation rules found.");
+        return;
+    }
+    for rule in rules {
+        println!("  {}: {}", rule.sqref, rule.describe());
+    }
+}
+
+pub fn print_violations(check: &SheetCheck) {
+    for violation in &check.violations {
+        println!(
+            "  {}: {} violates {} ({})",
+            violation.reference(),
+            violation.value,
+            violation.sqref,
+            violation.rule
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::open_excel_file;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    const SHEET_XML: &str = r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:x14="http://schemas.microsoft.com/office/spreadsheetml/2009/9/main" xmlns:xm="http://schemas.microsoft.com/office/excel/2006/main">
+  <sheetData/>
+  <dataValidations count="3">
+    <dataValidation type="list" allowBlank="1" showErrorMessage="1" error="Pick Yes or No" sqref="B2:B100"><formula1>"Yes,No"</formula1></dataValidation>
+    <dataValidation type="whole" operator="between" sqref="C2:C100 E2"><formula1>1</formula1><formula2>10</formula2></dataValidation>
+    <dataValidation type="textLength" operator="lessThanOrEqual" sqref="D2:D100"><formula1>5</formula1></dataValidation>
+  </dataValidations>
+  <extLst><ext><x14:dataValidations count="1"><x14:dataValidation type="list"><x14:formula1><xm:f>Lookups!$A$1:$A$3</xm:f></x14:formula1><xm:sqref>F2:F50</xm:sqref></x14:dataValidation></x14:dataValidations></ext></extLst>
+</worksheet>"#;
+
+    #[test]
+    fn test_parse_validations() {
+        let rules = parse_validations(SHEET_XML).unwrap();
+        let summary: Vec<(String, String)> = rules
+            .iter()
+            .map(|rule| (rule.sqref.clone(), rule.describe()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "B2:B100".to_string(),
+                    "list: Yes, No (error: Pick Yes or No)".to_string()
+                ),
+                (
+                    "C2:C100 E2".to_string(),
+                    "whole number between 1 and 10".to_string()
+                ),
+                ("D2:D100".to_string(), "text length at most 5".to_string()),
+                (
+                    "F2:F50".to_string(),
+                    "list from Lookups!$A$1:$A$3".to_string()
+                ),
+            ]
+        );
+        assert!(rules[0].allow_blank);
+        assert_eq!(rules[1].ranges.len(), 2);
+        assert!(rules[1].applies_to(1, 4));
+    }
+
+    #[test]
+    fn test_allows() {
+        let options = Constraint::Options(vec!["Yes".to_string(), "No".to_string()]);
+        let list = |value: &str| {
+            allows(
+                ValidationKind::List,
+                Operator::Between,
+                &options,
+                &Data::String(value.to_string()),
+            )
+        };
+        assert!(list("yes"));
+        assert!(!list("Maybe"));
+
+        let bounds = Constraint::Bounds(1.0, 10.0);
+        let whole = |value: Data| {
+            allows(
+                ValidationKind::WholeNumber,
+                Operator::Between,
+                &bounds,
+                &value,
+            )
+        };
+        assert!(whole(Data::Float(3.0)));
+        assert!(!whole(Data::Float(3.5)));
+        assert!(!whole(Data::Int(11)));
+        assert!(!whole(Data::String("three".to_string())));
+
+        let length = Constraint::Bounds(5.0, 5.0);
+        assert!(!allows(
+            ValidationKind::TextLength,
+            Operator::LessThanOrEqual,
+            &length,
+            &Data::String("too long".to_string()),
+        ));
+
+        // Dates in 2024, written as serials or as ISO 8601 text
+        let year = Constraint::Bounds(45292.0, 45657.0);
+        let date = |value: &str| {
+            allows(
+                ValidationKind::Date,
+                Operator::Between,
+                &year,
+                &Data::DateTimeIso(value.to_string()),
+            )
+        };
+        assert!(date("2024-01-15"));
+        assert!(date("2024-12-30T12:00:00"));
+        assert!(!date("2024-12-31T12:00:00"));
+        assert!(!date("2023-12-31"));
+        assert!(!date("not a date"));
+    }
+
+    /// Copy a workbook, adding `rules` to the first sheet. xlsxwriter has no
+    /// simple way to write data validation.
+    fn with_validations(workbook: &NamedTempFile, rules: &str) -> NamedTempFile {
+        use zip::write::SimpleFileOptions;
+        use zip::{ZipArchive, ZipWriter};
+
+        let mut archive = ZipArchive::new(File::open(workbook.path()).unwrap()).unwrap();
+        let output = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let mut writer = ZipWriter::new(File::create(output.path()).unwrap());
+        for idx in 0..archive.len() {
+            let mut part = archive.by_index(idx).unwrap();
+            if part.name() != "xl/worksheets/sheet1.xml" {
+                writer.raw_copy_file(part).unwrap();
+                continue;
+            }
+            let mut xml = String::new();
+            part.read_to_string(&mut xml).unwrap();
+            let xml = xml.replacen("</sheetData>", &format!("</sheetData>{}", rules), 1);
+            writer
+                .start_file(part.name(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(xml.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+        output
+    }
+
+    #[test]
+    fn test_check_sheet() {
+        use xlsxwriter::*;
+
+        let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+        let workbook = Workbook::new(temp_file.path().to_str().unwrap()).unwrap();
+        let mut intake = workbook.add_worksheet(Some("Intake")).unwrap();
+        for (col, header) in ["Name", "Consent", "Score", "Region"].iter().enumerate() {
+            intake.write_string(0, col as u16, header, None).unwrap();
+        }
+        intake.write_string(1, 0, "Ada", None).unwrap();
+        intake.write_string(1, 1, "yes", None).unwrap();
+        intake.write_number(1, 2, 5.0, None).unwrap();
+        intake.write_string(1, 3, "South", None).unwrap();
+        intake.write_string(2, 0, "Grace", None).unwrap();
+        intake.write_string(2, 1, "Maybe", None).unwrap();
+        intake.write_number(2, 2, 12.0, None).unwrap();
+        intake.write_string(2, 3, "West", None).unwrap();
+        let mut lookups = workbook.add_worksheet(Some("Lookups")).unwrap();
+        lookups.write_string(0, 0, "North", None).unwrap();
+        lookups.write_string(1, 0, "South", None).unwrap();
+        workbook.close().unwrap();
+
+        let rules = r#"<dataValidations count="5">
+<dataValidation type="list" sqref="B2:B10"><formula1>"Yes,No"</formula1></dataValidation>
+<dataValidation type="whole" sqref="C2:C10"><formula1>1</formula1><formula2>10</formula2></dataValidation>
+<dataValidation type="list" sqref="D2:D10"><formula1>Lookups!$A$1:$A$2</formula1></dataValidation>
+<dataValidation type="custom" sqref="A2:A10"><formula1>LEN(A2)&gt;1</formula1></dataValidation>
+<dataValidation type="decimal" operator="lessThan" sqref="C2"><formula1>5</formula1></dataValidation>
+</dataValidations>"#;
+        let validated = with_validations(&temp_file, rules);
+
+        let mut package = Package::new(File::open(validated.path()).unwrap()).unwrap();
+        let rules = read_validations(&mut package, "Intake").unwrap();
+        assert_eq!(rules.len(), 5);
+
+        let mut workbook = open_excel_file(validated.path()).unwrap();
+        let check = check_sheet(&mut workbook, "Intake", &rules).unwrap();
+        assert_eq!(check.checked, 6);
+        assert_eq!(check.skipped_rules, 1);
+        let broken: Vec<(String, String)> = check
+            .violations
+            .iter()
+            .map(|violation| (violation.reference(), violation.value.clone()))
+            .collect();
+        assert_eq!(
+            broken,
+            vec![
+                // C2 is within its column's rule but not the second one
+                ("Intake!C2".to_string(), "5".to_string()),
+                ("Intake!B3".to_string(), "Maybe".to_string()),
+                ("Intake!C3".to_string(), "12".to_string()),
+                ("Intake!D3".to_string(), "West".to_string()),
+            ]
+        );
+    }
+}
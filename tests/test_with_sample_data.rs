@@ -68,6 +68,53 @@ fn test_sheets_command() {
     assert!(stdout.contains("Products"));
 }
 
+#[test]
+fn test_sheets_command_visibility() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut report = workbook.add_worksheet(Some("Report")).unwrap();
+    report.write_string(0, 0, "Region", None).unwrap();
+    report.write_string(1, 0, "North", None).unwrap();
+    let mut lookups = workbook.add_worksheet(Some("Lookups")).unwrap();
+    lookups.write_string(0, 0, "key", None).unwrap();
+    lookups.hide();
+    let mut archive = workbook.add_worksheet(Some("Archive")).unwrap();
+    archive.write_string(0, 0, "old", None).unwrap();
+    workbook.close().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "sheets"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("  0: Report (worksheet, visible) A1:A2, 2 rows x 1 columns"));
+    assert!(stdout.contains("  1: Lookups (worksheet, hidden) A1, 1 rows x 1 columns"));
+
+    // Hidden sheets are left out but the others keep their index
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "sheets", "--visible-only"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("Lookups"));
+    assert!(stdout.contains("  2: Archive"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "sheets", "--json"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let sheets: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(sheets[0]["kind"], "worksheet");
+    assert_eq!(sheets[0]["dimensions"]["range"], "A1:A2");
+    assert_eq!(sheets[0]["dimensions"]["rows"], 2);
+    assert_eq!(sheets[1]["name"], "Lookups");
+    assert_eq!(sheets[1]["visibility"], "hidden");
+}
+
 #[test]
 fn test_show_command_default() {
     let temp_file = create_sample_excel().expect("Failed to create test file");
@@ -126,6 +173,24 @@ fn test_show_command_with_sheet_index() {
     assert!(row_lines.len() <= 2);
 }
 
+#[test]
+fn test_show_command_reports_remaining_rows() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "show", "-r", "2"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Dimensions: 4 rows x 4 columns"));
+    assert!(stdout.contains("Row 2: 1 | John Doe | Engineering | 85000"));
+    assert!(!stdout.contains("Jane Smith"));
+    assert!(stdout.contains("... and 2 more rows"));
+}
+
 #[test]
 fn test_search_command_case_insensitive() {
     let temp_file = create_sample_excel().expect("Failed to create test file");
@@ -161,7 +226,7 @@ fn test_search_command_case_sensitive() {
 
     let stdout = String::from_utf8(output.stdout).unwrap();
     assert!(stdout.contains("Searching for 'Engineering'"));
-    assert!(stdout.contains("***Engineering***")); // Should highlight matches
+    assert!(stdout.contains("1 | John Doe | Engineering | 85000"));
     assert!(stdout.contains("Found 2 matching row(s)"));
 }
 
@@ -175,7 +240,8 @@ fn test_search_command_no_matches() {
         .output()
         .expect("Failed to execute command");
 
-    assert!(output.status.success());
+    // grep-style: a search without matches exits with 1
+    assert_eq!(output.status.code(), Some(1));
     let stdout = String::from_utf8(output.stdout).unwrap();
     assert!(stdout.contains("No matches found"));
 }
@@ -195,7 +261,7 @@ fn test_search_in_specific_sheet() {
     assert!(output.status.success());
     let stdout = String::from_utf8(output.stdout).unwrap();
     assert!(stdout.contains("Searching for 'Laptop' in sheet: Products"));
-    assert!(stdout.contains("***Laptop***"));
+    assert!(stdout.contains("Products!A2 (Product): Laptop"));
     assert!(stdout.contains("Found 1 matching row(s)"));
 }
 
@@ -210,6 +276,7 @@ fn test_invalid_sheet_name() {
         .expect("Failed to execute command");
 
     assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(2));
     let stderr = String::from_utf8(output.stderr).unwrap();
     assert!(stderr.contains("not found"));
 }
@@ -225,6 +292,891 @@ fn test_invalid_sheet_index() {
         .expect("Failed to execute command");
 
     assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(2));
     let stderr = String::from_utf8(output.stderr).unwrap();
     assert!(stderr.contains("out of range"));
 }
+
+#[test]
+fn test_export_all_sheets() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+    let out_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let out_path = out_dir.path().to_str().unwrap();
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            file_path,
+            "export",
+            "--all-sheets",
+            "-o",
+            out_path,
+            "--name-template",
+            "book_{sheet}.csv",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Exported 2 sheet(s)"));
+
+    let employees = std::fs::read_to_string(out_dir.path().join("book_Employees.csv")).unwrap();
+    assert!(employees.starts_with("ID,Name,Department,Salary\n"));
+    assert!(employees.contains("1,John Doe,Engineering,85000"));
+    assert!(out_dir.path().join("book_Products.csv").exists());
+
+    // A second run must refuse to overwrite without --force
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            file_path,
+            "export",
+            "--all-sheets",
+            "-o",
+            out_path,
+            "--name-template",
+            "book_{sheet}.csv",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--force"));
+}
+
+#[test]
+fn test_export_single_sheet_json() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+    let out_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let out_file = out_dir.path().join("products.json");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            file_path,
+            "export",
+            "-s",
+            "Products",
+            "-o",
+            out_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let json = std::fs::read_to_string(&out_file).unwrap();
+    assert!(json.contains("\"Product\": \"Laptop\""));
+    assert!(json.contains("\"Price\": 1200"));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_to_sqlite_command() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+    let out_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let db_path = out_dir.path().join("book.db");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "to-sqlite",
+            file_path,
+            db_path.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Wrote sheet 'Employees' to table 'Employees' (3 rows)"));
+
+    // A run that fails on the second sheet leaves the existing database
+    // alone, even with --force, and cleans up after itself
+    let broken = NamedTempFile::with_suffix(".xlsx").unwrap();
+    {
+        use std::io::{Read, Write};
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(file_path).unwrap()).unwrap();
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(broken.path()).unwrap());
+        for idx in 0..archive.len() {
+            let mut part = archive.by_index(idx).unwrap();
+            if part.name() != "xl/worksheets/sheet2.xml" {
+                writer.raw_copy_file(part).unwrap();
+                continue;
+            }
+            let mut xml = String::new();
+            part.read_to_string(&mut xml).unwrap();
+            writer
+                .start_file(part.name(), zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer
+                .write_all(xml.replacen("<c ", "<c r=\"!\" ", 1).as_bytes())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", broken.path().to_str().unwrap()])
+        .args(["to-sqlite", "--force", db_path.to_str().unwrap()])
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Could not read sheet: Products"));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Wrote sheet 'Employees'"));
+    let leftovers: Vec<_> = std::fs::read_dir(out_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(leftovers, vec!["book.db"]);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let total: i64 = conn
+        .query_row(
+            "SELECT SUM(Salary) FROM Employees WHERE Department = 'Engineering'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(total, 175000);
+
+    let tables: Vec<String> = conn
+        .prepare("SELECT table_name FROM _sheets ORDER BY sheet_index")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|name| name.unwrap())
+        .collect();
+    assert_eq!(tables, vec!["Employees", "Products"]);
+}
+
+#[test]
+fn test_info_command() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut summary = workbook.add_worksheet(Some("Summary")).unwrap();
+    summary.write_string(0, 0, "Total", None).unwrap();
+    summary.write_number(1, 0, 10.0, None).unwrap();
+    summary.write_formula(1, 1, "=A2*2", None).unwrap();
+    summary.merge_range(2, 0, 2, 1, "Footer", None).unwrap();
+    let mut lookups = workbook.add_worksheet(Some("Lookups")).unwrap();
+    lookups.write_string(0, 0, "key", None).unwrap();
+    lookups.hide();
+    workbook.define_name("Rate", "=Lookups!$A$1").unwrap();
+    workbook.close().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "info"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Application: Microsoft Excel"));
+    assert!(stdout.contains("Title: (not set)"));
+    assert!(stdout.contains("Sheets: 2 (1 visible, 1 hidden, 0 very hidden)"));
+    assert!(stdout.contains("  0: Summary (worksheet, visible) A1:B3, 3 rows x 2 columns"));
+    assert!(stdout.contains("  1: Lookups (worksheet, hidden) A1, 1 rows x 1 columns"));
+    assert!(stdout.contains("Formulas: 1"));
+    assert!(stdout.contains("Merged regions: 1"));
+    assert!(stdout.contains("Defined names: 1"));
+}
+
+#[test]
+fn test_links_command() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut docs = workbook.add_worksheet(Some("Docs")).unwrap();
+    docs.write_url(0, 0, "https://example.com/guide", None)
+        .unwrap();
+    docs.write_url(1, 0, "internal:Data!B2", None).unwrap();
+    docs.write_url(2, 0, "internal:Archive!A1", None).unwrap();
+    let mut data = workbook.add_worksheet(Some("Data")).unwrap();
+    data.write_string(0, 0, "value", None).unwrap();
+    workbook.close().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "links"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Docs!A1: "));
+    assert!(stdout.contains("-> https://example.com/guide (url)"));
+    assert!(stdout.contains("Docs!A2: "));
+    assert!(stdout.contains("-> Data!B2 (internal)"));
+
+    // The link to the missing Archive sheet is reported and fails the check
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "links", "--check-internal"])
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Broken: Docs!A3 -> Archive!A1: sheet 'Archive' does not exist"));
+    assert!(!stdout.contains("Data!B2:"));
+    assert!(stdout.contains("2 internal link(s) checked, 1 broken"));
+}
+
+#[test]
+fn test_comments_command() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut audit = workbook.add_worksheet(Some("Audit")).unwrap();
+    audit.write_string(0, 0, "Item", None).unwrap();
+    audit.write_string(0, 1, "Amount", None).unwrap();
+    audit.write_string(1, 0, "Travel", None).unwrap();
+    audit.write_number(1, 1, 420.0, None).unwrap();
+    audit
+        .write_comment(1, 1, "Receipt missing for the taxi")
+        .unwrap();
+    audit.write_comment(0, 0, "Checked by audit").unwrap();
+    workbook.close().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "comments", "-s", "Audit"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Comments in sheet: Audit"));
+    let first = stdout.find("A1").expect("A1 comment listed");
+    let second = stdout.find("B2").expect("B2 comment listed");
+    assert!(first < second);
+    assert!(stdout.contains("Receipt missing for the taxi"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "show", "--with-comments"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Row 1: Item [1] | Amount"));
+    assert!(stdout.contains("Row 2: Travel | 420 [2]"));
+    assert!(stdout.contains("Comments:"));
+    assert!(stdout.contains("[2] B2"));
+
+    // Without the flag nothing is marked
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "show"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("[1]"));
+}
+
+#[test]
+fn test_cell_command() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut pay = workbook.add_worksheet(Some("Employees")).unwrap();
+    pay.write_string(0, 0, "Name", None).unwrap();
+    pay.write_number(2, 2, 1200.0, None).unwrap();
+    pay.write_formula_num(2, 3, "=C3*12", None, 14400.0)
+        .unwrap();
+    pay.write_comment(2, 3, "Annual salary").unwrap();
+    pay.write_url(3, 0, "https://example.com/payroll", None)
+        .unwrap();
+    workbook.close().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "cell", "Employees!D3"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Cell: Employees!D3"));
+    assert!(stdout.contains("Type: Float"));
+    assert!(stdout.contains("Formatted value: 14400"));
+    assert!(stdout.contains("Displayed by xlsq: 14400"));
+    assert!(stdout.contains("Formula: =C3*12"));
+    assert!(stdout.contains("Number format: General (id 0)"));
+    assert!(stdout.contains("Annual salary"));
+    assert!(stdout.contains("Hyperlink: (none)"));
+
+    // Without a sheet the first one is used
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "cell", "A4"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Hyperlink: https://example.com/payroll (url)"));
+    assert!(stdout.contains("Formula: (none)"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "cell", "Employees!A1"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Type: String"));
+    assert!(stdout.contains("Raw value: String(\"Name\")"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "cell", "Employees!A1:B2"])
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_get_command() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut config = workbook.add_worksheet(Some("Config")).unwrap();
+    config.write_string(0, 0, "host", None).unwrap();
+    config.write_string(0, 1, "db.internal", None).unwrap();
+    config.write_string(1, 0, "port", None).unwrap();
+    config.write_number(1, 1, 5432.0, None).unwrap();
+    config.write_string(2, 0, "user", None).unwrap();
+    config.write_string(2, 1, "report bot", None).unwrap();
+    config
+        .write_string(3, 1, "line one\nline two\tend", None)
+        .unwrap();
+    workbook.close().unwrap();
+
+    let get = |reference: &str| {
+        let output = Command::new("cargo")
+            .args(["run", "--", "-f", file_path, "get", reference])
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(get("Config!B2"), "5432\n");
+    assert_eq!(get("B1"), "db.internal\n");
+    assert_eq!(get("Config!B1:B3"), "db.internal\n5432\nreport bot\n");
+    // Row by row, one value per line
+    assert_eq!(get("Config!A2:B3"), "port\n5432\nuser\nreport bot\n");
+    // Line breaks and tabs inside a value are escaped
+    assert_eq!(get("Config!B4"), "line one\\nline two\\tend\n");
+    // Blank cells print as empty lines
+    assert_eq!(get("Config!C1:C2"), "\n\n");
+}
+
+#[test]
+fn test_show_layouts() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            file_path,
+            "show",
+            "--vertical",
+            "-r",
+            "2",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("-[ Row 2 ]-\nID        : 1\nName      : John Doe\n"));
+    assert!(stdout.contains("Salary    : 75000"));
+    assert!(!stdout.contains("Bob Johnson"));
+    assert!(stdout.contains("... and 1 more rows"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "show", "--transpose"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Column A: ID | 1 | 2 | 3"));
+    assert!(stdout.contains("Column B: Name | John Doe | Jane Smith | Bob Johnson"));
+    assert!(stdout.contains("Column D: Salary | 85000 | 75000 | 90000"));
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            file_path,
+            "show",
+            "--vertical",
+            "--transpose",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_show_command_uses_sheet_row_numbers() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut sheet = workbook.add_worksheet(Some("Report")).unwrap();
+    sheet.write_string(2, 1, "Item", None).unwrap();
+    sheet.write_string(2, 2, "Amount", None).unwrap();
+    sheet.write_string(3, 1, "Travel", None).unwrap();
+    sheet.write_number(3, 2, 420.0, None).unwrap();
+    workbook.close().unwrap();
+
+    let show = |layout: &[&str]| {
+        let output = Command::new("cargo")
+            .args(["run", "--", "-f", file_path, "show"])
+            .args(layout)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let stdout = show(&[]);
+    assert!(stdout.contains("Row 3: Item | Amount\nRow 4: Travel | 420\n"));
+    let stdout = show(&["--vertical"]);
+    assert!(stdout.contains("-[ Row 4 ]-\nItem  : Travel\n"));
+    let stdout = show(&["--transpose"]);
+    assert!(stdout.contains("Row: 3 | 4\nColumn B: Item | Travel\n"));
+}
+
+#[test]
+fn test_calc_command() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut inputs = workbook.add_worksheet(Some("Inputs")).unwrap();
+    inputs.write_string(0, 0, "Discount", None).unwrap();
+    inputs.write_number(0, 1, 0.1, None).unwrap();
+    inputs.write_string(1, 0, "Tier", None).unwrap();
+    inputs.write_string(1, 1, "Gold", None).unwrap();
+    let mut prices = workbook.add_worksheet(Some("Prices")).unwrap();
+    prices.write_string(0, 0, "Silver", None).unwrap();
+    prices.write_number(0, 1, 80.0, None).unwrap();
+    prices.write_string(1, 0, "Gold", None).unwrap();
+    prices.write_number(1, 1, 100.0, None).unwrap();
+    let mut summary = workbook.add_worksheet(Some("Summary")).unwrap();
+    summary
+        .write_formula_num(
+            0,
+            0,
+            "=VLOOKUP(Inputs!B2,Prices!A1:B2,2,FALSE)",
+            None,
+            100.0,
+        )
+        .unwrap();
+    summary
+        .write_formula_num(1, 0, "=ROUND(A1*(1-Inputs!B1),2)", None, 90.0)
+        .unwrap();
+    workbook.close().unwrap();
+
+    let calc = |args: &[&str]| {
+        let output = Command::new("cargo")
+            .args(["run", "--", "-f", file_path, "calc"])
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+
+    assert_eq!(
+        calc(&["--get", "Summary!A2"]),
+        (Some(0), "90\n".to_string())
+    );
+    assert_eq!(
+        calc(&[
+            "--set",
+            "Inputs!B1=25%",
+            "--set",
+            "Inputs!B2=Silver",
+            "--get",
+            "Summary!A1:A2",
+            "--get",
+            "Inputs!B1",
+        ]),
+        (Some(0), "80\n60\n0.25\n".to_string())
+    );
+    // A tier missing from the price list
+    assert_eq!(
+        calc(&["--set", "Inputs!B2=Bronze", "--get", "Summary!A2"]),
+        (Some(0), "#N/A\n".to_string())
+    );
+    assert_eq!(
+        calc(&["--set", "Inputs!B1", "--get", "Summary!A2"]).0,
+        Some(2)
+    );
+}
+
+#[test]
+fn test_deps_command() {
+    let temp_file = NamedTempFile::with_suffix(".xlsx").unwrap();
+    let file_path = temp_file.path().to_str().unwrap();
+    let workbook = Workbook::new(file_path).unwrap();
+    let mut inputs = workbook.add_worksheet(Some("Inputs")).unwrap();
+    inputs.write_number(0, 1, 0.2, None).unwrap();
+    inputs.write_number(1, 1, 500.0, None).unwrap();
+    let mut summary = workbook.add_worksheet(Some("Summary")).unwrap();
+    summary.write_formula(0, 2, "=Inputs!B2*12", None).unwrap();
+    summary
+        .write_formula(1, 2, "=C1*(1-Inputs!B1)", None)
+        .unwrap();
+    workbook.close().unwrap();
+
+    let deps = |args: &[&str]| {
+        let output = Command::new("cargo")
+            .args(["run", "--", "-f", file_path, "deps"])
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        deps(&["Summary!C2", "--precedents"]),
+        "Summary!C2 =C1*(1-Inputs!B1)
+├── Inputs!B1
+└── Summary!C1 =Inputs!B2*12
+    └── Inputs!B2
+"
+    );
+    assert_eq!(
+        deps(&["Inputs!B2", "--dependents"]),
+        "Inputs!B2
+└── Summary!C1 =Inputs!B2*12
+    └── Summary!C2 =C1*(1-Inputs!B1)
+"
+    );
+
+    let dot = deps(&["Summary!C2", "--format", "dot"]);
+    assert!(dot.starts_with("digraph dependencies {"));
+    assert!(dot.contains("\"Summary!C1\" -> \"Summary!C2\";"));
+
+    let graph: serde_json::Value = serde_json::from_str(&deps(&["--format", "json"])).unwrap();
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), 4);
+    assert_eq!(graph["edges"].as_array().unwrap().len(), 3);
+    assert_eq!(graph["nodes"][2]["formula"], "Inputs!B2*12");
+}
+
+#[test]
+fn test_read_workbook_from_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let bytes = std::fs::read(temp_file.path()).unwrap();
+
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "-f", "-", "show", "-s", "Products"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+    child.stdin.take().unwrap().write_all(&bytes).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Sheet: Products"));
+    assert!(stdout.contains("Laptop"));
+}
+
+#[test]
+fn test_search_command_fuzzy() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "search", "Jon Doe", "--fuzzy"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // The closest row is listed first
+    assert!(stdout.contains("Match 1 - Row 2 (score 0.86)"));
+    assert!(stdout.contains("Employees!B2 (Name): John Doe"));
+
+    // An exact search for the typo finds nothing
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "search", "Jon Doe"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_search_command_between() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            file_path,
+            "search",
+            "--between",
+            "80000",
+            "95000",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Searching for values between 80000 and 95000 in sheet: Employees"));
+    assert!(stdout.contains("Employees!D2 (Salary): 85000"));
+    assert!(stdout.contains("Employees!D4 (Salary): 90000"));
+    assert!(stdout.contains("Found 2 matching row(s)"));
+}
+
+#[test]
+fn test_search_command_grep_controls() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+
+    let run = |extra: &[&str]| {
+        Command::new("cargo")
+            .args(["run", "--", "-f", file_path, "search"])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    // Context rows around the match
+    let output = run(&["Marketing", "-C", "1"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Row 2: 1 | John Doe"));
+    assert!(stdout.contains("Match 1 - Row 3: "));
+    assert!(stdout.contains("Row 4: 3 | Bob Johnson"));
+
+    // Count only
+    let output = run(&["Engineering", "--count"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), "Employees: 2");
+
+    // Inverted match selects the header and the marketing row
+    let output = run(&["Engineering", "-v"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Jane Smith"));
+    assert!(!stdout.contains("Bob Johnson"));
+    assert!(stdout.contains("Found 2 matching row(s)"));
+
+    // Stop after the first match
+    let output = run(&["Engineering", "-m", "1"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Found 1 matching row(s)"));
+    assert!(!stdout.contains("Bob Johnson"));
+
+    // List the file only when it has a match
+    let output = run(&["Engineering", "-l"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), file_path);
+
+    let output = run(&["NoSuchValue", "-l"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_search_command_cell_addresses() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "search", "Engineering"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("  Employees!C2 (Department): Engineering"));
+    assert!(stdout.contains("  Employees!C4 (Department): Engineering"));
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            file_path,
+            "search",
+            "Engineering",
+            "--only-matching",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["Employees!C2: Engineering", "Employees!C4: Engineering"]
+    );
+}
+
+#[test]
+fn test_search_command_color() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let file_path = temp_file.path().to_str().unwrap();
+
+    // Only the matched text is colored, not the whole cell
+    let output = Command::new("cargo")
+        .args([
+            "run", "--", "-f", file_path, "search", "doe", "--color", "always",
+        ])
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("John \x1b[1;31mDoe\x1b[0m"));
+
+    // Piped output is plain by default
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", file_path, "search", "doe"])
+        .output()
+        .expect("Failed to execute command");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains('\x1b'));
+    assert!(stdout.contains("1 | John Doe | Engineering"));
+}
+
+#[test]
+fn test_search_command_many_workbooks() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let dir = tempfile::TempDir::new().unwrap();
+    let first = dir.path().join("a.xlsx");
+    let second = dir.path().join("b.xlsx");
+    std::fs::copy(temp_file.path(), &first).unwrap();
+    std::fs::copy(temp_file.path(), &second).unwrap();
+    let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+    // Results come out in file order, then sheet order
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            first,
+            "-f",
+            second,
+            "search",
+            "Electronics",
+            "--all-sheets",
+            "--count",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            format!("{}:Employees: 0", first),
+            format!("{}:Products: 2", first),
+            format!("{}:Employees: 0", second),
+            format!("{}:Products: 2", second),
+        ]
+    );
+
+    // A directory searches every workbook inside it
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "-f",
+            dir.path().to_str().unwrap(),
+            "search",
+            "Bob",
+            "-l",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), vec![first, second]);
+
+    // Other commands still take a single workbook
+    let output = Command::new("cargo")
+        .args(["run", "--", "-f", first, "-f", second, "sheets"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_index_build_and_search() {
+    let temp_file = create_sample_excel().expect("Failed to create test file");
+    let dir = tempfile::TempDir::new().unwrap();
+    let data = dir.path().join("share");
+    std::fs::create_dir(&data).unwrap();
+    let workbook = data.join("staff.xlsx");
+    std::fs::copy(temp_file.path(), &workbook).unwrap();
+    let index = dir.path().join("index.sqlite");
+    let index = index.to_str().unwrap();
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "index",
+            "build",
+            data.to_str().unwrap(),
+            "--index",
+            index,
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Indexed 1 file(s)"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "index", "search", "johnson", "--index", index])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout
+        .trim()
+        .ends_with("staff.xlsx:Employees!B4: Bob Johnson"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "index", "search", "nobody", "--index", index])
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(1));
+}